@@ -0,0 +1,479 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    database::{self, Database, Stream, StreamId, Value},
+    error::MiniRedisError,
+    rdb::{RdbDatabase, RedisString},
+    resp2::Message,
+};
+
+/// When AOF content is flushed to disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    Always,
+    #[default]
+    EverySec,
+    No,
+}
+
+impl FsyncPolicy {
+    pub fn parse(input: &[u8]) -> Result<Self, MiniRedisError> {
+        match input.to_ascii_lowercase().as_slice() {
+            b"always" => Ok(Self::Always),
+            b"everysec" => Ok(Self::EverySec),
+            b"no" => Ok(Self::No),
+            _ => Err(MiniRedisError::InvalidConfig(format!(
+                "Invalid appendfsync: {}",
+                String::from_utf8_lossy(input)
+            ))),
+        }
+    }
+}
+
+pub const DEFAULT_AUTO_REWRITE_PERCENTAGE: u64 = 100;
+pub const DEFAULT_AUTO_REWRITE_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Append only file logging every write command.
+///
+/// State is shared so rewrites can complete in a background task.
+#[derive(Debug, Clone)]
+pub struct Aof {
+    file: Arc<Mutex<Option<fs::File>>>,
+    fsync_policy: Arc<RwLock<FsyncPolicy>>,
+    pending_fsync: Arc<AtomicBool>,
+    /// Database selected by last command written to file.
+    selected_db: Arc<Mutex<Option<usize>>>,
+    /// Commands appended while a rewrite is running.
+    rewrite_buffer: Arc<Mutex<Option<Vec<u8>>>>,
+    current_size: Arc<AtomicU64>,
+    base_size: Arc<AtomicU64>,
+    auto_rewrite_percentage: Arc<AtomicU64>,
+    auto_rewrite_min_size: Arc<AtomicU64>,
+}
+
+impl Default for Aof {
+    fn default() -> Self {
+        Self {
+            file: Default::default(),
+            fsync_policy: Default::default(),
+            pending_fsync: Default::default(),
+            selected_db: Default::default(),
+            rewrite_buffer: Default::default(),
+            current_size: Default::default(),
+            base_size: Default::default(),
+            auto_rewrite_percentage: Arc::new(AtomicU64::new(DEFAULT_AUTO_REWRITE_PERCENTAGE)),
+            auto_rewrite_min_size: Arc::new(AtomicU64::new(DEFAULT_AUTO_REWRITE_MIN_SIZE)),
+        }
+    }
+}
+
+impl Aof {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn is_enabled(&self) -> bool {
+        self.file.lock().await.is_some()
+    }
+
+    pub async fn is_rewrite_in_progress(&self) -> bool {
+        self.rewrite_buffer.lock().await.is_some()
+    }
+
+    pub async fn set_fsync_policy(&self, policy: FsyncPolicy) {
+        *self.fsync_policy.write().await = policy;
+    }
+
+    /// Set growth (in percent of size after last rewrite) triggering an automatic rewrite.
+    ///
+    /// Zero disables automatic rewrites.
+    pub fn set_auto_rewrite_percentage(&self, percentage: u64) {
+        self.auto_rewrite_percentage
+            .store(percentage, Ordering::SeqCst);
+    }
+
+    /// Set minimal file size (in bytes) for automatic rewrites.
+    pub fn set_auto_rewrite_min_size(&self, size: u64) {
+        self.auto_rewrite_min_size.store(size, Ordering::SeqCst);
+    }
+
+    /// Current file size in bytes.
+    pub fn current_size(&self) -> u64 {
+        self.current_size.load(Ordering::SeqCst)
+    }
+
+    /// Start logging commands to an existing file.
+    pub async fn open(&self, path: &Path) -> Result<(), MiniRedisError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let size = file.metadata().await?.len();
+
+        *self.file.lock().await = Some(file);
+        *self.selected_db.lock().await = None;
+        self.current_size.store(size, Ordering::SeqCst);
+        self.base_size.store(size, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Start logging commands to a new file initialized with current database content.
+    pub async fn enable(&self, path: &Path, databases: &[Database]) -> Result<(), MiniRedisError> {
+        // Keep lock while dumping database, so no write is logged twice or lost
+        let mut file = self.file.lock().await;
+        if file.is_some() {
+            return Ok(());
+        }
+
+        let snapshot = database::snapshot_all(databases).await;
+        let mut writer = BufWriter::new(fs::File::create(path).await?);
+        write_dataset(&mut writer, &snapshot).await?;
+        let new_file = writer.into_inner();
+        new_file.sync_all().await?;
+        let size = new_file.metadata().await?.len();
+
+        *file = Some(OpenOptions::new().append(true).open(path).await?);
+        *self.selected_db.lock().await = None;
+        self.current_size.store(size, Ordering::SeqCst);
+        self.base_size.store(size, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Stop logging commands and flush file to disk.
+    pub async fn disable(&self) -> io::Result<()> {
+        if let Some(file) = self.file.lock().await.take() {
+            file.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    /// Log a command applied to given database.
+    pub async fn append(&self, db: usize, command: &Message) -> io::Result<()> {
+        let mut file = self.file.lock().await;
+        let Some(file) = file.as_mut() else {
+            return Ok(());
+        };
+
+        let mut data = Vec::new();
+        let mut selected_db = self.selected_db.lock().await;
+        if *selected_db != Some(db) {
+            data = database::select_command(db).to_bytes().await;
+            *selected_db = Some(db);
+        }
+        data.extend(command.to_bytes().await);
+
+        file.write_all(&data).await?;
+        self.current_size
+            .fetch_add(data.len() as u64, Ordering::SeqCst);
+
+        if let Some(buffer) = self.rewrite_buffer.lock().await.as_mut() {
+            buffer.extend_from_slice(&data);
+        }
+
+        match *self.fsync_policy.read().await {
+            FsyncPolicy::Always => file.sync_data().await?,
+            FsyncPolicy::EverySec => self.pending_fsync.store(true, Ordering::SeqCst),
+            FsyncPolicy::No => {}
+        }
+        Ok(())
+    }
+
+    /// Flush file to disk if commands were appended since last call.
+    ///
+    /// Must be called every second to apply `everysec` policy.
+    pub async fn fsync_if_needed(&self) -> io::Result<()> {
+        if !self.pending_fsync.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        if let Some(file) = self.file.lock().await.as_mut() {
+            file.sync_data().await?;
+        }
+        Ok(())
+    }
+
+    /// Check if file grew enough since last rewrite.
+    pub fn should_rewrite(&self) -> bool {
+        let percentage = self.auto_rewrite_percentage.load(Ordering::SeqCst);
+        let current_size = self.current_size();
+        let base_size = self.base_size.load(Ordering::SeqCst).max(1);
+
+        percentage > 0
+            && current_size >= self.auto_rewrite_min_size.load(Ordering::SeqCst)
+            && (current_size * 100 / base_size).saturating_sub(100) >= percentage
+    }
+
+    /// Rewrite file from database content in a background task.
+    ///
+    /// Returns `None` if a rewrite is already running.
+    pub async fn bgrewrite(
+        &self,
+        path: PathBuf,
+        databases: &[Database],
+    ) -> Option<JoinHandle<Result<(), MiniRedisError>>> {
+        // Start buffering while no command can be appended, so snapshot and buffer match
+        let snapshot = {
+            let _file = self.file.lock().await;
+            let mut buffer = self.rewrite_buffer.lock().await;
+            if buffer.is_some() {
+                return None;
+            }
+            *buffer = Some(Vec::new());
+
+            // Buffer must start by selecting its database
+            *self.selected_db.lock().await = None;
+            database::snapshot_all(databases).await
+        };
+
+        let aof = self.clone();
+        Some(tokio::task::spawn(async move {
+            let result = aof.rewrite(&path, &snapshot).await;
+            if let Err(e) = &result {
+                eprintln!("AOF rewrite error: {e}");
+                aof.rewrite_buffer.lock().await.take();
+            }
+            result
+        }))
+    }
+
+    async fn rewrite(
+        &self,
+        path: &Path,
+        snapshot: &BTreeMap<usize, RdbDatabase>,
+    ) -> Result<(), MiniRedisError> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".rewrite.tmp");
+
+        let mut writer = BufWriter::new(fs::File::create(&tmp_path).await?);
+        write_dataset(&mut writer, snapshot).await?;
+
+        // Swap files while no command can be appended
+        let mut file = self.file.lock().await;
+        if let Some(buffer) = self.rewrite_buffer.lock().await.take() {
+            writer.write_all(&buffer).await?;
+        }
+        writer.flush().await?;
+        let new_file = writer.into_inner();
+        new_file.sync_all().await?;
+        let size = new_file.metadata().await?.len();
+
+        fs::rename(&tmp_path, path).await?;
+        if file.is_some() {
+            *file = Some(OpenOptions::new().append(true).open(path).await?);
+            self.current_size.store(size, Ordering::SeqCst);
+            self.base_size.store(size, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+/// Commands read from an AOF file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AofContent {
+    pub commands: Vec<Message>,
+    /// Length of the file part containing complete commands.
+    pub valid_len: usize,
+    pub truncated: bool,
+}
+
+/// Read commands from AOF file.
+///
+/// An incomplete last command (after a crash while writing) is ignored.
+pub async fn read_aof_file<P: AsRef<Path>>(path: P) -> Result<AofContent, MiniRedisError> {
+    let data = fs::read(path).await?;
+
+    let mut commands = Vec::new();
+    let mut valid_len = 0;
+    while valid_len < data.len() {
+        let mut reader = &data[valid_len..];
+        match Message::read(&mut reader).await {
+            Ok(command) => {
+                valid_len = data.len() - reader.len();
+                commands.push(command);
+            }
+            Err(MiniRedisError::Io(_)) => {
+                return Ok(AofContent {
+                    commands,
+                    valid_len,
+                    truncated: true,
+                });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(AofContent {
+        commands,
+        valid_len,
+        truncated: false,
+    })
+}
+
+/// Write commands recreating the dataset.
+pub async fn write_dataset<W: AsyncWrite + Unpin + Send>(
+    writer: &mut W,
+    databases: &BTreeMap<usize, RdbDatabase>,
+) -> io::Result<()> {
+    for (index, db) in databases {
+        database::select_command(*index).write(writer).await?;
+
+        for (key, value) in &db.values {
+            for command in value_commands(key, value) {
+                command.write(writer).await?;
+            }
+
+            if let Some(expire_at) = db.expiry.get(key) {
+                Message::command(&[
+                    b"PEXPIREAT",
+                    key.as_slice(),
+                    expire_at.to_string().as_bytes(),
+                ])
+                .write(writer)
+                .await?;
+            }
+        }
+    }
+
+    writer.flush().await
+}
+
+/// Commands creating value at key.
+fn value_commands(key: &RedisString, value: &Value) -> Vec<Message> {
+    let command = match value {
+        Value::String(value) => Message::command(&[b"SET", key.as_slice(), value.as_slice()]),
+        Value::List(list) => {
+            let mut args = vec![b"RPUSH".as_slice(), key.as_slice()];
+            args.extend(list.iter().map(RedisString::as_slice));
+            Message::command(&args)
+        }
+        Value::Set(set) => {
+            let mut args = vec![b"SADD".as_slice(), key.as_slice()];
+            args.extend(set.iter().map(RedisString::as_slice));
+            Message::command(&args)
+        }
+        Value::SortedSet(zset) => {
+            let members: Vec<_> = zset
+                .iter()
+                .map(|(member, score)| (database::format_float(score), member))
+                .collect();
+            let mut args = vec![b"ZADD".as_slice(), key.as_slice()];
+            for (score, member) in &members {
+                args.push(score.as_slice());
+                args.push(member.as_slice());
+            }
+            Message::command(&args)
+        }
+        Value::Hash(hash) => {
+            let mut args = vec![b"HSET".as_slice(), key.as_slice()];
+            for (field, value) in hash.iter() {
+                args.push(field.as_slice());
+                args.push(value.as_slice());
+            }
+            Message::command(&args)
+        }
+        Value::Stream(stream) => return stream_commands(key, stream),
+    };
+    vec![command]
+}
+
+/// Commands adding stream entries, then restoring IDs of deleted entries.
+fn stream_commands(key: &RedisString, stream: &Stream) -> Vec<Message> {
+    let mut commands = Vec::with_capacity(stream.entries.len() + 1);
+    for (id, fields) in &stream.entries {
+        let id = id.to_string();
+        let mut args = vec![b"XADD".as_slice(), key.as_slice(), id.as_bytes()];
+        for (field, value) in fields {
+            args.extend([field.as_slice(), value.as_slice()]);
+        }
+        commands.push(Message::command(&args));
+    }
+    // Stream never written to only exists through its groups, created with `MKSTREAM`
+    let written = !stream.entries.is_empty() || stream.last_id != StreamId::MIN;
+    if stream.entries.is_empty() && written {
+        // Empty stream is created with a placeholder entry removed right away
+        let last_id = stream.last_id.to_string();
+        commands.push(Message::command(&[
+            b"XADD",
+            key.as_slice(),
+            b"MAXLEN",
+            b"0",
+            last_id.as_bytes(),
+            b"x",
+            b"y",
+        ]));
+    }
+    if written {
+        commands.push(Message::command(&[
+            b"XSETID",
+            key.as_slice(),
+            stream.last_id.to_string().as_bytes(),
+            b"ENTRIESADDED",
+            stream.entries_added.to_string().as_bytes(),
+            b"MAXDELETEDID",
+            stream.max_deleted_id.to_string().as_bytes(),
+        ]));
+    }
+
+    for (name, group) in &stream.groups {
+        let last_id = group.last_id.to_string();
+        let mut args = vec![
+            b"XGROUP".as_slice(),
+            b"CREATE",
+            key.as_slice(),
+            name.as_slice(),
+            last_id.as_bytes(),
+            b"MKSTREAM",
+        ];
+        let entries_read = group.entries_read.map(|count| count.to_string());
+        if let Some(entries_read) = &entries_read {
+            args.extend([b"ENTRIESREAD".as_slice(), entries_read.as_bytes()]);
+        }
+        commands.push(Message::command(&args));
+
+        // Pending entries removed from stream cannot be claimed again and are lost
+        for (id, entry) in &group.pending {
+            commands.push(Message::command(&[
+                b"XCLAIM",
+                key.as_slice(),
+                name.as_slice(),
+                entry.consumer.as_slice(),
+                b"0",
+                id.to_string().as_bytes(),
+                b"TIME",
+                entry.delivery_time.to_string().as_bytes(),
+                b"RETRYCOUNT",
+                entry.delivery_count.to_string().as_bytes(),
+                b"FORCE",
+                b"JUSTID",
+            ]));
+        }
+        for (consumer, state) in &group.consumers {
+            if state.pending.is_empty() {
+                commands.push(Message::command(&[
+                    b"XGROUP",
+                    b"CREATECONSUMER",
+                    key.as_slice(),
+                    name.as_slice(),
+                    consumer.as_slice(),
+                ]));
+            }
+        }
+    }
+    commands
+}
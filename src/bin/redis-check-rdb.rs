@@ -0,0 +1,50 @@
+use std::{env, process};
+
+use redis_starter_rust::rdb::{check_rdb, RdbChecksum};
+
+/// Validate the RDB file given as first argument, like `redis-check-rdb`.
+#[tokio::main]
+async fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: redis-check-rdb <rdb-file-name>");
+            process::exit(1);
+        }
+    };
+
+    println!("[info] Checking RDB file {path}");
+    let data = match tokio::fs::read(&path).await {
+        Ok(data) => data,
+        Err(e) => {
+            println!("[error] Cannot open RDB file: {e}");
+            process::exit(1);
+        }
+    };
+
+    let summary = match check_rdb(&data).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            println!("--- RDB ERROR DETECTED ---");
+            println!("[error] {e}");
+            process::exit(1);
+        }
+    };
+
+    println!("[info] RDB version {}", summary.version);
+    for (key, value) in &summary.aux_fields {
+        println!("[info] AUX FIELD {key} = '{value}'");
+    }
+    match summary.checksum {
+        RdbChecksum::Absent => {}
+        RdbChecksum::Disabled => println!("[info] Checksum disabled"),
+        RdbChecksum::Verified => println!("[info] Checksum OK"),
+    }
+    for db in &summary.databases {
+        println!(
+            "[info] db {}: {} keys, {} expires, {} already expired",
+            db.index, db.keys, db.expires, db.already_expired
+        );
+    }
+    println!("\\o/ RDB looks OK! \\o/");
+}
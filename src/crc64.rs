@@ -0,0 +1,93 @@
+//! CRC-64/Jones checksum, as used by Redis in RDB files footer.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Reflected form of the Jones polynomial `0xad93d23594c935a9`.
+const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+const TABLE: [u64; 256] = make_table();
+
+const fn make_table() -> [u64; 256] {
+    let mut table = [0_u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Update checksum with new data.
+pub fn update(mut crc: u64, data: &[u8]) -> u64 {
+    for byte in data {
+        crc = TABLE[((crc ^ *byte as u64) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// Compute checksum of data.
+pub fn crc64(data: &[u8]) -> u64 {
+    update(0, data)
+}
+
+/// Reader computing checksum of data read through it.
+#[derive(Debug)]
+pub struct Crc64Reader<R> {
+    inner: R,
+    crc: u64,
+    position: u64,
+}
+
+impl<R> Crc64Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            crc: 0,
+            position: 0,
+        }
+    }
+
+    /// Checksum of data read so far.
+    pub fn crc(&self) -> u64 {
+        self.crc
+    }
+
+    /// Number of bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Crc64Reader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled_before = buf.filled().len();
+
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let data = &buf.filled()[filled_before..];
+        this.crc = update(this.crc, data);
+        this.position += data.len() as u64;
+        Poll::Ready(Ok(()))
+    }
+}
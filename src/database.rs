@@ -1,16 +1,110 @@
 use std::{
-    collections::HashMap,
+    collections::{hash_map::RandomState, BTreeMap, HashMap, VecDeque},
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use tokio::{join, sync::RwLock};
+use tokio::sync::RwLock;
 
-use crate::rdb::RedisString;
+use crate::{
+    error::MiniRedisError,
+    rdb::{RdbDatabase, RedisString},
+    resp2::Message,
+};
+
+mod bitmap;
+mod blocking;
+mod expire;
+mod glob;
+mod hash;
+mod indexed;
+mod keys;
+mod keyspace;
+mod list;
+mod set;
+mod skiplist;
+mod stream;
+mod stream_group;
+mod string;
+mod zset;
+
+use indexed::IndexedMap;
+use keyspace::Keyspace;
+
+pub use bitmap::{BitFieldOp, BitFieldType, BitOperation, BitOverflow, BitUnit, MAX_BIT_OFFSET};
+pub use blocking::KeyWaiter;
+pub use expire::ExpireOptions;
+pub use glob::glob_match;
+pub use hash::Hash;
+pub use keyspace::{ScanOptions, DEFAULT_SCAN_COUNT};
+pub use list::ListEnd;
+pub use set::{Set, SetOperation};
+pub use stream::{
+    Stream, StreamEntry, StreamId, StreamInfo, StreamTrim, StreamTrimOptions, XAddId, XAddOptions,
+    STREAM_NODE_MAX_ENTRIES,
+};
+pub use stream_group::{
+    AutoClaim, Consumer, ConsumerGroup, ConsumerInfo, GroupEntry, GroupInfo, PendingEntry,
+    PendingInfo, PendingRange, PendingSummary, XClaimOptions,
+};
+pub use string::{LcsMatch, LcsOptions, SetCondition, SetExpiry, SetOptions, MAX_STRING_LEN};
+pub use zset::{LexBound, Score, ScoreBound, SortedSet, ZAddOptions, ZRange, ZRangeBy};
+
+/// Most items returned by `HRANDFIELD` and `SRANDMEMBER` with a negative count.
+pub const MAX_RANDOM_SAMPLE: usize = 1 << 20;
+
+/// Value stored at a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(RedisString),
+    List(VecDeque<RedisString>),
+    Hash(Hash),
+    Set(Set),
+    SortedSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    /// Name returned by `TYPE` command.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "string",
+            Self::List(_) => "list",
+            Self::Hash(_) => "hash",
+            Self::Set(_) => "set",
+            Self::SortedSet(_) => "zset",
+            Self::Stream(_) => "stream",
+        }
+    }
+
+    /// Check if value is an empty collection, which must not be kept in database.
+    fn is_empty_collection(&self) -> bool {
+        match self {
+            Self::String(_) => false,
+            Self::List(list) => list.is_empty(),
+            Self::Hash(hash) => hash.is_empty(),
+            Self::Set(set) => set.is_empty(),
+            Self::SortedSet(zset) => zset.is_empty(),
+            // Streams are kept when their last entry is removed
+            Self::Stream(_) => false,
+        }
+    }
+}
+
+impl From<RedisString> for Value {
+    fn from(value: RedisString) -> Self {
+        Self::String(value)
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct Database {
-    content: RwLock<HashMap<RedisString, RedisString>>,
-    expiry_millis: RwLock<HashMap<RedisString, u64>>,
+    content: RwLock<Keyspace>,
+    expiry_millis: RwLock<IndexedMap<u64>>,
+    changes: AtomicU64,
+    expired_keys: AtomicU64,
+    blocked: blocking::BlockedClients,
 }
 
 impl Database {
@@ -18,34 +112,75 @@ impl Database {
         Self::default()
     }
 
+    /// Total number of write operations applied since startup.
+    pub fn changes(&self) -> u64 {
+        self.changes.load(Ordering::SeqCst)
+    }
+
+    /// Number of keys removed because they expired since startup.
+    pub fn expired_keys(&self) -> u64 {
+        self.expired_keys.load(Ordering::SeqCst)
+    }
+
     pub async fn set<K, V>(&self, key: K, value: V)
     where
         K: Into<RedisString>,
         V: Into<RedisString>,
+    {
+        self.set_value(key, Value::String(value.into())).await;
+    }
+
+    /// Store value at key, replacing any existing value whatever its type, and its expiry.
+    pub async fn set_value<K>(&self, key: K, value: Value)
+    where
+        K: Into<RedisString>,
     {
         let key = key.into();
-        let value = value.into();
 
-        self.content.write().await.insert(key, value);
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        expiry_millis.remove(&key);
+        content.insert(key, value);
+        self.changes.fetch_add(1, Ordering::SeqCst);
     }
 
-    pub async fn expire_at_millis<K>(&self, key: K, timestamp: u64)
+    /// Set expiry of key, returning whether it exists.
+    pub async fn expire_at_millis<K>(&self, key: K, timestamp: u64) -> bool
     where
         K: Into<RedisString>,
     {
         let key = key.into();
 
+        let content = self.content.read().await;
+        if !content.contains_key(&key) {
+            return false;
+        }
         self.expiry_millis.write().await.insert(key, timestamp);
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        true
     }
 
-    pub async fn expire_in_millis<K>(&self, key: K, delta: u64)
+    pub async fn expire_in_millis<K>(&self, key: K, delta: u64) -> bool
     where
         K: Into<RedisString>,
     {
-        self.expire_at_millis(key, now_unix_millis() + delta).await;
+        self.expire_at_millis(key, now_unix_millis() + delta).await
+    }
+
+    /// Get string stored at key.
+    pub async fn get<K>(&self, key: K) -> Result<Option<RedisString>, MiniRedisError>
+    where
+        K: Into<RedisString>,
+    {
+        match self.get_value(key).await {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(MiniRedisError::WrongType),
+        }
     }
 
-    pub async fn get<K>(&self, key: K) -> Option<RedisString>
+    /// Get a copy of value stored at key, whatever its type.
+    pub async fn get_value<K>(&self, key: K) -> Option<Value>
     where
         K: Into<RedisString>,
     {
@@ -57,16 +192,87 @@ impl Database {
         // Check if key is expired
         if matches!(expire_at, Some(val) if val < now_unix_millis()) {
             // Do some cleanup
-            self.content.write().await.remove(&key);
-            self.expiry_millis.write().await.remove(&key);
+            let mut content = self.content.write().await;
+            let mut expiry_millis = self.expiry_millis.write().await;
+            self.purge_expired(&mut content, &mut expiry_millis, &key);
             return None;
         }
 
         content
     }
 
+    pub async fn exists<K>(&self, key: K) -> bool
+    where
+        K: Into<RedisString>,
+    {
+        let key = key.into();
+        self.read(&key, |value| value.is_some()).await
+    }
+
+    /// Name of the type of value stored at key, `None` if it does not exist.
+    pub async fn type_name(&self, key: &RedisString) -> Option<&'static str> {
+        self.read(key, |value| value.map(Value::type_name)).await
+    }
+
+    /// Run `f` on value stored at key without copying it.
+    async fn read<T>(&self, key: &RedisString, f: impl FnOnce(Option<&Value>) -> T) -> T {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        if is_expired(&expiry_millis, key) {
+            return f(None);
+        }
+        f(content.get(key))
+    }
+
+    /// Run `f` on value stored at key, allowing it to be created, changed or removed.
+    ///
+    /// `f` returns its output along with whether it changed the value, so that the change
+    /// counter only counts actual changes. Empty collections are removed.
+    async fn update<T>(
+        &self,
+        key: &RedisString,
+        f: impl FnOnce(&mut Option<Value>) -> Result<(T, bool), MiniRedisError>,
+    ) -> Result<T, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        let mut value = content.remove(key);
+        let output = f(&mut value);
+
+        match value {
+            Some(value) if !value.is_empty_collection() => {
+                content.insert(key.clone(), value);
+            }
+            _ => {
+                expiry_millis.remove(key);
+            }
+        }
+        let (output, changed) = output?;
+        if changed {
+            self.changes.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(output)
+    }
+
+    /// Remove key if it is expired.
+    fn purge_expired(
+        &self,
+        content: &mut Keyspace,
+        expiry_millis: &mut IndexedMap<u64>,
+        key: &RedisString,
+    ) {
+        if is_expired(expiry_millis, key) {
+            content.remove(key);
+            expiry_millis.remove(key);
+            self.expired_keys.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
     pub async fn keys(&self) -> Vec<RedisString> {
-        let (content, expiry_millis) = join!(self.content.read(), self.expiry_millis.read());
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
         let now = now_unix_millis();
 
         let mut output = Vec::with_capacity(content.len());
@@ -81,9 +287,179 @@ impl Database {
 
         output
     }
+
+    /// Remove key, returning its value and expiry if it was not expired.
+    pub async fn remove<K>(&self, key: K) -> Option<(Value, Option<u64>)>
+    where
+        K: Into<RedisString>,
+    {
+        let key = key.into();
+
+        let value = self.content.write().await.remove(&key)?;
+        let expire_at = self.expiry_millis.write().await.remove(&key);
+        self.changes.fetch_add(1, Ordering::SeqCst);
+
+        match expire_at {
+            Some(val) if val < now_unix_millis() => {
+                self.expired_keys.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+            expire_at => Some((value, expire_at)),
+        }
+    }
+
+    /// Remove all keys and return how many were removed.
+    pub async fn clear(&self) -> usize {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+
+        let count = content.len();
+        content.clear();
+        expiry_millis.clear();
+        self.changes.fetch_add(count as u64, Ordering::SeqCst);
+        count
+    }
+
+    /// Exchange content with another database, waking clients blocked on keys it brings.
+    ///
+    /// Callers must always swap databases in the same order to avoid deadlocks.
+    pub async fn swap(&self, other: &Database) {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        let mut other_content = other.content.write().await;
+        let mut other_expiry_millis = other.expiry_millis.write().await;
+
+        std::mem::swap(&mut *content, &mut *other_content);
+        std::mem::swap(&mut *expiry_millis, &mut *other_expiry_millis);
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        other.changes.fetch_add(1, Ordering::SeqCst);
+        self.wake_blocked_keys(&content);
+        other.wake_blocked_keys(&other_content);
+    }
+
+    /// Copy all non expired keys with their expiry.
+    pub async fn snapshot(&self) -> RdbDatabase {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+        let now = now_unix_millis();
+
+        let mut values = HashMap::with_capacity(content.len());
+        let mut expiry = HashMap::new();
+        for (key, value) in content.iter() {
+            match expiry_millis.get(key) {
+                Some(val) if *val < now => continue,
+                Some(val) => {
+                    expiry.insert(key.clone(), *val);
+                }
+                None => {}
+            }
+
+            values.insert(key.clone(), value.clone());
+        }
+
+        RdbDatabase { values, expiry }
+    }
+}
+
+fn is_expired(expiry_millis: &IndexedMap<u64>, key: &RedisString) -> bool {
+    matches!(expiry_millis.get(key), Some(val) if *val < now_unix_millis())
+}
+
+/// Convert an inclusive range with possibly negative bounds to positions in a sequence.
+///
+/// Returns `None` if range is empty.
+pub fn range_bounds(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        stop + len
+    } else {
+        stop.min(len - 1)
+    };
+
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+/// Pick a random position in a sequence of `len` elements.
+fn random_index(len: usize) -> usize {
+    // Each `RandomState` is seeded with random keys by the standard library
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(len);
+    (hasher.finish() % len as u64) as usize
+}
+
+/// Pick random items, distinct for a positive count or possibly repeated for a negative one.
+///
+/// Repeated picks are capped to [`MAX_RANDOM_SAMPLE`], since the whole reply is built in memory.
+fn random_sample<T: Clone>(mut items: Vec<T>, count: i64) -> Vec<T> {
+    if items.is_empty() {
+        return items;
+    }
+    if count < 0 {
+        let count = count.unsigned_abs().min(MAX_RANDOM_SAMPLE as u64);
+        return (0..count)
+            .map(|_| items[random_index(items.len())].clone())
+            .collect();
+    }
+
+    // Partial shuffle of distinct items
+    let count = (count as usize).min(items.len());
+    for i in 0..count {
+        let j = i + random_index(items.len() - i);
+        items.swap(i, j);
+    }
+    items.truncate(count);
+    items
+}
+
+/// Format a float result of an increment command.
+pub fn format_float(value: f64) -> RedisString {
+    RedisString::new(value.to_string().as_bytes())
+}
+
+/// Parse an integer as Redis does, rejecting `+` signs, leading zeros and spaces so that
+/// only canonical representations are numbers.
+pub fn parse_integer(input: &[u8]) -> Option<i64> {
+    let digits = input.strip_prefix(b"-").unwrap_or(input);
+    match digits {
+        [b'0'] if digits.len() == input.len() => return Some(0),
+        [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => {}
+        _ => return None,
+    }
+    std::str::from_utf8(input).ok()?.parse().ok()
+}
+
+/// Parse a float, rejecting NaN and infinity.
+pub fn parse_float(input: &[u8]) -> Option<f64> {
+    let value: f64 = std::str::from_utf8(input).ok()?.parse().ok()?;
+    value.is_finite().then_some(value)
+}
+
+/// Copy all non empty databases by index.
+pub async fn snapshot_all(databases: &[Database]) -> BTreeMap<usize, RdbDatabase> {
+    let mut output = BTreeMap::new();
+    for (index, database) in databases.iter().enumerate() {
+        let snapshot = database.snapshot().await;
+        if !snapshot.is_empty() {
+            output.insert(index, snapshot);
+        }
+    }
+    output
+}
+
+/// Command switching database of following commands in AOF and replication streams.
+pub fn select_command(index: usize) -> Message {
+    Message::command(&[b"SELECT", index.to_string().as_bytes()])
 }
 
-fn now_unix_millis() -> u64 {
+pub fn now_unix_millis() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time cannot go before 1970 with this implementation")
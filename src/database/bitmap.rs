@@ -0,0 +1,358 @@
+use std::sync::atomic::Ordering;
+
+use super::{
+    string::{as_string, as_string_mut, string_bounds, MAX_STRING_LEN},
+    Database, Value,
+};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Bit offsets must be lower than this, so that strings stay within their maximum size.
+pub const MAX_BIT_OFFSET: usize = MAX_STRING_LEN * 8;
+
+/// Unit of `BITCOUNT` and `BITPOS` ranges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+/// Operation of `BITOP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// Integer type of a `BITFIELD` field, such as `i16` or `u8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitFieldType {
+    pub signed: bool,
+    /// Up to 64 bits when signed, 63 otherwise.
+    pub bits: u32,
+}
+
+impl BitFieldType {
+    fn min(&self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(&self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Integer stored at bit offset, missing bytes being zero.
+    fn read(&self, bytes: &[u8], offset: usize) -> i64 {
+        let value = (offset..offset + self.bits as usize).fold(0u64, |value, bit| {
+            value << 1 | u64::from(get_bit(bytes, bit))
+        });
+        let negative = self.signed && self.bits < 64 && value >> (self.bits - 1) == 1;
+        if negative {
+            (value | u64::MAX << self.bits) as i64
+        } else {
+            value as i64
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: usize, value: i64) {
+        for i in 0..self.bits {
+            let bit = (value as u64) >> (self.bits - 1 - i) & 1 == 1;
+            set_bit(bytes, offset + i as usize, bit);
+        }
+    }
+
+    /// Fit value in the field according to overflow behaviour, `None` if it fails.
+    fn fit(&self, value: i128, overflow: BitOverflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            BitOverflow::Wrap => {
+                let wrapped = value.rem_euclid(1 << self.bits);
+                if wrapped > self.max() {
+                    Some((wrapped - (1 << self.bits)) as i64)
+                } else {
+                    Some(wrapped as i64)
+                }
+            }
+            BitOverflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            BitOverflow::Fail => None,
+        }
+    }
+}
+
+/// Behaviour of `BITFIELD` writes overflowing their field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitOverflow {
+    /// Keep the lowest bits.
+    #[default]
+    Wrap,
+    /// Saturate to the minimum or maximum value.
+    Sat,
+    /// Leave the field unchanged and reply nil.
+    Fail,
+}
+
+/// Subcommand of `BITFIELD`, offsets being in bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitFieldOp {
+    Get(BitFieldType, usize),
+    Set(BitFieldType, usize, i64),
+    IncrBy(BitFieldType, usize, i64),
+    Overflow(BitOverflow),
+}
+
+fn get_bit(bytes: &[u8], offset: usize) -> bool {
+    matches!(bytes.get(offset / 8), Some(byte) if byte & (0x80 >> (offset % 8)) != 0)
+}
+
+fn set_bit(bytes: &mut [u8], offset: usize, bit: bool) {
+    let mask = 0x80 >> (offset % 8);
+    if bit {
+        bytes[offset / 8] |= mask;
+    } else {
+        bytes[offset / 8] &= !mask;
+    }
+}
+
+/// Inclusive range of bits selected by positions in bytes or bits of a string of `len`
+/// bytes, see [`string_bounds`].
+fn bit_bounds(len: usize, start: i64, end: i64, unit: BitUnit) -> Option<(usize, usize)> {
+    match unit {
+        BitUnit::Byte => {
+            string_bounds(len, start, end).map(|(start, end)| (start * 8, end * 8 + 7))
+        }
+        BitUnit::Bit => string_bounds(len * 8, start, end),
+    }
+}
+
+/// Bits of byte at index within an inclusive range of bits.
+fn byte_mask(index: usize, start: usize, end: usize) -> u8 {
+    let first = if index == start / 8 {
+        0xFF >> (start % 8)
+    } else {
+        0xFF
+    };
+    let last = if index == end / 8 {
+        0xFF << (7 - end % 8)
+    } else {
+        0xFF
+    };
+    first & last
+}
+
+impl Database {
+    /// Set or clear bit at offset, padding string with zero bytes if needed. Returns the
+    /// previous bit.
+    pub async fn bit_set(
+        &self,
+        key: &RedisString,
+        offset: usize,
+        bit: bool,
+    ) -> Result<bool, MiniRedisError> {
+        self.update(key, |value| {
+            let string = as_string_mut(value)?.as_mut_vec();
+            if string.len() <= offset / 8 {
+                string.resize(offset / 8 + 1, 0);
+            }
+            let previous = get_bit(string, offset);
+            set_bit(string, offset, bit);
+            Ok((previous, true))
+        })
+        .await
+    }
+
+    /// Bit at offset, bits past the end of string being clear.
+    pub async fn bit_get(&self, key: &RedisString, offset: usize) -> Result<bool, MiniRedisError> {
+        self.read(key, |value| {
+            let string = as_string(value)?.map_or(&[][..], RedisString::as_slice);
+            Ok(get_bit(string, offset))
+        })
+        .await
+    }
+
+    /// Number of set bits, within an inclusive range if given.
+    pub async fn bit_count(
+        &self,
+        key: &RedisString,
+        range: Option<(i64, i64, BitUnit)>,
+    ) -> Result<usize, MiniRedisError> {
+        let (start, end, unit) = range.unwrap_or((0, -1, BitUnit::Byte));
+        self.read(key, |value| {
+            let string = as_string(value)?.map_or(&[][..], RedisString::as_slice);
+            let Some((start, end)) = bit_bounds(string.len(), start, end, unit) else {
+                return Ok(0);
+            };
+            Ok((start / 8..=end / 8)
+                .map(|index| (string[index] & byte_mask(index, start, end)).count_ones() as usize)
+                .sum())
+        })
+        .await
+    }
+
+    /// Position of the first bit equal to `bit` within an inclusive range, -1 if none.
+    ///
+    /// Without an explicit end, string is considered padded with clear bits.
+    pub async fn bit_pos(
+        &self,
+        key: &RedisString,
+        bit: bool,
+        start: i64,
+        end: Option<i64>,
+        unit: BitUnit,
+    ) -> Result<i64, MiniRedisError> {
+        self.read(key, |value| {
+            let Some(string) = as_string(value)? else {
+                return Ok(if bit { -1 } else { 0 });
+            };
+            let string = string.as_slice();
+            let Some((first, last)) = bit_bounds(string.len(), start, end.unwrap_or(-1), unit)
+            else {
+                return Ok(-1);
+            };
+
+            let position = (first / 8..=last / 8).find_map(|index| {
+                let byte = if bit { string[index] } else { !string[index] };
+                let found = byte & byte_mask(index, first, last);
+                (found != 0).then(|| index * 8 + found.leading_zeros() as usize)
+            });
+            Ok(match position {
+                Some(position) => position as i64,
+                None if !bit && end.is_none() => (string.len() * 8) as i64,
+                None => -1,
+            })
+        })
+        .await
+    }
+
+    /// Store the result of a bitwise operation between strings at destination, missing keys
+    /// being empty strings and shorter strings padded with zero bytes. Destination is removed
+    /// when the result is empty. Returns the length of the result.
+    pub async fn bit_op(
+        &self,
+        operation: BitOperation,
+        destination: &RedisString,
+        keys: &[RedisString],
+    ) -> Result<usize, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        for key in keys.iter().chain([destination]) {
+            self.purge_expired(&mut content, &mut expiry_millis, key);
+        }
+
+        let sources = keys
+            .iter()
+            .map(|key| Ok(as_string(content.get(key))?.map_or(&[][..], RedisString::as_slice)))
+            .collect::<Result<Vec<_>, MiniRedisError>>()?;
+        let len = sources.iter().map(|source| source.len()).max().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|index| {
+                let mut bytes = sources
+                    .iter()
+                    .map(|source| source.get(index).copied().unwrap_or(0));
+                let first = bytes.next().unwrap_or(0);
+                match operation {
+                    BitOperation::And => bytes.fold(first, |result, byte| result & byte),
+                    BitOperation::Or => bytes.fold(first, |result, byte| result | byte),
+                    BitOperation::Xor => bytes.fold(first, |result, byte| result ^ byte),
+                    BitOperation::Not => !first,
+                }
+            })
+            .collect();
+
+        expiry_millis.remove(destination);
+        if result.is_empty() {
+            content.remove(destination);
+        } else {
+            content.insert(destination.clone(), Value::String(result.into()));
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(len)
+    }
+
+    /// Run `BITFIELD` subcommands in order, returning a reply for each one but `OVERFLOW`,
+    /// nil when a write fails.
+    ///
+    /// String is only created or extended when there is a write, up to the furthest one.
+    pub async fn bit_field(
+        &self,
+        key: &RedisString,
+        ops: &[BitFieldOp],
+    ) -> Result<Vec<Option<i64>>, MiniRedisError> {
+        let end = ops
+            .iter()
+            .filter_map(|op| match op {
+                BitFieldOp::Set(field, offset, _) | BitFieldOp::IncrBy(field, offset, _) => {
+                    Some((offset + field.bits as usize - 1) / 8 + 1)
+                }
+                _ => None,
+            })
+            .max();
+        let Some(end) = end else {
+            return self
+                .read(key, |value| {
+                    let string = as_string(value)?.map_or(&[][..], RedisString::as_slice);
+                    Ok(ops
+                        .iter()
+                        .filter_map(|op| match op {
+                            BitFieldOp::Get(field, offset) => {
+                                Some(Some(field.read(string, *offset)))
+                            }
+                            _ => None,
+                        })
+                        .collect())
+                })
+                .await;
+        };
+
+        self.update(key, |value| {
+            let string = as_string_mut(value)?.as_mut_vec();
+            if string.len() < end {
+                string.resize(end, 0);
+            }
+
+            let mut overflow = BitOverflow::default();
+            let mut output = Vec::new();
+            for op in ops {
+                match *op {
+                    BitFieldOp::Get(field, offset) => output.push(Some(field.read(string, offset))),
+                    BitFieldOp::Set(field, offset, value) => {
+                        let previous = field.read(string, offset);
+                        // Like Redis, negative values overflow unsigned fields from above
+                        let value = if field.signed {
+                            i128::from(value)
+                        } else {
+                            i128::from(value as u64)
+                        };
+                        let value = field.fit(value, overflow);
+                        if let Some(value) = value {
+                            field.write(string, offset, value);
+                        }
+                        output.push(value.map(|_| previous));
+                    }
+                    BitFieldOp::IncrBy(field, offset, increment) => {
+                        let value = i128::from(field.read(string, offset)) + i128::from(increment);
+                        let value = field.fit(value, overflow);
+                        if let Some(value) = value {
+                            field.write(string, offset, value);
+                        }
+                        output.push(value);
+                    }
+                    BitFieldOp::Overflow(behaviour) => overflow = behaviour,
+                }
+            }
+            Ok((output, true))
+        })
+        .await
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::sync::Notify;
+
+use super::{keyspace::Keyspace, Database, Value};
+use crate::rdb::RedisString;
+
+/// Clients blocked on each key, oldest first.
+pub(super) type BlockedClients = Mutex<HashMap<RedisString, VecDeque<Arc<Waiter>>>>;
+
+#[derive(Debug, Default)]
+pub(super) struct Waiter {
+    notify: Notify,
+    /// Set when an element was made available to this waiter and it did not try to take it yet.
+    notified: AtomicBool,
+}
+
+impl Waiter {
+    fn wake(&self) {
+        if !self.notified.swap(true, Ordering::SeqCst) {
+            self.notify.notify_one();
+        }
+    }
+}
+
+/// Registration of a client waiting for elements to be added at some keys, either pushed on
+/// lists or appended to streams.
+///
+/// Client is unregistered when dropped.
+#[derive(Debug)]
+pub struct KeyWaiter<'a> {
+    pub(super) database: &'a Database,
+    keys: Vec<RedisString>,
+    waiter: Arc<Waiter>,
+}
+
+impl<'a> KeyWaiter<'a> {
+    pub(super) fn new(database: &'a Database, keys: Vec<RedisString>) -> Self {
+        let waiter = Arc::new(Waiter::default());
+        let mut blocked = database.blocked.lock().unwrap();
+        for key in &keys {
+            let queue = blocked.entry(key.clone()).or_default();
+            if !queue.iter().any(|other| Arc::ptr_eq(other, &waiter)) {
+                queue.push_back(waiter.clone());
+            }
+        }
+        drop(blocked);
+
+        Self {
+            database,
+            keys,
+            waiter,
+        }
+    }
+
+    pub fn database(&self) -> &'a Database {
+        self.database
+    }
+
+    pub fn keys(&self) -> &[RedisString] {
+        &self.keys
+    }
+
+    /// Wait until an element may be available at one of the keys.
+    pub async fn notified(&self) {
+        self.waiter.notify.notified().await;
+        self.waiter.notified.store(false, Ordering::SeqCst);
+    }
+
+    /// Check if this client may take an element from a list of `len` elements stored at key.
+    ///
+    /// Older clients blocked on the same key are served first.
+    pub(super) fn is_served(&self, key: &RedisString, len: usize) -> bool {
+        let blocked = self.database.blocked.lock().unwrap();
+        let position = blocked
+            .get(key)
+            .and_then(|queue| queue.iter().position(|w| Arc::ptr_eq(w, &self.waiter)));
+        match position {
+            Some(position) => position < len,
+            None => true,
+        }
+    }
+}
+
+impl Drop for KeyWaiter<'_> {
+    fn drop(&mut self) {
+        let mut blocked = self.database.blocked.lock().unwrap();
+        for key in &self.keys {
+            let Some(queue) = blocked.get_mut(key) else {
+                continue;
+            };
+            queue.retain(|other| !Arc::ptr_eq(other, &self.waiter));
+
+            if queue.is_empty() {
+                blocked.remove(key);
+                continue;
+            }
+
+            // Element this client may have been woken for goes to the next one
+            if let Some(next) = queue
+                .iter()
+                .find(|other| !other.notified.load(Ordering::SeqCst))
+            {
+                next.wake();
+            }
+        }
+    }
+}
+
+impl Database {
+    /// Register a client waiting for elements added at keys.
+    pub fn wait_keys(&self, keys: Vec<RedisString>) -> KeyWaiter<'_> {
+        KeyWaiter::new(self, keys)
+    }
+
+    /// Wake oldest clients blocked on key, one per available element.
+    pub(super) fn wake_blocked(&self, key: &RedisString, available: usize) {
+        let blocked = self.blocked.lock().unwrap();
+        if let Some(queue) = blocked.get(key) {
+            queue
+                .iter()
+                .take(available)
+                .for_each(|waiter| waiter.wake());
+        }
+    }
+
+    /// Wake clients blocked on any key of content, which replaced the whole database.
+    pub(super) fn wake_blocked_keys(&self, content: &Keyspace) {
+        let blocked = self.blocked.lock().unwrap();
+        for (key, queue) in blocked.iter() {
+            let available = content.get(key).map_or(0, available_elements);
+            queue
+                .iter()
+                .take(available)
+                .for_each(|waiter| waiter.wake());
+        }
+    }
+}
+
+/// Elements clients blocked on a key may be served after value is stored there.
+pub(super) fn available_elements(value: &Value) -> usize {
+    match value {
+        Value::List(list) => list.len(),
+        Value::Stream(_) => usize::MAX,
+        _ => 0,
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::atomic::Ordering;
+
+use super::{is_expired, now_unix_millis, random_index, Database};
+use crate::rdb::RedisString;
+
+/// Conditions of `EXPIRE` and its variants, checked against the current expiry of the key.
+///
+/// A persistent key counts as expiring at infinity, so `gt` never sets its expiry while `lt`
+/// always does. `nx` cannot be combined with other conditions, nor `gt` with `lt`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpireOptions {
+    /// Only make a persistent key volatile.
+    pub nx: bool,
+    /// Only change the expiry of a volatile key.
+    pub xx: bool,
+    /// Only postpone the expiry.
+    pub gt: bool,
+    /// Only bring the expiry forward.
+    pub lt: bool,
+}
+
+impl Database {
+    /// Set expiry of key to a unix time in milliseconds, according to conditions.
+    ///
+    /// Key is removed if time is already past. Returns whether expiry was set.
+    pub async fn expire(&self, key: &RedisString, expire_at: u64, options: ExpireOptions) -> bool {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        if !content.contains_key(key) {
+            return false;
+        }
+        let current = expiry_millis.get(key).copied();
+        let allowed = match current {
+            _ if options.nx && current.is_some() => false,
+            None => !options.xx && !options.gt,
+            Some(current) => {
+                !((options.gt && expire_at <= current) || (options.lt && expire_at >= current))
+            }
+        };
+        if !allowed {
+            return false;
+        }
+
+        if expire_at <= now_unix_millis() {
+            content.remove(key);
+            expiry_millis.remove(key);
+        } else {
+            expiry_millis.insert(key.clone(), expire_at);
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Expiry of key as unix time in milliseconds, `None` if key does not exist.
+    pub async fn expire_time(&self, key: &RedisString) -> Option<Option<u64>> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let expire_at = expiry_millis.get(key).copied();
+        match expire_at {
+            Some(val) if val < now_unix_millis() => None,
+            _ if !content.contains_key(key) => None,
+            expire_at => Some(expire_at),
+        }
+    }
+
+    /// Remove expiry of key, returning whether it had one.
+    pub async fn persist(&self, key: &RedisString) -> bool {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        if expiry_millis.remove(key).is_none() {
+            return false;
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Remove expired keys among up to `count` keys with an expiry, picked from a random
+    /// position. Returns how many keys were sampled and removed.
+    pub async fn expire_sample(&self, count: usize) -> (usize, usize) {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        if expiry_millis.is_empty() {
+            return (0, 0);
+        }
+
+        let len = expiry_millis.len();
+        let start = random_index(len);
+        let sample: Vec<_> = (start..start + count.min(len))
+            .filter_map(|index| expiry_millis.get_index(index % len))
+            .map(|(key, _)| key.clone())
+            .collect();
+        let expired: Vec<_> = sample
+            .iter()
+            .filter(|key| is_expired(&expiry_millis, key))
+            .collect();
+        for key in &expired {
+            self.purge_expired(&mut content, &mut expiry_millis, key);
+        }
+        (sample.len(), expired.len())
+    }
+}
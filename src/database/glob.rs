@@ -0,0 +1,83 @@
+/// Match a string against a glob-style pattern, as `KEYS` and `SCAN` do.
+///
+/// Supports `*`, `?`, classes such as `[abc]`, `[^a]` or `[a-z]`, and `\` escapes.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Pattern position after the last star, and string position it is matched up to
+    let mut star = None;
+
+    while s < string.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, s));
+            continue;
+        }
+        if let Some(next) = match_one(pattern, p, string[s]) {
+            p = next;
+            s += 1;
+            continue;
+        }
+        // Let the last star match one more byte
+        match star {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                star = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Match a single byte against the pattern token at `p`, returning the position of the next
+/// token.
+fn match_one(pattern: &[u8], p: usize, c: u8) -> Option<usize> {
+    match pattern.get(p)? {
+        b'?' => Some(p + 1),
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        b'[' => match_class(pattern, p + 1, c),
+        literal => (*literal == c).then_some(p + 1),
+    }
+}
+
+/// Match a byte against the class starting at `p`, after its opening bracket.
+///
+/// An unterminated class ends with the pattern.
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> Option<usize> {
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() {
+        match pattern[p] {
+            b']' => {
+                p += 1;
+                break;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            start if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                let end = pattern[p + 2];
+                let (low, high) = if start <= end {
+                    (start, end)
+                } else {
+                    (end, start)
+                };
+                matched |= (low..=high).contains(&c);
+                p += 3;
+            }
+            literal => {
+                matched |= literal == c;
+                p += 1;
+            }
+        }
+    }
+
+    (matched != negate).then_some(p)
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use super::{
+    format_float, keyspace::ScanOrder, parse_float, parse_integer, random_sample, Database,
+    ScanOptions, Value,
+};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Fields with their values, also ordered by hash so that they can be scanned.
+#[derive(Debug, Clone, Default)]
+pub struct Hash {
+    values: HashMap<RedisString, RedisString>,
+    order: ScanOrder,
+}
+
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+impl Eq for Hash {}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, field: &RedisString) -> Option<&RedisString> {
+        self.values.get(field)
+    }
+
+    pub fn contains_key(&self, field: &RedisString) -> bool {
+        self.values.contains_key(field)
+    }
+
+    /// Set value of field, returning the previous one.
+    pub fn insert(&mut self, field: RedisString, value: RedisString) -> Option<RedisString> {
+        if !self.values.contains_key(&field) {
+            self.order.insert(field.clone());
+        }
+        self.values.insert(field, value)
+    }
+
+    pub fn remove(&mut self, field: &RedisString) -> Option<RedisString> {
+        let value = self.values.remove(field)?;
+        self.order.remove(field);
+        Some(value)
+    }
+
+    /// Iterate over fields with their values, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&RedisString, &RedisString)> {
+        self.values.iter()
+    }
+
+    /// Fields from cursor in scan order with their values.
+    fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&RedisString, &RedisString)>) {
+        let (cursor, fields) = self.order.scan(cursor, count);
+        let fields = fields
+            .into_iter()
+            .map(|field| (field, &self.values[field]))
+            .collect();
+        (cursor, fields)
+    }
+}
+
+impl FromIterator<(RedisString, RedisString)> for Hash {
+    fn from_iter<I: IntoIterator<Item = (RedisString, RedisString)>>(iter: I) -> Self {
+        let mut hash = Self::new();
+        for (field, value) in iter {
+            hash.insert(field, value);
+        }
+        hash
+    }
+}
+
+impl<const N: usize> From<[(RedisString, RedisString); N]> for Hash {
+    fn from(pairs: [(RedisString, RedisString); N]) -> Self {
+        pairs.into_iter().collect()
+    }
+}
+
+fn as_hash(value: Option<&Value>) -> Result<Option<&Hash>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::Hash(hash)) => Ok(Some(hash)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+fn as_hash_mut(value: &mut Option<Value>) -> Result<&mut Hash, MiniRedisError> {
+    match value.get_or_insert_with(|| Value::Hash(Hash::new())) {
+        Value::Hash(hash) => Ok(hash),
+        _ => Err(MiniRedisError::WrongType),
+    }
+}
+
+impl Database {
+    /// Set fields and return how many were added.
+    pub async fn hash_set(
+        &self,
+        key: &RedisString,
+        pairs: Vec<(RedisString, RedisString)>,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let hash = as_hash_mut(value)?;
+            let mut added = 0;
+            for (field, value) in pairs {
+                if hash.insert(field, value).is_none() {
+                    added += 1;
+                }
+            }
+            Ok((added, true))
+        })
+        .await
+    }
+
+    /// Set field only if it does not exist yet.
+    pub async fn hash_set_nx(
+        &self,
+        key: &RedisString,
+        field: RedisString,
+        value: RedisString,
+    ) -> Result<bool, MiniRedisError> {
+        self.update(key, |current| {
+            let hash = as_hash_mut(current)?;
+            if hash.contains_key(&field) {
+                return Ok((false, false));
+            }
+            hash.insert(field, value);
+            Ok((true, true))
+        })
+        .await
+    }
+
+    pub async fn hash_get(
+        &self,
+        key: &RedisString,
+        field: &RedisString,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_hash(value)?.and_then(|hash| hash.get(field).cloned()))
+        })
+        .await
+    }
+
+    /// Get values of fields, in the same order.
+    pub async fn hash_get_many(
+        &self,
+        key: &RedisString,
+        fields: &[RedisString],
+    ) -> Result<Vec<Option<RedisString>>, MiniRedisError> {
+        self.read(key, |value| {
+            let hash = as_hash(value)?;
+            Ok(fields
+                .iter()
+                .map(|field| hash.and_then(|hash| hash.get(field).cloned()))
+                .collect())
+        })
+        .await
+    }
+
+    /// Remove fields and return how many existed.
+    pub async fn hash_delete(
+        &self,
+        key: &RedisString,
+        fields: &[RedisString],
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let hash = as_hash_mut(value)?;
+            let removed = fields
+                .iter()
+                .filter(|field| hash.remove(field).is_some())
+                .count();
+            Ok((removed, removed > 0))
+        })
+        .await
+    }
+
+    pub async fn hash_exists(
+        &self,
+        key: &RedisString,
+        field: &RedisString,
+    ) -> Result<bool, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_hash(value)?.is_some_and(|hash| hash.contains_key(field)))
+        })
+        .await
+    }
+
+    pub async fn hash_len(&self, key: &RedisString) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| Ok(as_hash(value)?.map_or(0, Hash::len)))
+            .await
+    }
+
+    /// Length of value stored at field, zero if it does not exist.
+    pub async fn hash_strlen(
+        &self,
+        key: &RedisString,
+        field: &RedisString,
+    ) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_hash(value)?
+                .and_then(|hash| hash.get(field))
+                .map_or(0, |value| value.as_slice().len()))
+        })
+        .await
+    }
+
+    /// Get all fields with their values.
+    pub async fn hash_get_all(
+        &self,
+        key: &RedisString,
+    ) -> Result<Vec<(RedisString, RedisString)>, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_hash(value)?
+                .map(|hash| {
+                    hash.iter()
+                        .map(|(field, value)| (field.clone(), value.clone()))
+                        .collect()
+                })
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Visit fields from cursor, returning the next cursor and visited fields matching
+    /// options with their values.
+    pub async fn hash_scan(
+        &self,
+        key: &RedisString,
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> Result<(u64, Vec<(RedisString, RedisString)>), MiniRedisError> {
+        self.read(key, |value| {
+            let Some(hash) = as_hash(value)? else {
+                return Ok((0, Vec::new()));
+            };
+            let (cursor, fields) = hash.scan(cursor, options.count);
+            let fields = fields
+                .into_iter()
+                .filter(|(field, _)| options.matches(field))
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect();
+            Ok((cursor, fields))
+        })
+        .await
+    }
+
+    /// Add increment to integer stored at field and return the new value.
+    pub async fn hash_incr_by(
+        &self,
+        key: &RedisString,
+        field: RedisString,
+        increment: i64,
+    ) -> Result<i64, MiniRedisError> {
+        self.update(key, |value| {
+            let hash = as_hash_mut(value)?;
+            let current = match hash.get(&field) {
+                Some(current) => {
+                    parse_integer(current.as_slice()).ok_or(MiniRedisError::HashValueNotInteger)?
+                }
+                None => 0,
+            };
+            let output = current
+                .checked_add(increment)
+                .ok_or(MiniRedisError::IncrementOverflow)?;
+            hash.insert(field, RedisString::from_integer(output));
+            Ok((output, true))
+        })
+        .await
+    }
+
+    /// Add increment to float stored at field and return the new value.
+    pub async fn hash_incr_by_float(
+        &self,
+        key: &RedisString,
+        field: RedisString,
+        increment: f64,
+    ) -> Result<RedisString, MiniRedisError> {
+        self.update(key, |value| {
+            let hash = as_hash_mut(value)?;
+            let current = match hash.get(&field) {
+                Some(current) => {
+                    parse_float(current.as_slice()).ok_or(MiniRedisError::HashValueNotFloat)?
+                }
+                None => 0.0,
+            };
+            let output = current + increment;
+            if !output.is_finite() {
+                return Err(MiniRedisError::IncrementNanOrInfinity);
+            }
+            let output = format_float(output);
+            hash.insert(field, output.clone());
+            Ok((output, true))
+        })
+        .await
+    }
+
+    /// Pick random fields with their values.
+    ///
+    /// A positive count returns distinct fields, a negative one may return the same field
+    /// several times.
+    pub async fn hash_random_fields(
+        &self,
+        key: &RedisString,
+        count: i64,
+    ) -> Result<Vec<(RedisString, RedisString)>, MiniRedisError> {
+        self.read(key, |value| {
+            let Some(hash) = as_hash(value)? else {
+                return Ok(Vec::new());
+            };
+            let entries = hash.iter().collect();
+            Ok(random_sample(entries, count)
+                .into_iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect())
+        })
+        .await
+    }
+}
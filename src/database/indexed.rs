@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use crate::rdb::RedisString;
+
+/// Map whose entries can also be reached by position, so that random keys are picked in
+/// constant time.
+///
+/// Removing an entry moves the last one to its position.
+#[derive(Debug, Clone)]
+pub(super) struct IndexedMap<V> {
+    entries: Vec<(RedisString, V)>,
+    positions: HashMap<RedisString, usize>,
+}
+
+impl<V> Default for IndexedMap<V> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<V> IndexedMap<V> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &RedisString) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    pub fn get(&self, key: &RedisString) -> Option<&V> {
+        let position = *self.positions.get(key)?;
+        Some(&self.entries[position].1)
+    }
+
+    pub fn get_mut(&mut self, key: &RedisString) -> Option<&mut V> {
+        let position = *self.positions.get(key)?;
+        Some(&mut self.entries[position].1)
+    }
+
+    /// Entry at position, in no particular order.
+    pub fn get_index(&self, index: usize) -> Option<(&RedisString, &V)> {
+        self.entries.get(index).map(|(key, value)| (key, value))
+    }
+
+    pub fn insert(&mut self, key: RedisString, value: V) -> Option<V> {
+        match self.positions.get(&key) {
+            Some(&position) => Some(std::mem::replace(&mut self.entries[position].1, value)),
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &RedisString) -> Option<V> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.swap_remove(position);
+        if let Some((moved, _)) = self.entries.get(position) {
+            self.positions.insert(moved.clone(), position);
+        }
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.positions.clear();
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &RedisString> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&RedisString, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::atomic::Ordering;
+
+use super::{blocking::available_elements, is_expired, random_index, Database, Value};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Expired keys `RANDOMKEY` removes before returning one of them anyway.
+const RANDOM_KEY_ATTEMPTS: usize = 100;
+
+impl Database {
+    /// Remove keys with their expiry, returning how many existed.
+    pub async fn delete(&self, keys: &[RedisString]) -> usize {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+
+        let mut removed = 0;
+        for key in keys {
+            self.purge_expired(&mut content, &mut expiry_millis, key);
+            if content.remove(key).is_some() {
+                expiry_millis.remove(key);
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            self.changes.fetch_add(removed as u64, Ordering::SeqCst);
+        }
+        removed
+    }
+
+    /// Number of existing keys, a key given several times being counted as many times.
+    pub async fn exists_count(&self, keys: &[RedisString]) -> usize {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        keys.iter()
+            .filter(|key| content.contains_key(key) && !is_expired(&expiry_millis, key))
+            .count()
+    }
+
+    /// Number of keys, including expired ones not removed yet.
+    pub async fn size(&self) -> usize {
+        self.content.read().await.len()
+    }
+
+    /// Rename source to destination with its expiry, replacing destination unless `nx` is
+    /// set. Returns whether source was renamed.
+    pub async fn rename(
+        &self,
+        source: &RedisString,
+        destination: &RedisString,
+        nx: bool,
+    ) -> Result<bool, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, source);
+        self.purge_expired(&mut content, &mut expiry_millis, destination);
+
+        if !content.contains_key(source) {
+            return Err(MiniRedisError::NoSuchKey);
+        }
+        if nx && content.contains_key(destination) {
+            return Ok(false);
+        }
+        if source == destination {
+            return Ok(true);
+        }
+
+        let value = content.remove(source).expect("source exists");
+        let available = available_elements(&value);
+        content.insert(destination.clone(), value);
+        match expiry_millis.remove(source) {
+            Some(expire_at) => expiry_millis.insert(destination.clone(), expire_at),
+            None => expiry_millis.remove(destination),
+        };
+
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        self.wake_blocked(destination, available);
+        Ok(true)
+    }
+
+    /// Copy of value stored at key with its expiry.
+    pub async fn get_with_expiry(&self, key: &RedisString) -> Option<(Value, Option<u64>)> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        if is_expired(&expiry_millis, key) {
+            return None;
+        }
+        let value = content.get(key)?.clone();
+        Some((value, expiry_millis.get(key).copied()))
+    }
+
+    /// Store value with its expiry, unless key exists and `replace` is not set. Returns
+    /// whether value was stored.
+    pub async fn restore(
+        &self,
+        key: &RedisString,
+        value: Value,
+        expire_at: Option<u64>,
+        replace: bool,
+    ) -> bool {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        if !replace && content.contains_key(key) {
+            return false;
+        }
+        let available = available_elements(&value);
+        content.insert(key.clone(), value);
+        match expire_at {
+            Some(expire_at) => expiry_millis.insert(key.clone(), expire_at),
+            None => expiry_millis.remove(key),
+        };
+
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        self.wake_blocked(key, available);
+        true
+    }
+
+    /// A random key, expired keys met along the way being removed.
+    ///
+    /// Like Redis, the last key picked is returned even if expired after
+    /// [`RANDOM_KEY_ATTEMPTS`] picks, so that mostly expired databases do not stall the server.
+    pub async fn random_key(&self) -> Option<RedisString> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+
+        for attempt in 1..=RANDOM_KEY_ATTEMPTS {
+            if content.is_empty() {
+                break;
+            }
+            let key = content.key_at(random_index(content.len()))?.clone();
+            if !is_expired(&expiry_millis, &key) || attempt == RANDOM_KEY_ATTEMPTS {
+                return Some(key);
+            }
+            self.purge_expired(&mut content, &mut expiry_millis, &key);
+        }
+        None
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    hash::Hasher,
+    ops::Bound,
+};
+
+use super::{glob_match, is_expired, Database, IndexedMap, Value};
+use crate::rdb::RedisString;
+
+/// Number of items `SCAN` commands return by default.
+pub const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// Options of `SCAN`, `HSCAN`, `SSCAN` and `ZSCAN`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Only return keys or members matching this glob pattern.
+    pub pattern: Option<RedisString>,
+    /// Number of keys or members to visit, some of which may be filtered out.
+    pub count: usize,
+    /// Only return keys of this type, `SCAN` only.
+    pub type_name: Option<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            count: DEFAULT_SCAN_COUNT,
+            type_name: None,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Whether key or member matches the pattern, if any.
+    pub(super) fn matches(&self, item: &RedisString) -> bool {
+        match &self.pattern {
+            Some(pattern) => glob_match(pattern.as_slice(), item.as_slice()),
+            None => true,
+        }
+    }
+}
+
+/// Position of a key in scan order, stable for the lifetime of the process.
+fn scan_hash(key: &RedisString) -> u64 {
+    // Default hasher is not randomly seeded, unlike `RandomState`
+    let mut hasher = DefaultHasher::new();
+    hasher.write(key.as_slice());
+    hasher.finish()
+}
+
+/// Take items ordered by hash until `count` is reached, keeping items sharing the last hash
+/// together. Returns the cursor of the next call, zero once all items were returned.
+fn scan_ordered<T>(items: impl Iterator<Item = (u64, T)>, count: usize) -> (u64, Vec<T>) {
+    let mut output = Vec::new();
+    let mut last = None;
+    for (hash, item) in items {
+        if output.len() >= count && last != Some(hash) {
+            return (hash, output);
+        }
+        last = Some(hash);
+        output.push(item);
+    }
+    (0, output)
+}
+
+/// Members of a collection ordered by hash, so that it can be scanned with an integer cursor.
+///
+/// Members present during a whole scan are returned whatever members are added or removed
+/// meanwhile, since the position of a member never changes.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ScanOrder(BTreeSet<(u64, RedisString)>);
+
+impl ScanOrder {
+    pub fn insert(&mut self, member: RedisString) {
+        self.0.insert((scan_hash(&member), member));
+    }
+
+    pub fn remove(&mut self, member: &RedisString) {
+        self.0.remove(&(scan_hash(member), member.clone()));
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Members from cursor in scan order, see [`scan_ordered`].
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&RedisString>) {
+        let start = (cursor, RedisString::new(b""));
+        let items = self
+            .0
+            .range((Bound::Included(start), Bound::Unbounded))
+            .map(|(hash, member)| (*hash, member));
+        scan_ordered(items, count)
+    }
+}
+
+/// Values by key, also ordered by hash so that keys can be scanned with an integer cursor.
+#[derive(Debug, Default)]
+pub(super) struct Keyspace {
+    values: IndexedMap<Value>,
+    order: ScanOrder,
+}
+
+impl Keyspace {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &RedisString) -> bool {
+        self.values.contains_key(key)
+    }
+
+    pub fn get(&self, key: &RedisString) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &RedisString) -> Option<&mut Value> {
+        self.values.get_mut(key)
+    }
+
+    pub fn insert(&mut self, key: RedisString, value: Value) -> Option<Value> {
+        if !self.values.contains_key(&key) {
+            self.order.insert(key.clone());
+        }
+        self.values.insert(key, value)
+    }
+
+    pub fn get_or_insert_with(
+        &mut self,
+        key: &RedisString,
+        default: impl FnOnce() -> Value,
+    ) -> &mut Value {
+        if !self.values.contains_key(key) {
+            self.insert(key.clone(), default());
+        }
+        self.values.get_mut(key).expect("key was inserted")
+    }
+
+    pub fn remove(&mut self, key: &RedisString) -> Option<Value> {
+        let value = self.values.remove(key)?;
+        self.order.remove(key);
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.order.clear();
+    }
+
+    /// Key at position, in no particular order.
+    pub fn key_at(&self, index: usize) -> Option<&RedisString> {
+        self.values.get_index(index).map(|(key, _)| key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &RedisString> {
+        self.values.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&RedisString, &Value)> {
+        self.values.iter()
+    }
+
+    /// Keys from cursor in scan order, see [`scan_ordered`].
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&RedisString>) {
+        self.order.scan(cursor, count)
+    }
+}
+
+impl Database {
+    /// Keys matching a glob pattern.
+    pub async fn keys_matching(&self, pattern: &[u8]) -> Vec<RedisString> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        content
+            .keys()
+            .filter(|key| !is_expired(&expiry_millis, key))
+            .filter(|key| glob_match(pattern, key.as_slice()))
+            .cloned()
+            .collect()
+    }
+
+    /// Visit keys from cursor, returning the next cursor, zero once done, and visited keys
+    /// matching options.
+    pub async fn scan(&self, cursor: u64, options: &ScanOptions) -> (u64, Vec<RedisString>) {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let (cursor, keys) = content.scan(cursor, options.count);
+        let keys = keys
+            .into_iter()
+            .filter(|key| !is_expired(&expiry_millis, key) && options.matches(key))
+            .filter(|key| match (&options.type_name, content.get(key)) {
+                (Some(type_name), Some(value)) => value.type_name().eq_ignore_ascii_case(type_name),
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        (cursor, keys)
+    }
+}
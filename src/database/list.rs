@@ -0,0 +1,354 @@
+use std::{collections::VecDeque, sync::atomic::Ordering};
+
+use super::{range_bounds, Database, KeyWaiter, Value};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Side of a list where elements are pushed or popped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+impl ListEnd {
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        if input.eq_ignore_ascii_case(b"LEFT") {
+            Some(Self::Left)
+        } else if input.eq_ignore_ascii_case(b"RIGHT") {
+            Some(Self::Right)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Self::Left => b"LEFT",
+            Self::Right => b"RIGHT",
+        }
+    }
+}
+
+fn as_list(value: Option<&Value>) -> Result<Option<&VecDeque<RedisString>>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+fn as_list_mut(
+    value: Option<&mut Value>,
+) -> Result<Option<&mut VecDeque<RedisString>>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+fn push(list: &mut VecDeque<RedisString>, end: ListEnd, element: RedisString) {
+    match end {
+        ListEnd::Left => list.push_front(element),
+        ListEnd::Right => list.push_back(element),
+    }
+}
+
+fn pop(list: &mut VecDeque<RedisString>, end: ListEnd) -> Option<RedisString> {
+    match end {
+        ListEnd::Left => list.pop_front(),
+        ListEnd::Right => list.pop_back(),
+    }
+}
+
+/// Convert a possibly negative index to a position in list.
+fn list_position(list: &VecDeque<RedisString>, index: i64) -> Option<usize> {
+    let index = if index < 0 {
+        index + list.len() as i64
+    } else {
+        index
+    };
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < list.len())
+}
+
+impl Database {
+    /// Push elements one by one and return new list length.
+    pub async fn list_push(
+        &self,
+        key: &RedisString,
+        end: ListEnd,
+        elements: Vec<RedisString>,
+    ) -> Result<usize, MiniRedisError> {
+        let len = self
+            .update(key, |value| {
+                let Value::List(list) = value.get_or_insert_with(|| Value::List(VecDeque::new()))
+                else {
+                    return Err(MiniRedisError::WrongType);
+                };
+                for element in elements {
+                    push(list, end, element);
+                }
+                Ok((list.len(), true))
+            })
+            .await?;
+        self.wake_blocked(key, len);
+        Ok(len)
+    }
+
+    /// Pop up to `count` elements, or `None` if key does not exist.
+    pub async fn list_pop(
+        &self,
+        key: &RedisString,
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<Vec<RedisString>>, MiniRedisError> {
+        self.update(key, |value| {
+            let Some(list) = as_list_mut(value.as_mut())? else {
+                return Ok((None, false));
+            };
+            let count = count.min(list.len());
+            let elements = (0..count).filter_map(|_| pop(list, end)).collect();
+            Ok((Some(elements), count > 0))
+        })
+        .await
+    }
+
+    pub async fn list_len(&self, key: &RedisString) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_list(value)?.map(|list| list.len()).unwrap_or(0))
+        })
+        .await
+    }
+
+    /// Get elements between `start` and `stop` included.
+    pub async fn list_range(
+        &self,
+        key: &RedisString,
+        start: i64,
+        stop: i64,
+    ) -> Result<Vec<RedisString>, MiniRedisError> {
+        self.read(key, |value| {
+            let Some(list) = as_list(value)? else {
+                return Ok(Vec::new());
+            };
+            Ok(match range_bounds(list.len(), start, stop) {
+                Some((start, stop)) => list.range(start..=stop).cloned().collect(),
+                None => Vec::new(),
+            })
+        })
+        .await
+    }
+
+    pub async fn list_index(
+        &self,
+        key: &RedisString,
+        index: i64,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        self.read(key, |value| {
+            let Some(list) = as_list(value)? else {
+                return Ok(None);
+            };
+            Ok(list_position(list, index).map(|index| list[index].clone()))
+        })
+        .await
+    }
+
+    pub async fn list_set(
+        &self,
+        key: &RedisString,
+        index: i64,
+        element: RedisString,
+    ) -> Result<(), MiniRedisError> {
+        self.update(key, |value| {
+            let list = as_list_mut(value.as_mut())?.ok_or(MiniRedisError::NoSuchKey)?;
+            let index = list_position(list, index).ok_or(MiniRedisError::IndexOutOfRange)?;
+            list[index] = element;
+            Ok(((), true))
+        })
+        .await
+    }
+
+    /// Remove `count` occurrences of element from head (or from tail if negative, or all if 0).
+    pub async fn list_remove(
+        &self,
+        key: &RedisString,
+        count: i64,
+        element: &RedisString,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let Some(list) = as_list_mut(value.as_mut())? else {
+                return Ok((0, false));
+            };
+            let limit = match count {
+                0 => usize::MAX,
+                count => count.unsigned_abs() as usize,
+            };
+
+            let mut removed = 0;
+            if count < 0 {
+                let mut index = list.len();
+                while index > 0 && removed < limit {
+                    index -= 1;
+                    if &list[index] == element {
+                        list.remove(index);
+                        removed += 1;
+                    }
+                }
+            } else {
+                let mut index = 0;
+                while index < list.len() && removed < limit {
+                    if &list[index] == element {
+                        list.remove(index);
+                        removed += 1;
+                    } else {
+                        index += 1;
+                    }
+                }
+            }
+            Ok((removed, removed > 0))
+        })
+        .await
+    }
+
+    /// Keep only elements between `start` and `stop` included.
+    pub async fn list_trim(
+        &self,
+        key: &RedisString,
+        start: i64,
+        stop: i64,
+    ) -> Result<(), MiniRedisError> {
+        self.update(key, |value| {
+            let Some(list) = as_list_mut(value.as_mut())? else {
+                return Ok(((), false));
+            };
+            let len = list.len();
+            match range_bounds(len, start, stop) {
+                Some((start, stop)) => {
+                    list.truncate(stop + 1);
+                    list.drain(..start);
+                }
+                None => list.clear(),
+            }
+            Ok(((), list.len() < len))
+        })
+        .await
+    }
+
+    /// Insert element next to pivot.
+    ///
+    /// Returns new list length, -1 if pivot is not found and 0 if key does not exist.
+    pub async fn list_insert(
+        &self,
+        key: &RedisString,
+        before: bool,
+        pivot: &RedisString,
+        element: RedisString,
+    ) -> Result<i64, MiniRedisError> {
+        self.update(key, |value| {
+            let Some(list) = as_list_mut(value.as_mut())? else {
+                return Ok((0, false));
+            };
+            let Some(index) = list.iter().position(|x| x == pivot) else {
+                return Ok((-1, false));
+            };
+            list.insert(if before { index } else { index + 1 }, element);
+            Ok((list.len() as i64, true))
+        })
+        .await
+    }
+
+    /// Atomically pop an element from source and push it to destination.
+    pub async fn list_move(
+        &self,
+        source: &RedisString,
+        destination: &RedisString,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        self.move_element(source, destination, from, to, None).await
+    }
+
+    /// Move element on behalf of a blocked client, which may have to let older ones be served.
+    async fn move_element(
+        &self,
+        source: &RedisString,
+        destination: &RedisString,
+        from: ListEnd,
+        to: ListEnd,
+        waiter: Option<&KeyWaiter<'_>>,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, source);
+        self.purge_expired(&mut content, &mut expiry_millis, destination);
+
+        as_list(content.get(destination))?;
+        let Some(list) = as_list_mut(content.get_mut(source))? else {
+            return Ok(None);
+        };
+        if matches!(waiter, Some(waiter) if !waiter.is_served(source, list.len())) {
+            return Ok(None);
+        }
+        let Some(element) = pop(list, from) else {
+            return Ok(None);
+        };
+
+        let value = content.get_or_insert_with(destination, || Value::List(VecDeque::new()));
+        let mut len = 0;
+        if let Value::List(list) = value {
+            push(list, to, element.clone());
+            len = list.len();
+        }
+        if matches!(content.get(source), Some(value) if value.is_empty_collection()) {
+            content.remove(source);
+            expiry_millis.remove(source);
+        }
+
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        self.wake_blocked(destination, len);
+        Ok(Some(element))
+    }
+}
+
+impl KeyWaiter<'_> {
+    /// Pop an element from the first non empty list, returning it with its key.
+    pub async fn pop(
+        &self,
+        end: ListEnd,
+    ) -> Result<Option<(RedisString, RedisString)>, MiniRedisError> {
+        for key in self.keys() {
+            let element = self
+                .database
+                .update(key, |value| match as_list_mut(value.as_mut())? {
+                    Some(list) if self.is_served(key, list.len()) => {
+                        let element = pop(list, end);
+                        let popped = element.is_some();
+                        Ok((element, popped))
+                    }
+                    _ => Ok((None, false)),
+                })
+                .await?;
+            if let Some(element) = element {
+                return Ok(Some((key.clone(), element)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Move an element from the first watched list to destination.
+    pub async fn move_to(
+        &self,
+        destination: &RedisString,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        let Some(source) = self.keys().first() else {
+            return Ok(None);
+        };
+        self.database
+            .move_element(source, destination, from, to, Some(self))
+            .await
+    }
+}
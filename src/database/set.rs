@@ -0,0 +1,355 @@
+use std::{collections::HashSet, sync::atomic::Ordering};
+
+use super::{
+    is_expired, keyspace::ScanOrder, random_sample, Database, IndexedMap, Keyspace, ScanOptions,
+    Value,
+};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Distinct members, also ordered by hash so that they can be scanned.
+#[derive(Debug, Clone, Default)]
+pub struct Set {
+    members: HashSet<RedisString>,
+    order: ScanOrder,
+}
+
+impl PartialEq for Set {
+    fn eq(&self, other: &Self) -> bool {
+        self.members == other.members
+    }
+}
+
+impl Eq for Set {}
+
+impl Set {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn contains(&self, member: &RedisString) -> bool {
+        self.members.contains(member)
+    }
+
+    /// Add member, returning `true` if it was not in set.
+    pub fn insert(&mut self, member: RedisString) -> bool {
+        if self.members.contains(&member) {
+            return false;
+        }
+        self.order.insert(member.clone());
+        self.members.insert(member)
+    }
+
+    pub fn remove(&mut self, member: &RedisString) -> bool {
+        if !self.members.remove(member) {
+            return false;
+        }
+        self.order.remove(member);
+        true
+    }
+
+    /// Iterate over members, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &RedisString> {
+        self.members.iter()
+    }
+
+    /// Members from cursor in scan order.
+    fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&RedisString>) {
+        self.order.scan(cursor, count)
+    }
+}
+
+impl FromIterator<RedisString> for Set {
+    fn from_iter<I: IntoIterator<Item = RedisString>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for member in iter {
+            set.insert(member);
+        }
+        set
+    }
+}
+
+impl<const N: usize> From<[RedisString; N]> for Set {
+    fn from(members: [RedisString; N]) -> Self {
+        members.into_iter().collect()
+    }
+}
+
+/// Operation combining several sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOperation {
+    Intersection,
+    Union,
+    Difference,
+}
+
+fn as_set(value: Option<&Value>) -> Result<Option<&Set>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+fn as_set_mut(value: &mut Option<Value>) -> Result<&mut Set, MiniRedisError> {
+    match value.get_or_insert_with(|| Value::Set(Set::new())) {
+        Value::Set(set) => Ok(set),
+        _ => Err(MiniRedisError::WrongType),
+    }
+}
+
+/// Combine sets stored at keys, missing keys being empty sets.
+fn combine(
+    content: &Keyspace,
+    expiry_millis: &IndexedMap<u64>,
+    operation: SetOperation,
+    keys: &[RedisString],
+) -> Result<HashSet<RedisString>, MiniRedisError> {
+    let mut sets = Vec::with_capacity(keys.len());
+    for key in keys {
+        let value = content.get(key).filter(|_| !is_expired(expiry_millis, key));
+        sets.push(as_set(value)?);
+    }
+    let empty = Set::new();
+    let mut sets = sets.into_iter().map(|set| set.unwrap_or(&empty));
+    let Some(first) = sets.next() else {
+        return Ok(HashSet::new());
+    };
+
+    let mut output: HashSet<_> = first.iter().cloned().collect();
+    for set in sets {
+        match operation {
+            SetOperation::Intersection => output.retain(|member| set.contains(member)),
+            SetOperation::Union => output.extend(set.iter().cloned()),
+            SetOperation::Difference => output.retain(|member| !set.contains(member)),
+        }
+    }
+    Ok(output)
+}
+
+impl Database {
+    /// Add members and return how many were not already in set.
+    pub async fn set_add(
+        &self,
+        key: &RedisString,
+        members: Vec<RedisString>,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let set = as_set_mut(value)?;
+            let added = members
+                .into_iter()
+                .filter(|member| set.insert(member.clone()))
+                .count();
+            Ok((added, added > 0))
+        })
+        .await
+    }
+
+    /// Remove members and return how many were in set.
+    pub async fn set_remove(
+        &self,
+        key: &RedisString,
+        members: &[RedisString],
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let set = as_set_mut(value)?;
+            let removed = members.iter().filter(|member| set.remove(member)).count();
+            Ok((removed, removed > 0))
+        })
+        .await
+    }
+
+    pub async fn set_members(&self, key: &RedisString) -> Result<Vec<RedisString>, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_set(value)?
+                .map(|set| set.iter().cloned().collect())
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Visit members from cursor, returning the next cursor and visited members matching
+    /// options.
+    pub async fn set_scan(
+        &self,
+        key: &RedisString,
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> Result<(u64, Vec<RedisString>), MiniRedisError> {
+        self.read(key, |value| {
+            let Some(set) = as_set(value)? else {
+                return Ok((0, Vec::new()));
+            };
+            let (cursor, members) = set.scan(cursor, options.count);
+            let members = members
+                .into_iter()
+                .filter(|member| options.matches(member))
+                .cloned()
+                .collect();
+            Ok((cursor, members))
+        })
+        .await
+    }
+
+    /// Check membership of each member, in the same order.
+    pub async fn set_contains(
+        &self,
+        key: &RedisString,
+        members: &[RedisString],
+    ) -> Result<Vec<bool>, MiniRedisError> {
+        self.read(key, |value| {
+            let set = as_set(value)?;
+            Ok(members
+                .iter()
+                .map(|member| set.is_some_and(|set| set.contains(member)))
+                .collect())
+        })
+        .await
+    }
+
+    pub async fn set_len(&self, key: &RedisString) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| Ok(as_set(value)?.map_or(0, Set::len)))
+            .await
+    }
+
+    /// Remove and return up to `count` random members.
+    pub async fn set_pop(
+        &self,
+        key: &RedisString,
+        count: usize,
+    ) -> Result<Vec<RedisString>, MiniRedisError> {
+        self.update(key, |value| {
+            if value.is_none() {
+                return Ok((Vec::new(), false));
+            }
+            let set = as_set_mut(value)?;
+            let members = random_sample(set.iter().cloned().collect(), count as i64);
+            for member in &members {
+                set.remove(member);
+            }
+            let popped = !members.is_empty();
+            Ok((members, popped))
+        })
+        .await
+    }
+
+    /// Pick random members, distinct for a positive count or possibly repeated for a negative one.
+    pub async fn set_random_members(
+        &self,
+        key: &RedisString,
+        count: i64,
+    ) -> Result<Vec<RedisString>, MiniRedisError> {
+        self.read(key, |value| {
+            let Some(set) = as_set(value)? else {
+                return Ok(Vec::new());
+            };
+            Ok(random_sample(set.iter().collect(), count)
+                .into_iter()
+                .cloned()
+                .collect())
+        })
+        .await
+    }
+
+    /// Atomically move member from source to destination set.
+    ///
+    /// Returns `false` if member was not in source. Moving to the same set changes nothing.
+    pub async fn set_move(
+        &self,
+        source: &RedisString,
+        destination: &RedisString,
+        member: RedisString,
+    ) -> Result<bool, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, source);
+        self.purge_expired(&mut content, &mut expiry_millis, destination);
+
+        as_set(content.get(destination))?;
+        if source == destination {
+            // Nothing to move, key and its expiry are left untouched
+            return Ok(as_set(content.get(source))?.is_some_and(|set| set.contains(&member)));
+        }
+        let removed = match content.get_mut(source) {
+            None => return Ok(false),
+            Some(Value::Set(set)) => set.remove(&member),
+            Some(_) => return Err(MiniRedisError::WrongType),
+        };
+        if !removed {
+            return Ok(false);
+        }
+
+        if matches!(content.get(source), Some(value) if value.is_empty_collection()) {
+            content.remove(source);
+            expiry_millis.remove(source);
+        }
+        if let Value::Set(set) = content.get_or_insert_with(destination, || Value::Set(Set::new()))
+        {
+            set.insert(member);
+        }
+
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Combine sets stored at keys.
+    pub async fn set_combine(
+        &self,
+        operation: SetOperation,
+        keys: &[RedisString],
+    ) -> Result<Vec<RedisString>, MiniRedisError> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let output = combine(&content, &expiry_millis, operation, keys)?;
+        Ok(output.into_iter().collect())
+    }
+
+    /// Combine sets stored at keys and store result at destination, replacing any value.
+    ///
+    /// Returns the size of the resulting set.
+    pub async fn set_combine_store(
+        &self,
+        operation: SetOperation,
+        destination: &RedisString,
+        keys: &[RedisString],
+    ) -> Result<usize, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+
+        let output = combine(&content, &expiry_millis, operation, keys)?;
+        let len = output.len();
+        expiry_millis.remove(destination);
+        if output.is_empty() {
+            content.remove(destination);
+        } else {
+            content.insert(
+                destination.clone(),
+                Value::Set(output.into_iter().collect()),
+            );
+        }
+
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(len)
+    }
+
+    /// Count members of the intersection of sets, stopping at `limit` unless it is zero.
+    pub async fn set_intersection_len(
+        &self,
+        keys: &[RedisString],
+        limit: usize,
+    ) -> Result<usize, MiniRedisError> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let len = combine(&content, &expiry_millis, SetOperation::Intersection, keys)?.len();
+        Ok(if limit == 0 { len } else { len.min(limit) })
+    }
+}
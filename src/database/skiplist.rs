@@ -0,0 +1,237 @@
+use std::cmp::Ordering;
+
+use super::random_index;
+use crate::rdb::RedisString;
+
+const MAX_LEVEL: usize = 32;
+const HEAD: usize = 0;
+const NIL: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    next: usize,
+    /// Number of nodes skipped by following this link.
+    span: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    score: f64,
+    member: RedisString,
+    links: Vec<Link>,
+}
+
+/// Compare elements by score, then by member.
+fn compare(score: f64, member: &RedisString, other_score: f64, other: &RedisString) -> Ordering {
+    score
+        .partial_cmp(&other_score)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| member.cmp(other))
+}
+
+/// Level of a new node, each level being four times less likely than the previous one.
+fn random_level() -> usize {
+    let mut level = 1;
+    while level < MAX_LEVEL && random_index(4) == 0 {
+        level += 1;
+    }
+    level
+}
+
+/// Skiplist of elements ordered by score then member, with spans allowing access by rank.
+///
+/// Nodes are stored in an arena, the first one being the head.
+#[derive(Debug, Clone)]
+pub(super) struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let head = Node {
+            score: 0.0,
+            member: RedisString::new(b""),
+            links: vec![Link { next: NIL, span: 0 }; MAX_LEVEL],
+        };
+        Self {
+            nodes: vec![head],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+        }
+    }
+}
+
+impl SkipList {
+    /// Find the last node before element at each level, with its rank.
+    fn predecessors(
+        &self,
+        score: f64,
+        member: &RedisString,
+    ) -> ([usize; MAX_LEVEL], [usize; MAX_LEVEL]) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i + 1 == self.level { 0 } else { rank[i + 1] };
+            loop {
+                let link = &self.nodes[x].links[i];
+                if link.next == NIL {
+                    break;
+                }
+                let next = &self.nodes[link.next];
+                if compare(next.score, &next.member, score, member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += link.span;
+                x = link.next;
+            }
+            update[i] = x;
+        }
+        (update, rank)
+    }
+
+    /// Insert an element, which must not already be in list.
+    pub fn insert(&mut self, score: f64, member: RedisString) {
+        let (mut update, mut rank) = self.predecessors(score, &member);
+
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].links[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            score,
+            member,
+            links: vec![Link { next: NIL, span: 0 }; level],
+        };
+        let x = match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        for i in 0..level {
+            let before = &mut self.nodes[update[i]].links[i];
+            let next = before.next;
+            let span = before.span;
+            before.next = x;
+            before.span = rank[0] - rank[i] + 1;
+            self.nodes[x].links[i] = Link {
+                next,
+                span: span - (rank[0] - rank[i]),
+            };
+        }
+        for (i, &before) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[before].links[i].span += 1;
+        }
+        self.len += 1;
+    }
+
+    /// Remove an element, returning `false` if it was not in list.
+    pub fn remove(&mut self, score: f64, member: &RedisString) -> bool {
+        let (update, _) = self.predecessors(score, member);
+        let x = self.nodes[update[0]].links[0].next;
+        if x == NIL || self.nodes[x].score != score || &self.nodes[x].member != member {
+            return false;
+        }
+
+        for (i, &before) in update.iter().enumerate().take(self.level) {
+            if self.nodes[before].links[i].next == x {
+                let Link { next, span } = self.nodes[x].links[i];
+                let link = &mut self.nodes[before].links[i];
+                link.span += span;
+                link.span -= 1;
+                link.next = next;
+            } else {
+                self.nodes[before].links[i].span -= 1;
+            }
+        }
+        while self.level > 1 && self.nodes[HEAD].links[self.level - 1].next == NIL {
+            self.level -= 1;
+        }
+
+        self.nodes[x].member = RedisString::new(b"");
+        self.nodes[x].links.clear();
+        self.free.push(x);
+        self.len -= 1;
+        true
+    }
+
+    /// Count leading elements matching `f`, which must match a prefix of the list.
+    pub fn count_while(&self, f: impl Fn(f64, &RedisString) -> bool) -> usize {
+        let mut count = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let link = &self.nodes[x].links[i];
+                if link.next == NIL {
+                    break;
+                }
+                let next = &self.nodes[link.next];
+                if !f(next.score, &next.member) {
+                    break;
+                }
+                count += link.span;
+                x = link.next;
+            }
+        }
+        count
+    }
+
+    /// Rank of an element in list, assuming it is present.
+    pub fn rank(&self, score: f64, member: &RedisString) -> usize {
+        self.count_while(|other_score, other| {
+            compare(other_score, other, score, member) == Ordering::Less
+        })
+    }
+
+    /// Node at a zero based rank.
+    fn node_at(&self, rank: usize) -> Option<usize> {
+        if rank >= self.len {
+            return None;
+        }
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            loop {
+                let link = &self.nodes[x].links[i];
+                if link.next == NIL || traversed + link.span > rank + 1 {
+                    break;
+                }
+                traversed += link.span;
+                x = link.next;
+            }
+            if traversed == rank + 1 {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// Iterate over elements in order starting at a zero based rank.
+    pub fn iter_from(&self, rank: usize) -> impl Iterator<Item = (f64, &RedisString)> + '_ {
+        let mut x = self.node_at(rank).unwrap_or(NIL);
+        std::iter::from_fn(move || {
+            if x == NIL {
+                return None;
+            }
+            let node = &self.nodes[x];
+            x = node.links[0].next;
+            Some((node.score, &node.member))
+        })
+    }
+}
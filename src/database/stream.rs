@@ -0,0 +1,475 @@
+use std::{collections::BTreeMap, fmt, sync::atomic::Ordering};
+
+use super::{is_expired, now_unix_millis, ConsumerGroup, Database, Value};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// ID of a stream entry, made of a millisecond timestamp and a sequence number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: Self = Self { ms: 0, seq: 0 };
+    pub const MAX: Self = Self {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// Parse `ms-seq`, or `ms` alone completed with `missing_seq`.
+    pub fn parse(input: &[u8], missing_seq: u64) -> Option<Self> {
+        let input = std::str::from_utf8(input).ok()?;
+        let (ms, seq) = match input.split_once('-') {
+            Some((ms, seq)) => (ms.parse().ok()?, seq.parse().ok()?),
+            None => (input.parse().ok()?, missing_seq),
+        };
+        Some(Self { ms, seq })
+    }
+
+    /// Parse the start of a range, like `-`, `1-2`, `1` or `(1-2` when exclusive.
+    pub fn parse_start(input: &[u8]) -> Option<Self> {
+        match input {
+            b"-" => Some(Self::MIN),
+            [b'(', id @ ..] => Self::parse(id, 0)?.next(),
+            _ => Self::parse(input, 0),
+        }
+    }
+
+    /// Parse the end of a range, like `+`, `1-2`, `1` or `(1-2` when exclusive.
+    pub fn parse_end(input: &[u8]) -> Option<Self> {
+        match input {
+            b"+" => Some(Self::MAX),
+            [b'(', id @ ..] => Self::parse(id, u64::MAX)?.prev(),
+            _ => Self::parse(input, u64::MAX),
+        }
+    }
+
+    /// Smallest ID greater than this one.
+    pub fn next(self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(Self { ms: self.ms, seq }),
+            None => Some(Self {
+                ms: self.ms.checked_add(1)?,
+                seq: 0,
+            }),
+        }
+    }
+
+    /// Greatest ID smaller than this one.
+    pub fn prev(self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(Self { ms: self.ms, seq }),
+            None => Some(Self {
+                ms: self.ms.checked_sub(1)?,
+                seq: u64::MAX,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl From<StreamId> for RedisString {
+    fn from(id: StreamId) -> Self {
+        RedisString::from(id.to_string().into_bytes())
+    }
+}
+
+/// ID requested for an entry added by `XADD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XAddId {
+    /// `*`, generated from current time.
+    Auto,
+    /// `ms-*`, with a generated sequence number.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+impl XAddId {
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        match input {
+            b"*" => Some(Self::Auto),
+            [ms @ .., b'-', b'*'] => {
+                let ms = std::str::from_utf8(ms).ok()?.parse().ok()?;
+                Some(Self::AutoSeq(ms))
+            }
+            _ => StreamId::parse(input, 0).map(Self::Explicit),
+        }
+    }
+}
+
+/// Number of entries of a stream node, which approximate trimming removes at once.
+pub const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// Strategy removing oldest stream entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamTrim {
+    /// Keep at most this number of entries.
+    MaxLen(usize),
+    /// Remove entries with a smaller ID.
+    MinId(StreamId),
+}
+
+/// Trimming requested by `XADD` or `XTRIM`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTrimOptions {
+    pub trim: StreamTrim,
+    /// Only remove whole nodes of entries, possibly keeping some more than requested.
+    pub approximate: bool,
+    /// Most entries removed by approximate trimming, zero for no limit.
+    pub limit: usize,
+}
+
+impl From<StreamTrim> for StreamTrimOptions {
+    /// Exact trimming.
+    fn from(trim: StreamTrim) -> Self {
+        Self {
+            trim,
+            approximate: false,
+            limit: 0,
+        }
+    }
+}
+
+/// Options of `XADD`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XAddOptions {
+    /// Do not create stream if it does not exist.
+    pub nomkstream: bool,
+    /// Trimming applied after entry is added.
+    pub trim: Option<StreamTrimOptions>,
+}
+
+/// Entry ID with its fields and values.
+pub type StreamEntry = (StreamId, Vec<(RedisString, RedisString)>);
+
+/// Summary returned by `XINFO STREAM`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub length: usize,
+    pub last_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub first_id: StreamId,
+    pub groups: usize,
+    pub first_entry: Option<StreamEntry>,
+    pub last_entry: Option<StreamEntry>,
+}
+
+/// Append only log of entries ordered by ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stream {
+    pub entries: BTreeMap<StreamId, Vec<(RedisString, RedisString)>>,
+    /// Greatest ID ever added, even if entry was removed since.
+    pub last_id: StreamId,
+    /// Greatest ID of an entry removed by `XDEL`.
+    pub max_deleted_id: StreamId,
+    /// Number of entries added during stream lifetime.
+    pub entries_added: u64,
+    pub groups: BTreeMap<RedisString, ConsumerGroup>,
+}
+
+impl Stream {
+    /// ID of the first entry, `0-0` if stream is empty.
+    pub fn first_id(&self) -> StreamId {
+        self.entries.keys().next().copied().unwrap_or_default()
+    }
+
+    /// Resolve ID of a new entry, which must be greater than all previous ones.
+    fn next_id(&self, id: XAddId) -> Result<StreamId, MiniRedisError> {
+        let last = self.last_id;
+        match id {
+            XAddId::Auto => {
+                let ms = now_unix_millis();
+                if ms > last.ms {
+                    Ok(StreamId { ms, seq: 0 })
+                } else {
+                    last.next().ok_or(MiniRedisError::StreamExhausted)
+                }
+            }
+            XAddId::AutoSeq(ms) if ms > last.ms => Ok(StreamId { ms, seq: 0 }),
+            XAddId::AutoSeq(ms) if ms == last.ms => last
+                .seq
+                .checked_add(1)
+                .map(|seq| StreamId { ms, seq })
+                .ok_or(MiniRedisError::StreamIdTooSmall),
+            XAddId::AutoSeq(_) => Err(MiniRedisError::StreamIdTooSmall),
+            XAddId::Explicit(StreamId::MIN) => Err(MiniRedisError::StreamIdZero),
+            XAddId::Explicit(id) if id <= last => Err(MiniRedisError::StreamIdTooSmall),
+            XAddId::Explicit(id) => Ok(id),
+        }
+    }
+
+    fn add(
+        &mut self,
+        id: XAddId,
+        fields: Vec<(RedisString, RedisString)>,
+    ) -> Result<StreamId, MiniRedisError> {
+        let id = self.next_id(id)?;
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+        Ok(id)
+    }
+
+    /// Remove oldest entries and return how many were removed.
+    fn trim(&mut self, options: StreamTrimOptions) -> usize {
+        let excess = match options.trim {
+            StreamTrim::MaxLen(max_len) => self.entries.len().saturating_sub(max_len),
+            StreamTrim::MinId(min_id) => self.entries.range(..min_id).count(),
+        };
+        let count = if options.approximate {
+            let limit = if options.limit == 0 {
+                usize::MAX
+            } else {
+                options.limit
+            };
+            excess.min(limit) / STREAM_NODE_MAX_ENTRIES * STREAM_NODE_MAX_ENTRIES
+        } else {
+            excess
+        };
+        for _ in 0..count {
+            self.entries.pop_first();
+        }
+        count
+    }
+
+    /// Entries between inclusive IDs, from the last one when `rev` is set.
+    pub(super) fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<StreamEntry> {
+        if start > end {
+            return Vec::new();
+        }
+        let range = self.entries.range(start..=end);
+        let count = count.unwrap_or(usize::MAX);
+        let clone = |(id, fields): (&StreamId, &Vec<_>)| (*id, fields.clone());
+        if rev {
+            range.rev().take(count).map(clone).collect()
+        } else {
+            range.take(count).map(clone).collect()
+        }
+    }
+}
+
+pub(super) fn as_stream(value: Option<&Value>) -> Result<Option<&Stream>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+pub(super) fn as_stream_mut(
+    value: Option<&mut Value>,
+) -> Result<Option<&mut Stream>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+impl Database {
+    /// Add an entry and return its ID, or `None` if stream does not exist and must not be
+    /// created.
+    pub async fn stream_add(
+        &self,
+        key: &RedisString,
+        options: XAddOptions,
+        id: XAddId,
+        fields: Vec<(RedisString, RedisString)>,
+    ) -> Result<Option<StreamId>, MiniRedisError> {
+        let id = self
+            .update(key, |value| {
+                // Stream is only created once its first entry is accepted
+                let (mut stream, created) = match value.take() {
+                    None if options.nomkstream => return Ok((None, false)),
+                    None => (Stream::default(), true),
+                    Some(Value::Stream(stream)) => (stream, false),
+                    Some(other) => {
+                        *value = Some(other);
+                        return Err(MiniRedisError::WrongType);
+                    }
+                };
+                let result = stream.add(id, fields);
+                if result.is_ok() {
+                    if let Some(trim) = options.trim {
+                        stream.trim(trim);
+                    }
+                }
+                if result.is_ok() || !created {
+                    *value = Some(Value::Stream(stream));
+                }
+                result.map(|id| (Some(id), true))
+            })
+            .await?;
+        if id.is_some() {
+            self.wake_blocked(key, usize::MAX);
+        }
+        Ok(id)
+    }
+
+    pub async fn stream_len(&self, key: &RedisString) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_stream(value)?.map_or(0, |stream| stream.entries.len()))
+        })
+        .await
+    }
+
+    /// Entries between inclusive IDs, from the last one when `rev` is set.
+    pub async fn stream_range(
+        &self,
+        key: &RedisString,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Result<Vec<StreamEntry>, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_stream(value)?
+                .map(|stream| stream.range(start, end, count, rev))
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Remove entries and return how many existed.
+    pub async fn stream_delete(
+        &self,
+        key: &RedisString,
+        ids: &[StreamId],
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let Some(stream) = as_stream_mut(value.as_mut())? else {
+                return Ok((0, false));
+            };
+            let mut deleted = 0;
+            for id in ids {
+                if stream.entries.remove(id).is_some() {
+                    stream.max_deleted_id = stream.max_deleted_id.max(*id);
+                    deleted += 1;
+                }
+            }
+            Ok((deleted, deleted > 0))
+        })
+        .await
+    }
+
+    /// Remove oldest entries and return how many were removed.
+    pub async fn stream_trim(
+        &self,
+        key: &RedisString,
+        trim: StreamTrimOptions,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let trimmed = as_stream_mut(value.as_mut())?.map_or(0, |stream| stream.trim(trim));
+            Ok((trimmed, trimmed > 0))
+        })
+        .await
+    }
+
+    /// Greatest ID ever added to stream, `0-0` if it does not exist.
+    pub async fn stream_last_id(&self, key: &RedisString) -> Result<StreamId, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_stream(value)?.map_or(StreamId::MIN, |stream| stream.last_id))
+        })
+        .await
+    }
+
+    /// Read up to `count` entries after given IDs, only returning streams having some.
+    pub async fn stream_read(
+        &self,
+        streams: &[(RedisString, StreamId)],
+        count: Option<usize>,
+    ) -> Result<Vec<(RedisString, Vec<StreamEntry>)>, MiniRedisError> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let mut output = Vec::new();
+        for (key, id) in streams {
+            let value = content
+                .get(key)
+                .filter(|_| !is_expired(&expiry_millis, key));
+            let Some(stream) = as_stream(value)? else {
+                continue;
+            };
+            let Some(start) = id.next() else {
+                continue;
+            };
+            let entries = stream.range(start, StreamId::MAX, count, false);
+            if !entries.is_empty() {
+                output.push((key.clone(), entries));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Change metadata of an existing stream, used to restore it exactly.
+    pub async fn stream_set_id(
+        &self,
+        key: &RedisString,
+        last_id: StreamId,
+        entries_added: Option<u64>,
+        max_deleted_id: Option<StreamId>,
+    ) -> Result<(), MiniRedisError> {
+        let mut content = self.content.write().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let value = content
+            .get_mut(key)
+            .filter(|_| !is_expired(&expiry_millis, key));
+        let stream = as_stream_mut(value)?.ok_or(MiniRedisError::NoSuchKey)?;
+        if stream
+            .entries
+            .keys()
+            .next_back()
+            .is_some_and(|top| *top > last_id)
+        {
+            return Err(MiniRedisError::StreamSetIdTooSmall);
+        }
+        if entries_added.is_some_and(|added| added < stream.entries.len() as u64) {
+            return Err(MiniRedisError::StreamEntriesAddedTooSmall);
+        }
+        if max_deleted_id.is_some_and(|max_deleted_id| max_deleted_id > last_id) {
+            return Err(MiniRedisError::StreamMaxDeletedIdTooBig);
+        }
+
+        stream.last_id = last_id;
+        if let Some(entries_added) = entries_added {
+            stream.entries_added = entries_added;
+        }
+        if let Some(max_deleted_id) = max_deleted_id {
+            stream.max_deleted_id = max_deleted_id;
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub async fn stream_info(&self, key: &RedisString) -> Result<StreamInfo, MiniRedisError> {
+        self.read(key, |value| {
+            let stream = as_stream(value)?.ok_or(MiniRedisError::NoSuchKey)?;
+            let clone = |(id, fields): (&StreamId, &Vec<_>)| (*id, fields.clone());
+            Ok(StreamInfo {
+                length: stream.entries.len(),
+                last_id: stream.last_id,
+                max_deleted_id: stream.max_deleted_id,
+                entries_added: stream.entries_added,
+                first_id: stream.first_id(),
+                groups: stream.groups.len(),
+                first_entry: stream.entries.first_key_value().map(clone),
+                last_entry: stream.entries.last_key_value().map(clone),
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,675 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::atomic::Ordering,
+};
+
+use super::{
+    is_expired, now_unix_millis,
+    stream::{as_stream, as_stream_mut},
+    Database, Stream, StreamEntry, StreamId, Value,
+};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Entry delivered to a consumer and not acknowledged yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    pub consumer: RedisString,
+    /// Unix time of the last delivery in milliseconds.
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+/// Member of a consumer group.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Consumer {
+    /// Unix time of the last interaction in milliseconds.
+    pub seen_time: u64,
+    /// Unix time of the last read or claim in milliseconds, `None` if there was none.
+    pub active_time: Option<u64>,
+    /// IDs of entries delivered to this consumer and not acknowledged yet.
+    pub pending: BTreeSet<StreamId>,
+}
+
+/// Readers sharing the entries of a stream, each entry being delivered to a single consumer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsumerGroup {
+    /// ID of the last entry delivered to the group.
+    pub last_id: StreamId,
+    /// Number of entries read by the group, `None` when it is unknown.
+    pub entries_read: Option<u64>,
+    /// Entries delivered to any consumer and not acknowledged yet.
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<RedisString, Consumer>,
+}
+
+/// Entry read by a consumer, its fields being `None` if it was removed while pending.
+pub type GroupEntry = (StreamId, Option<Vec<(RedisString, RedisString)>>);
+
+/// Pending entries count, lowest and greatest pending IDs, and count per consumer.
+pub type PendingSummary = (
+    usize,
+    Option<(StreamId, StreamId)>,
+    Vec<(RedisString, usize)>,
+);
+
+/// Pending entry ID, its consumer, idle time in milliseconds and delivery count.
+pub type PendingInfo = (StreamId, RedisString, u64, u64);
+
+/// ID to continue scanning from, claimed entries and IDs of entries removed from stream.
+pub type AutoClaim = (StreamId, Vec<StreamEntry>, Vec<StreamId>);
+
+/// Options of `XCLAIM`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XClaimOptions {
+    /// Idle time of claimed entries in milliseconds.
+    pub idle: Option<u64>,
+    /// Unix time of the last delivery in milliseconds.
+    pub time: Option<u64>,
+    pub retry_count: Option<u64>,
+    /// Claim entries even if they are not pending.
+    pub force: bool,
+    /// Only return IDs, without counting a delivery.
+    pub justid: bool,
+    /// Last delivered ID of the group, set if greater.
+    pub last_id: Option<StreamId>,
+}
+
+/// Extended form of `XPENDING`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRange {
+    pub min_idle: Option<u64>,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    pub consumer: Option<RedisString>,
+}
+
+/// Group returned by `XINFO GROUPS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    pub name: RedisString,
+    pub consumers: usize,
+    pub pending: usize,
+    pub last_id: StreamId,
+    pub entries_read: Option<u64>,
+    /// Entries not delivered to the group yet, `None` if it cannot be known.
+    pub lag: Option<u64>,
+}
+
+/// Consumer returned by `XINFO CONSUMERS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerInfo {
+    pub name: RedisString,
+    pub pending: usize,
+    /// Milliseconds since the last interaction.
+    pub idle: u64,
+    /// Milliseconds since the last read or claim.
+    pub inactive: Option<u64>,
+}
+
+impl Stream {
+    /// Check if an entry with an ID greater than or equal to `id` was removed.
+    fn has_tombstones_from(&self, id: StreamId) -> bool {
+        !self.entries.is_empty()
+            && self.max_deleted_id != StreamId::MIN
+            && id <= self.max_deleted_id
+    }
+
+    /// Number of entries added up to `id`, `None` if removed entries prevent knowing it.
+    fn entries_until(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0
+            || id == self.last_id
+            || (self.entries.is_empty() && id < self.last_id)
+        {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+
+        let first_id = self.first_id();
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first_id {
+            let trimmed = self.entries_added.saturating_sub(self.entries.len() as u64);
+            if id < first_id {
+                return Some(trimmed);
+            } else if id == first_id {
+                return Some(trimmed + 1);
+            }
+        }
+        None
+    }
+
+    /// Number of entries not delivered to the group yet.
+    fn lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        let entries_read = match group.entries_read {
+            Some(read) if !self.has_tombstones_from(group.last_id) => Some(read),
+            _ => self.entries_until(group.last_id),
+        };
+        entries_read.map(|read| self.entries_added.saturating_sub(read))
+    }
+}
+
+impl ConsumerGroup {
+    pub fn new(last_id: StreamId, entries_read: Option<u64>) -> Self {
+        Self {
+            last_id,
+            entries_read,
+            ..Default::default()
+        }
+    }
+
+    /// Consumer with given name, created if needed, marked as seen.
+    fn consumer(&mut self, name: &RedisString, now: u64) -> &mut Consumer {
+        let consumer = self.consumers.entry(name.clone()).or_default();
+        consumer.seen_time = now;
+        consumer
+    }
+
+    /// Make an entry pending for a consumer, removing it from its previous owner.
+    fn assign(
+        &mut self,
+        id: StreamId,
+        consumer: &RedisString,
+        delivery_time: u64,
+        delivery_count: u64,
+    ) {
+        let entry = PendingEntry {
+            consumer: consumer.clone(),
+            delivery_time,
+            delivery_count,
+        };
+        if let Some(previous) = self.pending.insert(id, entry) {
+            if let Some(owner) = self.consumers.get_mut(&previous.consumer) {
+                owner.pending.remove(&id);
+            }
+        }
+        self.consumers
+            .entry(consumer.clone())
+            .or_default()
+            .pending
+            .insert(id);
+    }
+
+    /// Remove an entry from pending ones, returning whether it was pending.
+    fn acknowledge(&mut self, id: StreamId) -> bool {
+        let Some(entry) = self.pending.remove(&id) else {
+            return false;
+        };
+        if let Some(owner) = self.consumers.get_mut(&entry.consumer) {
+            owner.pending.remove(&id);
+        }
+        true
+    }
+
+    /// Deliver entries added after the last delivered one.
+    fn read_new(
+        &mut self,
+        stream: &Stream,
+        consumer: &RedisString,
+        count: Option<usize>,
+        noack: bool,
+        now: u64,
+    ) -> Vec<GroupEntry> {
+        self.consumer(consumer, now);
+        let Some(start) = self.last_id.next() else {
+            return Vec::new();
+        };
+        let entries = stream.range(start, StreamId::MAX, count, false);
+        for (id, _) in &entries {
+            self.entries_read = match self.entries_read {
+                Some(read) if !stream.has_tombstones_from(*id) => Some(read + 1),
+                _ => stream.entries_until(*id),
+            };
+            self.last_id = *id;
+            if !noack {
+                self.assign(*id, consumer, now, 1);
+            }
+        }
+        if !entries.is_empty() {
+            self.consumer(consumer, now).active_time = Some(now);
+        }
+        entries
+            .into_iter()
+            .map(|(id, fields)| (id, Some(fields)))
+            .collect()
+    }
+
+    /// Entries pending for consumer with an ID greater than `after`, without counting a delivery.
+    fn read_history(
+        &mut self,
+        stream: &Stream,
+        consumer: &RedisString,
+        after: StreamId,
+        count: Option<usize>,
+        now: u64,
+    ) -> Vec<GroupEntry> {
+        let consumer = self.consumer(consumer, now);
+        let Some(start) = after.next() else {
+            return Vec::new();
+        };
+        consumer
+            .pending
+            .range(start..)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|id| (*id, stream.entries.get(id).cloned()))
+            .collect()
+    }
+}
+
+fn no_group(key: &RedisString, group: &RedisString) -> MiniRedisError {
+    MiniRedisError::NoGroup {
+        key: String::from_utf8_lossy(key.as_slice()).into_owned(),
+        group: String::from_utf8_lossy(group.as_slice()).into_owned(),
+    }
+}
+
+/// Run `f` on a consumer group of the stream stored in value, along with the stream.
+fn with_group<T>(
+    value: Option<&mut Value>,
+    key: &RedisString,
+    name: &RedisString,
+    f: impl FnOnce(&Stream, &mut ConsumerGroup) -> T,
+) -> Result<T, MiniRedisError> {
+    let stream = as_stream_mut(value)?.ok_or_else(|| no_group(key, name))?;
+    let mut group = stream
+        .groups
+        .remove(name)
+        .ok_or_else(|| no_group(key, name))?;
+    let output = f(stream, &mut group);
+    stream.groups.insert(name.clone(), group);
+    Ok(output)
+}
+
+impl Database {
+    /// Create a group reading entries after `last_id`, `None` standing for the stream last ID.
+    pub async fn stream_group_create(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        last_id: Option<StreamId>,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    ) -> Result<(), MiniRedisError> {
+        self.update(key, |value| {
+            if value.is_none() && mkstream {
+                *value = Some(Value::Stream(Stream::default()));
+            }
+            let stream = as_stream_mut(value.as_mut())?.ok_or(MiniRedisError::StreamKeyRequired)?;
+            if stream.groups.contains_key(name) {
+                return Err(MiniRedisError::BusyGroup);
+            }
+            let last_id = last_id.unwrap_or(stream.last_id);
+            stream
+                .groups
+                .insert(name.clone(), ConsumerGroup::new(last_id, entries_read));
+            Ok(((), true))
+        })
+        .await
+    }
+
+    /// Remove a group and return whether it existed.
+    pub async fn stream_group_destroy(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+    ) -> Result<bool, MiniRedisError> {
+        self.update(key, |value| {
+            let stream = as_stream_mut(value.as_mut())?.ok_or(MiniRedisError::StreamKeyRequired)?;
+            let removed = stream.groups.remove(name).is_some();
+            Ok((removed, removed))
+        })
+        .await
+    }
+
+    /// Change last delivered ID of a group, `None` standing for the stream last ID.
+    pub async fn stream_group_set_id(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        last_id: Option<StreamId>,
+        entries_read: Option<u64>,
+    ) -> Result<(), MiniRedisError> {
+        self.update(key, |value| {
+            let stream = as_stream_mut(value.as_mut())?.ok_or(MiniRedisError::StreamKeyRequired)?;
+            let last_id = last_id.unwrap_or(stream.last_id);
+            let group = stream
+                .groups
+                .get_mut(name)
+                .ok_or_else(|| no_group(key, name))?;
+            group.last_id = last_id;
+            group.entries_read = entries_read;
+            Ok(((), true))
+        })
+        .await
+    }
+
+    /// Add a consumer to a group and return whether it was created.
+    pub async fn stream_consumer_create(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        consumer: &RedisString,
+    ) -> Result<bool, MiniRedisError> {
+        let now = now_unix_millis();
+        self.update(key, |value| {
+            if value.is_none() {
+                return Err(MiniRedisError::StreamKeyRequired);
+            }
+            with_group(value.as_mut(), key, name, |_, group| {
+                let created = !group.consumers.contains_key(consumer);
+                group.consumer(consumer, now);
+                (created, created)
+            })
+        })
+        .await
+    }
+
+    /// Remove a consumer from a group and return how many entries were pending for it.
+    pub async fn stream_consumer_delete(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        consumer: &RedisString,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            if value.is_none() {
+                return Err(MiniRedisError::StreamKeyRequired);
+            }
+            with_group(value.as_mut(), key, name, |_, group| {
+                let Some(removed) = group.consumers.remove(consumer) else {
+                    return (0, false);
+                };
+                for id in &removed.pending {
+                    group.pending.remove(id);
+                }
+                (removed.pending.len(), true)
+            })
+        })
+        .await
+    }
+
+    /// Read entries for a group consumer.
+    ///
+    /// Streams with a `None` ID get entries never delivered to the group, which become pending
+    /// unless `noack` is set, and are only returned if there are some. Other streams get
+    /// entries already pending for the consumer.
+    pub async fn stream_read_group(
+        &self,
+        name: &RedisString,
+        consumer: &RedisString,
+        streams: &[(RedisString, Option<StreamId>)],
+        count: Option<usize>,
+        noack: bool,
+    ) -> Result<Vec<(RedisString, Vec<GroupEntry>)>, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let expiry_millis = self.expiry_millis.read().await;
+        let now = now_unix_millis();
+
+        // Nothing is read unless all groups exist
+        for (key, _) in streams {
+            let value = content
+                .get(key)
+                .filter(|_| !is_expired(&expiry_millis, key));
+            if !as_stream(value)?.is_some_and(|stream| stream.groups.contains_key(name)) {
+                return Err(no_group(key, name));
+            }
+        }
+
+        let mut output = Vec::new();
+        for (key, id) in streams {
+            let value = content
+                .get_mut(key)
+                .filter(|_| !is_expired(&expiry_millis, key));
+            let entries = with_group(value, key, name, |stream, group| match id {
+                None => group.read_new(stream, consumer, count, noack, now),
+                Some(after) => group.read_history(stream, consumer, *after, count, now),
+            })?;
+            if id.is_some() || !entries.is_empty() {
+                output.push((key.clone(), entries));
+            }
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(output)
+    }
+
+    /// Acknowledge pending entries and return how many were pending.
+    pub async fn stream_ack(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        ids: &[StreamId],
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let group =
+                as_stream_mut(value.as_mut())?.and_then(|stream| stream.groups.get_mut(name));
+            let acknowledged = group.map_or(0, |group| {
+                ids.iter().filter(|id| group.acknowledge(**id)).count()
+            });
+            Ok((acknowledged, acknowledged > 0))
+        })
+        .await
+    }
+
+    pub async fn stream_pending_summary(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+    ) -> Result<PendingSummary, MiniRedisError> {
+        self.read(key, |value| {
+            let group = as_stream(value)?
+                .and_then(|stream| stream.groups.get(name))
+                .ok_or_else(|| no_group(key, name))?;
+            let bounds = group
+                .pending
+                .keys()
+                .next()
+                .zip(group.pending.keys().next_back())
+                .map(|(first, last)| (*first, *last));
+            let consumers = group
+                .consumers
+                .iter()
+                .filter(|(_, consumer)| !consumer.pending.is_empty())
+                .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+                .collect();
+            Ok((group.pending.len(), bounds, consumers))
+        })
+        .await
+    }
+
+    pub async fn stream_pending(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        range: &PendingRange,
+    ) -> Result<Vec<PendingInfo>, MiniRedisError> {
+        let now = now_unix_millis();
+        self.read(key, |value| {
+            let group = as_stream(value)?
+                .and_then(|stream| stream.groups.get(name))
+                .ok_or_else(|| no_group(key, name))?;
+            if range.start > range.end {
+                return Ok(Vec::new());
+            }
+            Ok(group
+                .pending
+                .range(range.start..=range.end)
+                .filter(|(_, entry)| match &range.consumer {
+                    Some(consumer) => entry.consumer == *consumer,
+                    None => true,
+                })
+                .map(|(id, entry)| {
+                    let idle = now.saturating_sub(entry.delivery_time);
+                    (*id, entry.consumer.clone(), idle, entry.delivery_count)
+                })
+                .filter(|(_, _, idle, _)| match range.min_idle {
+                    Some(min_idle) => *idle >= min_idle,
+                    None => true,
+                })
+                .take(range.count)
+                .collect())
+        })
+        .await
+    }
+
+    /// Give pending entries idle for at least `min_idle` milliseconds to a consumer.
+    ///
+    /// Entries removed from stream are acknowledged instead of being claimed.
+    pub async fn stream_claim(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        consumer: &RedisString,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: XClaimOptions,
+    ) -> Result<Vec<StreamEntry>, MiniRedisError> {
+        let now = now_unix_millis();
+        let delivery_time = match (options.idle, options.time) {
+            (Some(idle), _) => now.saturating_sub(idle),
+            (None, Some(time)) => time.min(now),
+            (None, None) => now,
+        };
+        self.update(key, |value| {
+            with_group(value.as_mut(), key, name, |stream, group| {
+                group.consumer(consumer, now);
+                if let Some(last_id) = options.last_id {
+                    group.last_id = group.last_id.max(last_id);
+                }
+
+                let mut claimed = Vec::new();
+                for id in ids {
+                    let Some(fields) = stream.entries.get(id) else {
+                        group.acknowledge(*id);
+                        continue;
+                    };
+                    let delivery_count = match group.pending.get(id) {
+                        Some(entry) if now.saturating_sub(entry.delivery_time) < min_idle => {
+                            continue
+                        }
+                        Some(entry) => entry.delivery_count,
+                        None if options.force => 0,
+                        None => continue,
+                    };
+                    let delivery_count = options.retry_count.unwrap_or(if options.justid {
+                        delivery_count
+                    } else {
+                        delivery_count + 1
+                    });
+                    group.assign(*id, consumer, delivery_time, delivery_count);
+                    group.consumer(consumer, now).active_time = Some(now);
+                    claimed.push((*id, fields.clone()));
+                }
+                (claimed, true)
+            })
+        })
+        .await
+    }
+
+    /// Claim up to `count` entries idle for at least `min_idle` milliseconds, scanning pending
+    /// entries from `start`.
+    ///
+    /// At most ten times `count` entries are scanned. Returned ID is `0-0` when scan is done.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn stream_auto_claim(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+        consumer: &RedisString,
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> Result<AutoClaim, MiniRedisError> {
+        let now = now_unix_millis();
+        self.update(key, |value| {
+            with_group(value.as_mut(), key, name, |stream, group| {
+                group.consumer(consumer, now);
+                let mut attempts = count.saturating_mul(10);
+                let scanned: Vec<_> = group
+                    .pending
+                    .range(start..)
+                    .map(|(id, entry)| (*id, entry.delivery_time, entry.delivery_count))
+                    .take(attempts.saturating_add(1))
+                    .collect();
+
+                let (mut cursor, mut claimed, mut deleted) =
+                    (StreamId::MIN, Vec::new(), Vec::new());
+                for (id, delivery_time, delivery_count) in scanned {
+                    if attempts == 0 || claimed.len() == count {
+                        cursor = id;
+                        break;
+                    }
+                    attempts -= 1;
+                    let Some(fields) = stream.entries.get(&id) else {
+                        group.acknowledge(id);
+                        deleted.push(id);
+                        continue;
+                    };
+                    if now.saturating_sub(delivery_time) < min_idle {
+                        continue;
+                    }
+                    let delivery_count = if justid {
+                        delivery_count
+                    } else {
+                        delivery_count + 1
+                    };
+                    group.assign(id, consumer, now, delivery_count);
+                    group.consumer(consumer, now).active_time = Some(now);
+                    claimed.push((id, fields.clone()));
+                }
+                ((cursor, claimed, deleted), true)
+            })
+        })
+        .await
+    }
+
+    pub async fn stream_groups_info(
+        &self,
+        key: &RedisString,
+    ) -> Result<Vec<GroupInfo>, MiniRedisError> {
+        self.read(key, |value| {
+            let stream = as_stream(value)?.ok_or(MiniRedisError::NoSuchKey)?;
+            Ok(stream
+                .groups
+                .iter()
+                .map(|(name, group)| GroupInfo {
+                    name: name.clone(),
+                    consumers: group.consumers.len(),
+                    pending: group.pending.len(),
+                    last_id: group.last_id,
+                    entries_read: group.entries_read,
+                    lag: stream.lag(group),
+                })
+                .collect())
+        })
+        .await
+    }
+
+    pub async fn stream_consumers_info(
+        &self,
+        key: &RedisString,
+        name: &RedisString,
+    ) -> Result<Vec<ConsumerInfo>, MiniRedisError> {
+        let now = now_unix_millis();
+        self.read(key, |value| {
+            let stream = as_stream(value)?.ok_or(MiniRedisError::NoSuchKey)?;
+            let group = stream.groups.get(name).ok_or_else(|| no_group(key, name))?;
+            Ok(group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| ConsumerInfo {
+                    name: name.clone(),
+                    pending: consumer.pending.len(),
+                    idle: now.saturating_sub(consumer.seen_time),
+                    inactive: consumer
+                        .active_time
+                        .map(|active_time| now.saturating_sub(active_time)),
+                })
+                .collect())
+        })
+        .await
+    }
+}
@@ -0,0 +1,432 @@
+use std::sync::atomic::Ordering;
+
+use super::{
+    format_float, is_expired, now_unix_millis, parse_float, parse_integer, Database, Value,
+};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Largest string `APPEND` and `SETRANGE` may build.
+pub const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// Largest table, in cells, `LCS` may allocate.
+const MAX_LCS_CELLS: usize = 128 * 1024 * 1024;
+
+pub(super) fn as_string(value: Option<&Value>) -> Result<Option<&RedisString>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::String(string)) => Ok(Some(string)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+pub(super) fn as_string_mut(value: &mut Option<Value>) -> Result<&mut RedisString, MiniRedisError> {
+    match value.get_or_insert_with(|| Value::String(RedisString::new(b""))) {
+        Value::String(string) => Ok(string),
+        _ => Err(MiniRedisError::WrongType),
+    }
+}
+
+/// Convert an inclusive range of `GETRANGE` or bit commands to positions in a string of
+/// `len` bytes or bits, negative positions counting from the end.
+///
+/// Unlike lists, a stop before the beginning still selects the first position. Returns
+/// `None` if range is empty.
+pub(super) fn string_bounds(len: usize, start: i64, end: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    if (start < 0 && end < 0 && start > end) || len == 0 {
+        return None;
+    }
+    let start = if start < 0 { start + len } else { start }.max(0);
+    let end = if end < 0 { end + len } else { end }.clamp(0, len - 1);
+    (start <= end).then_some((start as usize, end as usize))
+}
+
+/// Condition on key existence for `SET` to be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    /// Only set missing key (`NX`).
+    Missing,
+    /// Only set existing key (`XX`).
+    Existing,
+}
+
+/// Expiry of a key set by `SET`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpiry {
+    /// Keep current expiry (`KEEPTTL`).
+    Keep,
+    /// Milliseconds from now (`EX` and `PX`).
+    In(u64),
+    /// Unix time in milliseconds (`EXAT` and `PXAT`).
+    At(u64),
+}
+
+/// Contiguous range common to both strings of a longest common subsequence, as inclusive
+/// positions in each string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LcsMatch {
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+}
+
+impl LcsMatch {
+    /// Number of bytes in the range.
+    pub fn match_len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+}
+
+/// Options of `LCS`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LcsOptions {
+    /// Only return the length of the subsequence.
+    pub len: bool,
+    /// Return the ranges the subsequence is made of.
+    pub idx: bool,
+    /// Shortest range returned with `idx`.
+    pub min_match_len: usize,
+    /// Return the length of each range with `idx`.
+    pub with_match_len: bool,
+}
+
+/// Options of `SET`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetOptions {
+    pub condition: Option<SetCondition>,
+    /// Expiry of the key, any previous one being removed if `None`.
+    pub expiry: Option<SetExpiry>,
+    /// Return previous string (`GET`).
+    pub get: bool,
+}
+
+impl Database {
+    /// Store a string according to `SET` options, returning whether it was stored and the
+    /// previous string if `get` option is set.
+    pub async fn set_with_options(
+        &self,
+        key: &RedisString,
+        value: RedisString,
+        options: SetOptions,
+    ) -> Result<(bool, Option<RedisString>), MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        let previous = match content.get(key) {
+            Some(Value::String(previous)) if options.get => Some(previous.clone()),
+            Some(_) if options.get => return Err(MiniRedisError::WrongType),
+            _ => None,
+        };
+        let exists = content.contains_key(key);
+        let allowed = match options.condition {
+            None => true,
+            Some(SetCondition::Missing) => !exists,
+            Some(SetCondition::Existing) => exists,
+        };
+        if !allowed {
+            return Ok((false, previous));
+        }
+
+        content.insert(key.clone(), Value::String(value));
+        match options.expiry {
+            Some(SetExpiry::Keep) => {}
+            Some(SetExpiry::In(ms_delta)) => {
+                let expire_at = now_unix_millis().saturating_add(ms_delta);
+                expiry_millis.insert(key.clone(), expire_at);
+            }
+            Some(SetExpiry::At(expire_at)) => {
+                expiry_millis.insert(key.clone(), expire_at);
+            }
+            None => {
+                expiry_millis.remove(key);
+            }
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok((true, previous))
+    }
+
+    /// Append to string, returning its new length.
+    pub async fn string_append(
+        &self,
+        key: &RedisString,
+        value: &RedisString,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |current| {
+            let string = as_string_mut(current)?.as_mut_vec();
+            if string.len() + value.as_slice().len() > MAX_STRING_LEN {
+                return Err(MiniRedisError::StringTooLong);
+            }
+            string.extend_from_slice(value.as_slice());
+            Ok((string.len(), true))
+        })
+        .await
+    }
+
+    /// Length of string, zero if it does not exist.
+    pub async fn string_len(&self, key: &RedisString) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_string(value)?.map_or(0, |string| string.as_slice().len()))
+        })
+        .await
+    }
+
+    /// Substring between inclusive positions, negative ones counting from the end.
+    pub async fn string_range(
+        &self,
+        key: &RedisString,
+        start: i64,
+        end: i64,
+    ) -> Result<RedisString, MiniRedisError> {
+        self.read(key, |value| {
+            let string = as_string(value)?.map_or(&[][..], RedisString::as_slice);
+            Ok(match string_bounds(string.len(), start, end) {
+                Some((start, end)) => RedisString::new(&string[start..=end]),
+                None => RedisString::new(b""),
+            })
+        })
+        .await
+    }
+
+    /// Overwrite part of string at offset, padding it with zero bytes if needed. Returns the
+    /// new length of string.
+    pub async fn string_set_range(
+        &self,
+        key: &RedisString,
+        offset: usize,
+        value: &RedisString,
+    ) -> Result<usize, MiniRedisError> {
+        let value = value.as_slice();
+        // Nothing to write, the key is not created
+        if value.is_empty() {
+            return self.string_len(key).await;
+        }
+        if offset.saturating_add(value.len()) > MAX_STRING_LEN {
+            return Err(MiniRedisError::StringTooLong);
+        }
+
+        self.update(key, |current| {
+            let string = as_string_mut(current)?.as_mut_vec();
+            let end = offset + value.len();
+            if string.len() < end {
+                string.resize(end, 0);
+            }
+            string[offset..end].copy_from_slice(value);
+            Ok((string.len(), true))
+        })
+        .await
+    }
+
+    /// Remove string, returning it.
+    pub async fn string_get_del(
+        &self,
+        key: &RedisString,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        if as_string(content.get(key))?.is_none() {
+            return Ok(None);
+        }
+        expiry_millis.remove(key);
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        match content.remove(key) {
+            Some(Value::String(string)) => Ok(Some(string)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Get string and change its expiry, which is removed if `None`. An expiry already past
+    /// removes the key.
+    pub async fn string_get_ex(
+        &self,
+        key: &RedisString,
+        expiry: Option<SetExpiry>,
+    ) -> Result<Option<RedisString>, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+        self.purge_expired(&mut content, &mut expiry_millis, key);
+
+        let Some(string) = as_string(content.get(key))?.cloned() else {
+            return Ok(None);
+        };
+        let expire_at = match expiry {
+            Some(SetExpiry::Keep) => return Ok(Some(string)),
+            Some(SetExpiry::In(ms_delta)) => Some(now_unix_millis().saturating_add(ms_delta)),
+            Some(SetExpiry::At(expire_at)) => Some(expire_at),
+            None => None,
+        };
+        match expire_at {
+            Some(expire_at) if expire_at <= now_unix_millis() => {
+                content.remove(key);
+                expiry_millis.remove(key);
+            }
+            Some(expire_at) => {
+                expiry_millis.insert(key.clone(), expire_at);
+            }
+            None => {
+                expiry_millis.remove(key);
+            }
+        }
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(string))
+    }
+
+    /// Get several strings, `None` for missing keys and other types.
+    pub async fn get_many(&self, keys: &[RedisString]) -> Vec<Option<RedisString>> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        keys.iter()
+            .map(|key| match content.get(key) {
+                Some(Value::String(string)) if !is_expired(&expiry_millis, key) => {
+                    Some(string.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Store several strings at once, removing their expiry. With `nx`, nothing is stored if
+    /// any key exists. Returns whether strings were stored.
+    pub async fn set_many(&self, pairs: Vec<(RedisString, RedisString)>, nx: bool) -> bool {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+
+        if nx {
+            for (key, _) in &pairs {
+                self.purge_expired(&mut content, &mut expiry_millis, key);
+                if content.contains_key(key) {
+                    return false;
+                }
+            }
+        }
+        let count = pairs.len() as u64;
+        for (key, value) in pairs {
+            expiry_millis.remove(&key);
+            content.insert(key, Value::String(value));
+        }
+        self.changes.fetch_add(count, Ordering::SeqCst);
+        true
+    }
+
+    /// Add increment to integer stored at key and return the new value.
+    pub async fn string_incr_by(
+        &self,
+        key: &RedisString,
+        increment: i64,
+    ) -> Result<i64, MiniRedisError> {
+        self.update(key, |value| {
+            let current = match as_string(value.as_ref())? {
+                Some(current) => {
+                    parse_integer(current.as_slice()).ok_or(MiniRedisError::NotInteger)?
+                }
+                None => 0,
+            };
+            let output = current
+                .checked_add(increment)
+                .ok_or(MiniRedisError::NotInteger)?;
+            *value = Some(Value::String(RedisString::from_integer(output)));
+            Ok((output, true))
+        })
+        .await
+    }
+
+    /// Add increment to float stored at key and return the new value.
+    pub async fn string_incr_by_float(
+        &self,
+        key: &RedisString,
+        increment: f64,
+    ) -> Result<RedisString, MiniRedisError> {
+        self.update(key, |value| {
+            let current = match as_string(value.as_ref())? {
+                Some(current) => parse_float(current.as_slice()).ok_or(MiniRedisError::NotFloat)?,
+                None => 0.0,
+            };
+            let output = current + increment;
+            if !output.is_finite() {
+                return Err(MiniRedisError::IncrementNanOrInfinity);
+            }
+            let output = format_float(output);
+            *value = Some(Value::String(output.clone()));
+            Ok((output, true))
+        })
+        .await
+    }
+
+    /// Longest common subsequence of two strings, with the ranges it is made of from the last
+    /// one. Missing keys are empty strings.
+    pub async fn string_lcs(
+        &self,
+        key1: &RedisString,
+        key2: &RedisString,
+    ) -> Result<(RedisString, Vec<LcsMatch>), MiniRedisError> {
+        let content = self.content.read().await;
+        let expiry_millis = self.expiry_millis.read().await;
+
+        let get = |key: &RedisString| match content.get(key) {
+            Some(_) if is_expired(&expiry_millis, key) => Ok(&[][..]),
+            Some(Value::String(string)) => Ok(string.as_slice()),
+            Some(_) => Err(MiniRedisError::StringsRequired),
+            None => Ok(&[][..]),
+        };
+        let (a, b) = (get(key1)?, get(key2)?);
+        if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_LCS_CELLS {
+            return Err(MiniRedisError::LcsTooLarge);
+        }
+        Ok(longest_common_subsequence(a, b))
+    }
+}
+
+/// Longest common subsequence using dynamic programming, with its ranges from the last one.
+fn longest_common_subsequence(a: &[u8], b: &[u8]) -> (RedisString, Vec<LcsMatch>) {
+    // Length of the subsequence of a[..i] and b[..j] at (i, j)
+    let width = b.len() + 1;
+    let mut table = vec![0_u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+
+    // Walk back from the end, extending the current range while bytes keep matching
+    let mut sequence = Vec::with_capacity(table[a.len() * width + b.len()] as usize);
+    let mut matches = Vec::new();
+    let mut current: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            sequence.push(a[i - 1]);
+            match current.as_mut() {
+                Some(range) => {
+                    range.a.0 = i - 1;
+                    range.b.0 = j - 1;
+                }
+                None => {
+                    current = Some(LcsMatch {
+                        a: (i - 1, i - 1),
+                        b: (j - 1, j - 1),
+                    });
+                }
+            }
+            i -= 1;
+            j -= 1;
+            continue;
+        }
+
+        matches.extend(current.take());
+        if table[(i - 1) * width + j] > table[i * width + j - 1] {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    matches.extend(current);
+
+    sequence.reverse();
+    (sequence.into(), matches)
+}
@@ -0,0 +1,536 @@
+use std::{collections::HashMap, sync::atomic::Ordering};
+
+use super::{
+    is_expired, keyspace::ScanOrder, range_bounds, skiplist::SkipList, Database, ScanOptions, Value,
+};
+use crate::{error::MiniRedisError, rdb::RedisString};
+
+/// Score of a sorted set member, never NaN.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Score(pub f64);
+
+impl Eq for Score {}
+
+impl Score {
+    /// Parse a score, accepting infinities but not NaN.
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        let value: f64 = std::str::from_utf8(input).ok()?.parse().ok()?;
+        (!value.is_nan()).then_some(Self(value))
+    }
+}
+
+/// Bound of a score range, like `1.5` or `(1.5` when exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreBound {
+    Inclusive(Score),
+    Exclusive(Score),
+}
+
+impl ScoreBound {
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        match input.strip_prefix(b"(") {
+            Some(score) => Score::parse(score).map(Self::Exclusive),
+            None => Score::parse(input).map(Self::Inclusive),
+        }
+    }
+
+    /// Check if score is before the range starting at this bound.
+    fn is_before_start(&self, score: f64) -> bool {
+        match self {
+            Self::Inclusive(Score(min)) => score < *min,
+            Self::Exclusive(Score(min)) => score <= *min,
+        }
+    }
+
+    /// Check if score is not after the range ending at this bound.
+    fn is_not_after_end(&self, score: f64) -> bool {
+        match self {
+            Self::Inclusive(Score(max)) => score <= *max,
+            Self::Exclusive(Score(max)) => score < *max,
+        }
+    }
+}
+
+/// Bound of a lexicographical range, like `[a`, `(a`, `-` or `+`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(RedisString),
+    Exclusive(RedisString),
+}
+
+impl LexBound {
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        match input {
+            b"-" => Some(Self::Min),
+            b"+" => Some(Self::Max),
+            [b'[', member @ ..] => Some(Self::Inclusive(RedisString::new(member))),
+            [b'(', member @ ..] => Some(Self::Exclusive(RedisString::new(member))),
+            _ => None,
+        }
+    }
+
+    /// Check if member is before the range starting at this bound.
+    fn is_before_start(&self, member: &RedisString) -> bool {
+        match self {
+            Self::Min => false,
+            Self::Max => true,
+            Self::Inclusive(min) => member < min,
+            Self::Exclusive(min) => member <= min,
+        }
+    }
+
+    /// Check if member is not after the range ending at this bound.
+    fn is_not_after_end(&self, member: &RedisString) -> bool {
+        match self {
+            Self::Min => false,
+            Self::Max => true,
+            Self::Inclusive(max) => member <= max,
+            Self::Exclusive(max) => member < max,
+        }
+    }
+}
+
+/// Selection of sorted set members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZRangeBy {
+    /// Inclusive ranks, negative ones counting from the end.
+    Rank(i64, i64),
+    /// Minimum and maximum scores.
+    Score(ScoreBound, ScoreBound),
+    /// Minimum and maximum members, for members sharing the same score.
+    Lex(LexBound, LexBound),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZRange {
+    pub by: ZRangeBy,
+    /// Return members from highest to lowest, ranks counting from the highest.
+    pub rev: bool,
+    /// Offset and count of returned members, a negative count returning all of them.
+    pub limit: Option<(usize, i64)>,
+}
+
+/// Conditions of `ZADD`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZAddOptions {
+    /// Only add new members.
+    pub nx: bool,
+    /// Only update existing members.
+    pub xx: bool,
+    /// Only update when new score is greater.
+    pub gt: bool,
+    /// Only update when new score is lower.
+    pub lt: bool,
+}
+
+/// Members ordered by score, then lexicographically.
+#[derive(Debug, Clone, Default)]
+pub struct SortedSet {
+    scores: HashMap<RedisString, f64>,
+    list: SkipList,
+    order: ScanOrder,
+}
+
+impl PartialEq for SortedSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.scores == other.scores
+    }
+}
+
+// Scores are never NaN
+impl Eq for SortedSet {}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &RedisString) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Set score of member, returning `true` if it was added.
+    pub fn insert(&mut self, member: RedisString, score: f64) -> bool {
+        let added = match self.scores.insert(member.clone(), score) {
+            Some(previous) if previous == score => return false,
+            Some(previous) => {
+                self.list.remove(previous, &member);
+                false
+            }
+            None => {
+                self.order.insert(member.clone());
+                true
+            }
+        };
+        self.list.insert(score, member);
+        added
+    }
+
+    pub fn remove(&mut self, member: &RedisString) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => {
+                self.order.remove(member);
+                self.list.remove(score, member)
+            }
+            None => false,
+        }
+    }
+
+    /// Zero based rank of member by increasing score.
+    pub fn rank(&self, member: &RedisString) -> Option<usize> {
+        let score = self.score(member)?;
+        Some(self.list.rank(score, member))
+    }
+
+    /// Iterate over members by increasing score.
+    pub fn iter(&self) -> impl Iterator<Item = (&RedisString, f64)> + '_ {
+        self.iter_from(0)
+    }
+
+    fn iter_from(&self, rank: usize) -> impl Iterator<Item = (&RedisString, f64)> + '_ {
+        self.list
+            .iter_from(rank)
+            .map(|(score, member)| (member, score))
+    }
+
+    /// Members from cursor in scan order with their scores.
+    fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&RedisString, f64)>) {
+        let (cursor, members) = self.order.scan(cursor, count);
+        let members = members
+            .into_iter()
+            .map(|member| (member, self.scores[member]))
+            .collect();
+        (cursor, members)
+    }
+
+    /// Ranks of the first member in range and of the first member after it.
+    fn rank_bounds(&self, by: &ZRangeBy) -> (usize, usize) {
+        let (start, end) = match by {
+            ZRangeBy::Rank(..) => (0, self.len()),
+            ZRangeBy::Score(min, max) => (
+                self.list.count_while(|score, _| min.is_before_start(score)),
+                self.list
+                    .count_while(|score, _| max.is_not_after_end(score)),
+            ),
+            ZRangeBy::Lex(min, max) => (
+                self.list
+                    .count_while(|_, member| min.is_before_start(member)),
+                self.list
+                    .count_while(|_, member| max.is_not_after_end(member)),
+            ),
+        };
+        (start, end.max(start))
+    }
+
+    /// Number of members in range.
+    pub fn count(&self, by: &ZRangeBy) -> usize {
+        let (start, end) = self.rank_bounds(by);
+        end - start
+    }
+
+    /// Members in range with their scores, in requested order.
+    pub fn range(&self, range: &ZRange) -> Vec<(RedisString, f64)> {
+        let len = self.len();
+        let (mut start, mut end) = match range.by {
+            ZRangeBy::Rank(start, stop) => match range_bounds(len, start, stop) {
+                Some((start, stop)) if range.rev => (len - 1 - stop, len - start),
+                Some((start, stop)) => (start, stop + 1),
+                None => return Vec::new(),
+            },
+            ref by => self.rank_bounds(by),
+        };
+        if let Some((offset, count)) = range.limit {
+            let count = usize::try_from(count).unwrap_or(usize::MAX);
+            if range.rev {
+                end = end.saturating_sub(offset).max(start);
+                start = start.max(end.saturating_sub(count));
+            } else {
+                start = start.saturating_add(offset).min(end);
+                end = end.min(start.saturating_add(count));
+            }
+        }
+
+        let mut output: Vec<_> = self
+            .iter_from(start)
+            .take(end - start)
+            .map(|(member, score)| (member.clone(), score))
+            .collect();
+        if range.rev {
+            output.reverse();
+        }
+        output
+    }
+}
+
+fn as_zset(value: Option<&Value>) -> Result<Option<&SortedSet>, MiniRedisError> {
+    match value {
+        None => Ok(None),
+        Some(Value::SortedSet(zset)) => Ok(Some(zset)),
+        Some(_) => Err(MiniRedisError::WrongType),
+    }
+}
+
+fn as_zset_mut(value: &mut Option<Value>) -> Result<&mut SortedSet, MiniRedisError> {
+    match value.get_or_insert_with(|| Value::SortedSet(SortedSet::new())) {
+        Value::SortedSet(zset) => Ok(zset),
+        _ => Err(MiniRedisError::WrongType),
+    }
+}
+
+/// Result of adding a member with `ZADD` conditions.
+enum Added {
+    New(f64),
+    Updated(f64),
+    Unchanged(f64),
+    Skipped,
+}
+
+fn add_member(
+    zset: &mut SortedSet,
+    options: ZAddOptions,
+    member: RedisString,
+    score: f64,
+    increment: bool,
+) -> Result<Added, MiniRedisError> {
+    let Some(current) = zset.score(&member) else {
+        if options.xx {
+            return Ok(Added::Skipped);
+        }
+        zset.insert(member, score);
+        return Ok(Added::New(score));
+    };
+    if options.nx {
+        return Ok(Added::Skipped);
+    }
+
+    let score = if increment { current + score } else { score };
+    if score.is_nan() {
+        return Err(MiniRedisError::ScoreNan);
+    }
+    if (options.gt && score <= current) || (options.lt && score >= current) {
+        return Ok(Added::Skipped);
+    }
+    if score == current {
+        return Ok(Added::Unchanged(score));
+    }
+    zset.insert(member, score);
+    Ok(Added::Updated(score))
+}
+
+impl Database {
+    /// Add or update members and return how many were added, or changed when `changed` is set.
+    pub async fn zset_add(
+        &self,
+        key: &RedisString,
+        options: ZAddOptions,
+        changed: bool,
+        members: Vec<(Score, RedisString)>,
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let zset = as_zset_mut(value)?;
+            let (mut added, mut updated) = (0, 0);
+            for (Score(score), member) in members {
+                match add_member(zset, options, member, score, false)? {
+                    Added::New(_) => added += 1,
+                    Added::Updated(_) => updated += 1,
+                    _ => {}
+                }
+            }
+            let count = if changed { added + updated } else { added };
+            Ok((count, added + updated > 0))
+        })
+        .await
+    }
+
+    /// Increment score of member, returning `None` if options prevented it.
+    pub async fn zset_incr(
+        &self,
+        key: &RedisString,
+        options: ZAddOptions,
+        increment: Score,
+        member: RedisString,
+    ) -> Result<Option<f64>, MiniRedisError> {
+        self.update(key, |value| {
+            let zset = as_zset_mut(value)?;
+            match add_member(zset, options, member, increment.0, true)? {
+                Added::New(score) | Added::Updated(score) => Ok((Some(score), true)),
+                Added::Unchanged(score) => Ok((Some(score), false)),
+                Added::Skipped => Ok((None, false)),
+            }
+        })
+        .await
+    }
+
+    /// Remove members and return how many existed.
+    pub async fn zset_remove(
+        &self,
+        key: &RedisString,
+        members: &[RedisString],
+    ) -> Result<usize, MiniRedisError> {
+        self.update(key, |value| {
+            let zset = as_zset_mut(value)?;
+            let removed = members.iter().filter(|member| zset.remove(member)).count();
+            Ok((removed, removed > 0))
+        })
+        .await
+    }
+
+    pub async fn zset_score(
+        &self,
+        key: &RedisString,
+        member: &RedisString,
+    ) -> Result<Option<f64>, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_zset(value)?.and_then(|zset| zset.score(member)))
+        })
+        .await
+    }
+
+    pub async fn zset_len(&self, key: &RedisString) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| Ok(as_zset(value)?.map_or(0, SortedSet::len)))
+            .await
+    }
+
+    /// Visit members from cursor, returning the next cursor and visited members matching
+    /// options with their scores.
+    pub async fn zset_scan(
+        &self,
+        key: &RedisString,
+        cursor: u64,
+        options: &ScanOptions,
+    ) -> Result<(u64, Vec<(RedisString, f64)>), MiniRedisError> {
+        self.read(key, |value| {
+            let Some(zset) = as_zset(value)? else {
+                return Ok((0, Vec::new()));
+            };
+            let (cursor, members) = zset.scan(cursor, options.count);
+            let members = members
+                .into_iter()
+                .filter(|(member, _)| options.matches(member))
+                .map(|(member, score)| (member.clone(), score))
+                .collect();
+            Ok((cursor, members))
+        })
+        .await
+    }
+
+    /// Rank of member with its score, counting from highest score when `rev` is set.
+    pub async fn zset_rank(
+        &self,
+        key: &RedisString,
+        member: &RedisString,
+        rev: bool,
+    ) -> Result<Option<(usize, f64)>, MiniRedisError> {
+        self.read(key, |value| {
+            let Some(zset) = as_zset(value)? else {
+                return Ok(None);
+            };
+            Ok(zset.rank(member).map(|rank| {
+                let rank = if rev { zset.len() - 1 - rank } else { rank };
+                (rank, zset.score(member).unwrap_or_default())
+            }))
+        })
+        .await
+    }
+
+    pub async fn zset_count(
+        &self,
+        key: &RedisString,
+        by: &ZRangeBy,
+    ) -> Result<usize, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_zset(value)?.map_or(0, |zset| zset.count(by)))
+        })
+        .await
+    }
+
+    pub async fn zset_range(
+        &self,
+        key: &RedisString,
+        range: &ZRange,
+    ) -> Result<Vec<(RedisString, f64)>, MiniRedisError> {
+        self.read(key, |value| {
+            Ok(as_zset(value)?
+                .map(|zset| zset.range(range))
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Store members of source in range at destination, replacing any value.
+    ///
+    /// Returns the number of stored members.
+    pub async fn zset_range_store(
+        &self,
+        destination: &RedisString,
+        source: &RedisString,
+        range: &ZRange,
+    ) -> Result<usize, MiniRedisError> {
+        let mut content = self.content.write().await;
+        let mut expiry_millis = self.expiry_millis.write().await;
+
+        let source = content
+            .get(source)
+            .filter(|_| !is_expired(&expiry_millis, source));
+        let members = as_zset(source)?
+            .map(|zset| zset.range(range))
+            .unwrap_or_default();
+        let len = members.len();
+
+        self.purge_expired(&mut content, &mut expiry_millis, destination);
+        expiry_millis.remove(destination);
+        if members.is_empty() {
+            content.remove(destination);
+        } else {
+            let mut zset = SortedSet::new();
+            for (member, score) in members {
+                zset.insert(member, score);
+            }
+            content.insert(destination.clone(), Value::SortedSet(zset));
+        }
+
+        self.changes.fetch_add(1, Ordering::SeqCst);
+        Ok(len)
+    }
+
+    /// Remove and return up to `count` members with lowest scores, or highest ones if `max`.
+    pub async fn zset_pop(
+        &self,
+        key: &RedisString,
+        count: usize,
+        max: bool,
+    ) -> Result<Vec<(RedisString, f64)>, MiniRedisError> {
+        self.update(key, |value| {
+            if value.is_none() {
+                return Ok((Vec::new(), false));
+            }
+            let zset = as_zset_mut(value)?;
+            let range = ZRange {
+                by: ZRangeBy::Rank(0, count.min(i64::MAX as usize) as i64 - 1),
+                rev: max,
+                limit: None,
+            };
+            let members = if count == 0 {
+                Vec::new()
+            } else {
+                zset.range(&range)
+            };
+            for (member, _) in &members {
+                zset.remove(member);
+            }
+            let popped = !members.is_empty();
+            Ok((members, popped))
+        })
+        .await
+    }
+}
@@ -24,6 +24,141 @@ pub enum MiniRedisError {
 
     #[error("Unsupported length encoding")]
     UnsupportedLengthEncoding,
+
+    #[error("Unknown RDB opcode 0x{opcode:02x} at offset {offset}")]
+    UnknownRdbOpcode { opcode: u8, offset: u64 },
+
+    #[error("Invalid RDB value: {0}")]
+    InvalidRdbValue(String),
+
+    #[error("Invalid RDB checksum: expected 0x{expected:016x}, computed 0x{computed:016x}")]
+    InvalidRdbChecksum { expected: u64, computed: u64 },
+
+    #[error("Invalid replication handshake: {0}")]
+    InvalidHandshake(String),
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+
+    #[error("syntax error")]
+    SyntaxError,
+
+    #[error("value is not an integer or out of range")]
+    NotInteger,
+
+    #[error("value is out of range")]
+    ValueOutOfRange,
+
+    #[error("invalid expire time in '{0}' command")]
+    InvalidExpireTime(String),
+
+    #[error("offset is out of range")]
+    OffsetOutOfRange,
+
+    #[error("string exceeds maximum allowed size (proto-max-bulk-len)")]
+    StringTooLong,
+
+    #[error("The specified keys must contain string values")]
+    StringsRequired,
+
+    #[error("If you want both the length and indexes, please just use IDX.")]
+    LcsLenAndIdx,
+
+    #[error("Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len")]
+    LcsTooLarge,
+
+    #[error("invalid cursor")]
+    InvalidCursor,
+
+    #[error("bit offset is not an integer or out of range")]
+    InvalidBitOffset,
+
+    #[error("bit is not an integer or out of range")]
+    InvalidBit,
+
+    #[error("The bit argument must be 1 or 0.")]
+    InvalidBitPosBit,
+
+    #[error("BITOP NOT must be called with a single source key.")]
+    BitOpNotSingleKey,
+
+    #[error("Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.")]
+    InvalidBitFieldType,
+
+    #[error("Invalid OVERFLOW type specified")]
+    InvalidOverflowType,
+
+    #[error("BITFIELD_RO only supports the GET subcommand")]
+    BitFieldReadOnly,
+
+    #[error("Unsupported option {0}")]
+    UnsupportedOption(String),
+
+    #[error("{0} options at the same time are not compatible")]
+    IncompatibleOptions(&'static str),
+
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+
+    #[error("no such key")]
+    NoSuchKey,
+
+    #[error("index out of range")]
+    IndexOutOfRange,
+
+    #[error("DB index is out of range")]
+    DbIndexOutOfRange,
+
+    #[error("hash value is not an integer")]
+    HashValueNotInteger,
+
+    #[error("hash value is not a float")]
+    HashValueNotFloat,
+
+    #[error("increment or decrement would overflow")]
+    IncrementOverflow,
+
+    #[error("value is not a valid float")]
+    NotFloat,
+
+    #[error("increment would produce NaN or Infinity")]
+    IncrementNanOrInfinity,
+
+    #[error("resulting score is not a number (NaN)")]
+    ScoreNan,
+
+    #[error("Invalid stream ID specified as stream command argument")]
+    InvalidStreamId,
+
+    #[error("syntax error, LIMIT cannot be used without the special ~ option")]
+    StreamLimitWithoutApprox,
+
+    #[error("The ID specified in XADD must be greater than 0-0")]
+    StreamIdZero,
+
+    #[error("The ID specified in XADD is equal or smaller than the target stream top item")]
+    StreamIdTooSmall,
+
+    #[error("The stream has exhausted the last possible ID, unable to add more items")]
+    StreamExhausted,
+
+    #[error("The ID specified in XSETID is smaller than the target stream top item")]
+    StreamSetIdTooSmall,
+
+    #[error("The entries_added specified in XSETID is smaller than the target stream length")]
+    StreamEntriesAddedTooSmall,
+
+    #[error("The ID specified in XSETID is smaller than the provided max_deleted_entry_id")]
+    StreamMaxDeletedIdTooBig,
+
+    #[error("The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")]
+    StreamKeyRequired,
+
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+
+    #[error("NOGROUP No such key '{key}' or consumer group '{group}'")]
+    NoGroup { key: String, group: String },
 }
 
 impl From<io::Error> for MiniRedisError {
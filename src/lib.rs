@@ -1,11 +1,17 @@
+pub mod aof;
+pub mod crc64;
 pub mod database;
 pub mod error;
+pub mod lzf;
+pub mod persistence;
 pub mod rdb;
+pub mod replication;
 pub mod request;
 pub mod resp2;
 pub mod response;
+pub mod server;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ServerMode {
     Master,
     Slave,
@@ -0,0 +1,42 @@
+//! LZF decompression, as used by Redis for compressed strings in RDB files.
+
+use crate::error::MiniRedisError;
+
+/// Decompress data, checking output has expected length.
+pub fn decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>, MiniRedisError> {
+    let invalid = || MiniRedisError::InvalidRdbValue("Invalid LZF data".to_string());
+    // Expected length comes from the file, bound preallocation by the input size
+    let mut output = Vec::with_capacity(expected_len.min(input.len() * 4));
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let ctrl = input[pos] as usize;
+        pos += 1;
+
+        if ctrl < 1 << 5 {
+            // Literal run
+            let literal = input.get(pos..pos + ctrl + 1).ok_or_else(invalid)?;
+            output.extend_from_slice(literal);
+            pos += ctrl + 1;
+        } else {
+            // Back reference
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(pos).ok_or_else(invalid)? as usize;
+                pos += 1;
+            }
+            let offset = ((ctrl & 0x1f) << 8) + *input.get(pos).ok_or_else(invalid)? as usize + 1;
+            pos += 1;
+
+            let start = output.len().checked_sub(offset).ok_or_else(invalid)?;
+            for index in start..start + len + 2 {
+                output.push(output[index]);
+            }
+        }
+    }
+
+    if output.len() != expected_len {
+        return Err(invalid());
+    }
+    Ok(output)
+}
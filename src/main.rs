@@ -1,62 +1,188 @@
-use std::{
-    env,
-    os::unix::ffi::OsStrExt,
-    path::{Path, PathBuf},
-    sync::Arc,
-};
+use std::{env, os::unix::ffi::OsStrExt, path::PathBuf, sync::Arc, time::Duration};
 
 use redis_starter_rust::{
-    database::Database, error::MiniRedisError, rdb::Rdb, request::Request, response::Response,
+    aof, persistence,
+    rdb::RedisString,
+    replication::{self, ReplicaLink, ReplicaSync},
+    request::Request,
+    resp2::{self, Message},
+    response::Response,
+    server::{self, Server, Session},
     ServerMode,
 };
 use tokio::{
-    fs,
-    io::{BufReader, BufWriter},
-    net::{TcpListener, TcpStream},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    signal::unix::{signal, SignalKind},
+    time,
 };
 
+/// Period of the active expiration cycle, which may spend a quarter of it removing keys.
+const ACTIVE_EXPIRE_PERIOD: Duration = Duration::from_millis(100);
+
 #[tokio::main]
 async fn main() {
     let dir = parse_cli_dir().unwrap_or_else(env::temp_dir);
     let dbfilename = parse_cli_dbfilename().unwrap_or_else(|| PathBuf::from("dump.rdb"));
     let port = parse_cli_port().unwrap_or(6379);
+    let databases = parse_cli_databases().unwrap_or(server::DEFAULT_DATABASES);
+    let replicaof = parse_cli_replicaof();
+    let repl_backlog_size = parse_cli_repl_backlog_size();
+    let save = parse_cli_save().unwrap_or_else(|| persistence::DEFAULT_SAVE.to_string());
+    let appendonly = parse_cli_appendonly().unwrap_or(false);
+    let appendfsync = parse_cli_appendfsync().unwrap_or_else(|| "everysec".to_string());
+    let appendfilename =
+        parse_cli_appendfilename().unwrap_or_else(|| PathBuf::from("appendonly.aof"));
 
     // Create DBs
-    let config = Arc::new(Database::new());
-    config.set(b"dir", dir.as_os_str().as_bytes()).await;
-    config
+    let role = match replicaof {
+        Some(_) => ServerMode::Slave,
+        None => ServerMode::Master,
+    };
+    let server = Arc::new(Server::new(role, databases));
+    server.config.set(b"dir", dir.as_os_str().as_bytes()).await;
+    server
+        .config
+        .set(b"databases", databases.to_string().as_bytes())
+        .await;
+    server
+        .config
         .set(b"dbfilename", dbfilename.as_os_str().as_bytes())
         .await;
 
-    let database = Arc::new(Database::new());
+    let repl_backlog_size = repl_backlog_size.unwrap_or(replication::DEFAULT_BACKLOG_SIZE);
+    server
+        .set_config(
+            b"repl-backlog-size",
+            repl_backlog_size.to_string().as_bytes(),
+        )
+        .await
+        .expect("Invalid repl-backlog-size");
+    server
+        .set_config(b"save", save.as_bytes())
+        .await
+        .expect("Invalid save parameters");
+    server
+        .set_config(b"appendfsync", appendfsync.as_bytes())
+        .await
+        .expect("Invalid appendfsync");
+    server
+        .config
+        .set(b"appendfilename", appendfilename.as_os_str().as_bytes())
+        .await;
+    for (key, value) in [
+        (
+            "auto-aof-rewrite-percentage",
+            parse_cli_auto_aof_rewrite_percentage().unwrap_or(aof::DEFAULT_AUTO_REWRITE_PERCENTAGE),
+        ),
+        (
+            "auto-aof-rewrite-min-size",
+            parse_cli_auto_aof_rewrite_min_size().unwrap_or(aof::DEFAULT_AUTO_REWRITE_MIN_SIZE),
+        ),
+    ] {
+        server
+            .set_config(key.as_bytes(), value.to_string().as_bytes())
+            .await
+            .expect("Invalid AOF rewrite config");
+    }
 
     // Apply CLI args
     env::set_current_dir(&dir).expect("Fail to set current dir");
-    if dbfilename.exists() {
-        let rdb = read_rdb(&dbfilename).await.expect("Fail to read .rdb file");
-        for (key, value) in rdb.values {
-            database.set(key.as_slice(), value.as_slice()).await;
+    if appendonly && appendfilename.exists() {
+        // AOF is more up to date than RDB
+        server
+            .load_aof(&appendfilename)
+            .await
+            .expect("Fail to read .aof file");
+        server
+            .aof
+            .open(&appendfilename)
+            .await
+            .expect("Fail to open .aof file");
+    } else if dbfilename.exists() {
+        let rdb = persistence::read_rdb_file(&dbfilename)
+            .await
+            .expect("Fail to read .rdb file");
+        server.load_rdb(rdb).await.expect("Fail to load .rdb file");
+    }
+    server
+        .set_config(b"appendonly", if appendonly { b"yes" } else { b"no" })
+        .await
+        .expect("Fail to setup AOF");
+
+    // Connect to master
+    if let Some((host, master_port)) = replicaof {
+        let server = server.clone();
+        tokio::task::spawn(async move {
+            if let Err(e) = replicate_from_master(&host, master_port, port, server).await {
+                eprintln!("replication error: {}", e);
+            }
+        });
+    }
+
+    // Remove expired keys that are never read
+    tokio::task::spawn({
+        let server = server.clone();
+        async move {
+            let mut interval = time::interval(ACTIVE_EXPIRE_PERIOD);
+            loop {
+                interval.tick().await;
+                server.active_expire_cycle(ACTIVE_EXPIRE_PERIOD / 4).await;
+            }
         }
-        for (key, value) in rdb.expiry {
-            database.expire_at_millis(key.as_slice(), value).await;
+    });
+
+    // Check save points and flush AOF
+    tokio::task::spawn({
+        let server = server.clone();
+        async move {
+            let mut interval = time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                server.bgsave_if_needed().await;
+                server.bgrewriteaof_if_needed().await;
+                if let Err(e) = server.aof.fsync_if_needed().await {
+                    eprintln!("AOF fsync error: {}", e);
+                }
+            }
         }
-    }
+    });
 
     // Startup server
     let listener = TcpListener::bind(("127.0.0.1", port))
         .await
         .expect("Fail to start TCP server");
+    let mut sigterm = signal(SignalKind::terminate()).expect("Fail to listen SIGTERM");
 
     loop {
-        match listener.accept().await {
-            Ok((stream, _addr)) => {
-                tokio::task::spawn(handle_client(stream, database.clone(), config.clone()));
-            }
-            Err(e) => {
-                eprintln!("error: {}", e);
-            }
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _addr)) => {
+                    tokio::task::spawn(handle_client(stream, server.clone()));
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                }
+            },
+            // Shutdown command already saved database
+            _ = server.shutdown_requested() => return,
+            _ = sigterm.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
         }
     }
+
+    if let Err(e) = server.shutdown(None).await {
+        eprintln!("shutdown error: {}", e);
+    }
+}
+
+fn parse_cli_databases() -> Option<usize> {
+    let index = env::args().position(|x| x == "--databases")?;
+    let value = env::args().nth(index + 1)?;
+    value.parse().ok().filter(|count| *count > 0)
 }
 
 fn parse_cli_dir() -> Option<PathBuf> {
@@ -77,53 +203,84 @@ fn parse_cli_port() -> Option<u16> {
     value.parse().ok()
 }
 
-async fn handle_client(
-    stream: TcpStream,
-    db: Arc<Database>,
-    config: Arc<Database>,
-) -> anyhow::Result<()> {
+fn parse_cli_repl_backlog_size() -> Option<usize> {
+    let index = env::args().position(|x| x == "--repl-backlog-size")?;
+    let value = env::args().nth(index + 1)?;
+    value.parse().ok()
+}
+
+fn parse_cli_save() -> Option<String> {
+    let index = env::args().position(|x| x == "--save")?;
+    env::args().nth(index + 1)
+}
+
+fn parse_cli_appendonly() -> Option<bool> {
+    let index = env::args().position(|x| x == "--appendonly")?;
+    let value = env::args().nth(index + 1)?;
+    Some(value.eq_ignore_ascii_case("yes"))
+}
+
+fn parse_cli_appendfsync() -> Option<String> {
+    let index = env::args().position(|x| x == "--appendfsync")?;
+    env::args().nth(index + 1)
+}
+
+fn parse_cli_appendfilename() -> Option<PathBuf> {
+    let index = env::args().position(|x| x == "--appendfilename")?;
+    let value = env::args().nth(index + 1)?;
+    value.parse().ok()
+}
+
+fn parse_cli_auto_aof_rewrite_percentage() -> Option<u64> {
+    let index = env::args().position(|x| x == "--auto-aof-rewrite-percentage")?;
+    let value = env::args().nth(index + 1)?;
+    value.parse().ok()
+}
+
+fn parse_cli_auto_aof_rewrite_min_size() -> Option<u64> {
+    let index = env::args().position(|x| x == "--auto-aof-rewrite-min-size")?;
+    let value = env::args().nth(index + 1)?;
+    value.parse().ok()
+}
+
+/// Accept both `--replicaof <host> <port>` and `--replicaof "<host> <port>"`.
+fn parse_cli_replicaof() -> Option<(String, u16)> {
+    let index = env::args().position(|x| x == "--replicaof")?;
+    let value = env::args().nth(index + 1)?;
+    match value.split_once(' ') {
+        Some((host, port)) => Some((host.to_string(), port.trim().parse().ok()?)),
+        None => Some((value, env::args().nth(index + 2)?.parse().ok()?)),
+    }
+}
+
+async fn handle_client(stream: TcpStream, server: Arc<Server>) -> anyhow::Result<()> {
     let (reader, writer) = stream.into_split();
     let mut buf_reader = BufReader::new(reader);
     let mut buf_writer = BufWriter::new(writer);
+    let mut session = Session::default();
 
     loop {
-        let request = Request::read(&mut buf_reader).await?;
+        let msg = Message::read(&mut buf_reader).await?;
+        let request = Request::parse(&msg);
 
         let response = match request {
-            Request::Ping => Response::Pong,
-            Request::InfoReplication => Response::InfoReplication {
-                role: ServerMode::Master,
-                master_replid: "".to_string(),
-                master_repl_offset: 0,
-                repl_backlog_active: 0,
-                repl_backlog_size: 0,
-                repl_backlog_first_byte_offset: 0,
-                repl_backlog_histlen: 0,
-            },
-            Request::Echo(data) => Response::Echo(data),
-            Request::Get(key) => match db.get(key).await {
-                Some(data) => Response::Content(data),
-                None => Response::NoContent,
-            },
-            Request::Set(key, value) => {
-                db.set(key, value).await;
-                Response::Ok
+            Request::Psync(replid, offset) => {
+                return serve_replica(buf_reader, buf_writer, server, replid, offset).await;
             }
-            Request::SetExpire(key, value, ms_delta) => {
-                db.set(key.clone(), value).await;
-                db.expire_in_millis(key, ms_delta).await;
-                Response::Ok
+            request if server.is_readonly(&request) => {
+                Response::Error("READONLY You can't write against a read only replica.".to_string())
             }
-            Request::Keys => {
-                let keys = db.keys().await;
-                Response::KeyMatches(keys)
+            request if request.is_blocking() => {
+                let closed = client_closed(&mut buf_reader);
+                match server.execute_blocking(request, &session, closed).await {
+                    Some(response) => response,
+                    None => return Ok(()),
+                }
             }
-            Request::ConfigGet(key) => match config.get(key.clone()).await {
-                Some(value) => Response::ConfigGet(key, value),
-                None => Response::NoContent,
-            },
-            Request::UnhandledCommand => {
-                Response::Error("BAD_CMD Invalid command received".to_string())
+            request => {
+                server
+                    .execute_and_propagate(request, &msg, &mut session)
+                    .await
             }
         };
 
@@ -131,8 +288,116 @@ async fn handle_client(
     }
 }
 
-async fn read_rdb<P: AsRef<Path>>(path: P) -> Result<Rdb, MiniRedisError> {
-    let file = fs::File::open(path).await?;
-    let mut reader = BufReader::new(file);
-    Rdb::read(&mut reader).await
+/// Complete when client closes its connection while waiting for a blocking request.
+///
+/// Pipelined requests are left in buffer, in which case this never completes.
+async fn client_closed(reader: &mut BufReader<OwnedReadHalf>) {
+    match reader.fill_buf().await {
+        Ok(data) if !data.is_empty() => std::future::pending().await,
+        _ => {}
+    }
+}
+
+/// Synchronize the replica and then stream every write command to it.
+async fn serve_replica(
+    mut reader: BufReader<OwnedReadHalf>,
+    mut writer: BufWriter<OwnedWriteHalf>,
+    server: Arc<Server>,
+    psync_replid: RedisString,
+    psync_offset: i64,
+) -> anyhow::Result<()> {
+    let (
+        ReplicaLink {
+            sync,
+            mut receiver,
+            acknowledger,
+        },
+        snapshot,
+    ) = server
+        .register_replica(psync_replid.as_slice(), psync_offset)
+        .await;
+
+    let result: anyhow::Result<()> = async {
+        match sync {
+            ReplicaSync::Full { replid, offset } => {
+                let mut rdb = Vec::new();
+                if let Some(snapshot) = snapshot {
+                    snapshot.write(&mut rdb).await?;
+                }
+
+                Response::FullResync(replid, offset)
+                    .write(&mut writer)
+                    .await?;
+                resp2::write_unterminated_binary(&mut writer, &rdb).await?;
+            }
+            ReplicaSync::Partial { replid, missing } => {
+                Response::Continue(replid).write(&mut writer).await?;
+                writer.write_all(&missing).await?;
+                writer.flush().await?;
+            }
+        }
+
+        tokio::task::spawn(async move {
+            while let Some(data) = receiver.recv().await {
+                if writer.write_all(&data).await.is_err() || writer.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Keep reading acks until replica disconnect
+        loop {
+            let msg = Message::read(&mut reader).await?;
+            if let Request::ReplConfAck(offset) = Request::parse(&msg) {
+                acknowledger.ack(offset);
+            }
+        }
+    }
+    .await;
+
+    // Dropping the handle also stops the task writing to replica
+    server.replication.unregister_replica(&acknowledger).await;
+    result
+}
+
+/// Sync with master and then apply every command it propagates.
+async fn replicate_from_master(
+    host: &str,
+    port: u16,
+    listening_port: u16,
+    server: Arc<Server>,
+) -> anyhow::Result<()> {
+    let stream = TcpStream::connect((host, port)).await?;
+    let (reader, writer) = stream.into_split();
+    let mut buf_reader = BufReader::new(reader);
+    let mut buf_writer = BufWriter::new(writer);
+
+    let resync = replication::handshake(&mut buf_reader, &mut buf_writer, listening_port).await?;
+    server
+        .replication
+        .set_master(resync.replid, resync.offset)
+        .await;
+    server.load_rdb(resync.rdb).await?;
+    // Loaded snapshot is only logged by a rewrite
+    if server.aof.is_enabled().await {
+        server.bgrewriteaof().await;
+    }
+
+    let mut session = Session::default();
+    loop {
+        let msg = Message::read(&mut buf_reader).await?;
+        let request = Request::parse(&msg);
+
+        // Master does not expect any reply from propagated commands except acks
+        if let Request::ReplConfGetAck = request {
+            let offset = server.replication.offset().to_string();
+            Message::command(&[b"REPLCONF", b"ACK", offset.as_bytes()])
+                .write(&mut buf_writer)
+                .await?;
+        } else {
+            server.execute_replicated(request, &msg, &mut session).await;
+        }
+
+        server.replication.add_offset(msg.to_bytes().await.len());
+    }
 }
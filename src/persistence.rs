@@ -0,0 +1,177 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    fs,
+    io::{BufReader, BufWriter},
+    sync::RwLock,
+};
+
+use crate::{error::MiniRedisError, rdb::Rdb};
+
+/// Save points used when none are configured, an empty list disabling snapshots.
+pub const DEFAULT_SAVE: &str = "3600 1 300 100 60 10000";
+
+/// Track RDB snapshots written to disk.
+#[derive(Debug)]
+pub struct Persistence {
+    last_save: Arc<AtomicU64>,
+    saved_changes: Arc<AtomicU64>,
+    bgsave_in_progress: Arc<AtomicBool>,
+    save_points: RwLock<Vec<SavePoint>>,
+}
+
+/// Snapshot database if `changes` writes happened within `seconds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavePoint {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+impl SavePoint {
+    /// Parse `save` config, which is a list of `<seconds> <changes>` pairs.
+    pub fn parse_list(input: &[u8]) -> Result<Vec<Self>, MiniRedisError> {
+        let input = std::str::from_utf8(input)?;
+        let numbers = input
+            .split_whitespace()
+            .map(|x| x.parse())
+            .collect::<Result<Vec<u64>, _>>()?;
+
+        if numbers.len() % 2 != 0 {
+            return Err(MiniRedisError::InvalidConfig(format!(
+                "Invalid save parameters: {input}"
+            )));
+        }
+
+        Ok(numbers
+            .chunks(2)
+            .map(|pair| Self {
+                seconds: pair[0],
+                changes: pair[1],
+            })
+            .collect())
+    }
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Self {
+            last_save: Arc::new(AtomicU64::new(now_unix_secs())),
+            saved_changes: Arc::new(AtomicU64::new(0)),
+            bgsave_in_progress: Arc::new(AtomicBool::new(false)),
+            save_points: RwLock::new(Vec::new()),
+        }
+    }
+}
+
+impl Persistence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unix timestamp (in seconds) of last successful save.
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::SeqCst)
+    }
+
+    pub fn is_bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// Number of database changes not yet written to disk.
+    pub fn changes_since_last_save(&self, changes: u64) -> u64 {
+        changes.saturating_sub(self.saved_changes.load(Ordering::SeqCst))
+    }
+
+    /// Consider database content up to given changes count as already on disk.
+    pub fn mark_clean(&self, changes: u64) {
+        self.saved_changes.store(changes, Ordering::SeqCst);
+    }
+
+    pub async fn save_points(&self) -> Vec<SavePoint> {
+        self.save_points.read().await.clone()
+    }
+
+    pub async fn set_save_points(&self, save_points: Vec<SavePoint>) {
+        *self.save_points.write().await = save_points;
+    }
+
+    /// Check if any save point is reached.
+    pub async fn should_save(&self, changes: u64) -> bool {
+        let dirty = self.changes_since_last_save(changes);
+        let elapsed = now_unix_secs().saturating_sub(self.last_save());
+
+        self.save_points
+            .read()
+            .await
+            .iter()
+            .any(|point| dirty > 0 && dirty >= point.changes && elapsed >= point.seconds)
+    }
+
+    /// Write snapshot taken after `changes` writes to disk and wait for completion.
+    pub async fn save(&self, rdb: &Rdb, path: &Path, changes: u64) -> Result<(), MiniRedisError> {
+        write_rdb_file(rdb, path).await?;
+        self.last_save.store(now_unix_secs(), Ordering::SeqCst);
+        self.saved_changes.store(changes, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Write snapshot taken after `changes` writes to disk in a background task.
+    ///
+    /// Returns `false` if another background save is still running.
+    pub fn bgsave(&self, rdb: Rdb, path: PathBuf, changes: u64) -> bool {
+        if self.bgsave_in_progress.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        let last_save = self.last_save.clone();
+        let saved_changes = self.saved_changes.clone();
+        let bgsave_in_progress = self.bgsave_in_progress.clone();
+        tokio::task::spawn(async move {
+            match write_rdb_file(&rdb, &path).await {
+                Ok(()) => {
+                    last_save.store(now_unix_secs(), Ordering::SeqCst);
+                    saved_changes.store(changes, Ordering::SeqCst);
+                }
+                Err(e) => eprintln!("Background saving error: {e}"),
+            }
+            bgsave_in_progress.store(false, Ordering::SeqCst);
+        });
+
+        true
+    }
+}
+
+pub async fn read_rdb_file<P: AsRef<Path>>(path: P) -> Result<Rdb, MiniRedisError> {
+    let file = fs::File::open(path).await?;
+    let mut reader = BufReader::new(file);
+    Rdb::read(&mut reader).await
+}
+
+/// Write RDB to a temporary file and then rename it, so readers never see partial content.
+pub async fn write_rdb_file<P: AsRef<Path>>(rdb: &Rdb, path: P) -> Result<(), MiniRedisError> {
+    let path = path.as_ref();
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let file = fs::File::create(&tmp_path).await?;
+    let mut writer = BufWriter::new(file);
+    rdb.write(&mut writer).await?;
+    writer.into_inner().sync_all().await?;
+
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+fn now_unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time cannot go before 1970 with this implementation")
+        .as_secs()
+}
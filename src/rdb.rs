@@ -1,8 +1,21 @@
-use std::{collections::HashMap, fmt, io::Write, string::FromUtf8Error};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    io::{self, Write},
+    string::FromUtf8Error,
+};
 
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::error::MiniRedisError;
+use crate::{
+    crc64::{self, Crc64Reader},
+    database::{
+        now_unix_millis, Consumer, ConsumerGroup, Hash, PendingEntry, Score, Set, SortedSet,
+        Stream, StreamId, Value, STREAM_NODE_MAX_ENTRIES,
+    },
+    error::MiniRedisError,
+    lzf,
+};
 
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct Rdb {
@@ -11,12 +24,59 @@ pub struct Rdb {
     pub aux_redis_bits: Option<String>,
     pub aux_ctime: Option<String>,
     pub aux_used_men: Option<String>,
-    pub values: HashMap<RedisString, RedisString>,
+    /// Non empty logical databases by index.
+    pub databases: BTreeMap<usize, RdbDatabase>,
+}
+
+/// Content of a single logical database.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RdbDatabase {
+    pub values: HashMap<RedisString, Value>,
     pub expiry: HashMap<RedisString, u64>,
 }
 
+impl RdbDatabase {
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// First RDB version having a checksum in its footer.
+const FIRST_CHECKSUM_VERSION: u32 = 5;
+
+// Value types
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_STREAM_LISTPACKS: u8 = 15;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const TYPE_SET_LISTPACK: u8 = 20;
+const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+
+/// Maximum number of entries reserved up front from a RESIZEDB hint.
+const MAX_RESIZE_DB_RESERVE: usize = 1024;
+
+// Quicklist node containers
+const QUICKLIST_NODE_PLAIN: i64 = 1;
+
+// Stream entry flags
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
 impl Rdb {
+    /// Parse RDB content and verify its checksum.
+    ///
+    /// Files written with checksum disabled (stored as 0) are accepted.
     pub async fn read<R: AsyncRead + Unpin>(input: &mut R) -> Result<Self, MiniRedisError> {
+        let input = &mut Crc64Reader::new(input);
+
         // Check magic number and version
         let mut magic = [0_u8; 5];
         input.read_exact(&mut magic).await?;
@@ -28,6 +88,7 @@ impl Rdb {
         input.read_exact(&mut version_bytes).await?;
         let version: u32 = std::str::from_utf8(&version_bytes)?.parse()?;
 
+        let mut db_index = 0;
         let mut next_expire_ms = None;
         let mut output = Self {
             version,
@@ -62,16 +123,19 @@ impl Rdb {
                 }
                 // Select DB
                 0xFE => {
-                    let db_id = RedisString::read(input).await?;
-                    assert_eq!(db_id, RedisString::new(b""), "Multi DB is not supported");
+                    db_index = read_integer(input).await? as usize;
                 }
                 // Resize DB
                 0xFB => {
-                    let db_values_size = read_integer(input).await?;
-                    let db_expiry_size = read_integer(input).await?;
+                    let db_values_size = read_resize_db_size(input).await?;
+                    let db_expiry_size = read_resize_db_size(input).await?;
 
-                    output.values.reserve(db_values_size as usize);
-                    output.expiry.reserve(db_expiry_size as usize);
+                    if db_values_size > 0 {
+                        // Sizes are only hints, don't let a corrupted file allocate for them
+                        let db = output.databases.entry(db_index).or_default();
+                        db.values.reserve(db_values_size.min(MAX_RESIZE_DB_RESERVE));
+                        db.expiry.reserve(db_expiry_size.min(MAX_RESIZE_DB_RESERVE));
+                    }
                 }
                 // Expire time millis
                 0xFC => {
@@ -85,28 +149,835 @@ impl Rdb {
                 }
 
                 // Key / values
-                // type: String
-                0x00 => {
+                value_type @ (TYPE_STRING
+                | TYPE_LIST
+                | TYPE_SET
+                | TYPE_ZSET
+                | TYPE_HASH
+                | TYPE_ZSET_2
+                | TYPE_SET_INTSET
+                | TYPE_HASH_LISTPACK
+                | TYPE_ZSET_LISTPACK
+                | TYPE_STREAM_LISTPACKS
+                | TYPE_LIST_QUICKLIST_2
+                | TYPE_STREAM_LISTPACKS_2
+                | TYPE_SET_LISTPACK
+                | TYPE_STREAM_LISTPACKS_3) => {
                     let key = RedisString::read(input).await?;
-                    let value = RedisString::read(input).await?;
+                    let value = read_value(input, value_type).await?;
 
+                    let db = output.databases.entry(db_index).or_default();
                     if let Some(expire_at) = next_expire_ms.take() {
-                        output.expiry.insert(key.clone(), expire_at);
+                        db.expiry.insert(key.clone(), expire_at);
                     }
 
-                    output.values.insert(key, value);
+                    db.values.insert(key, value);
                 }
                 // End of file
                 0xFF => break,
-                _ => {}
+                opcode => {
+                    return Err(MiniRedisError::UnknownRdbOpcode {
+                        opcode,
+                        offset: input.position() - 1,
+                    });
+                }
+            }
+        }
+
+        // Checksum covers everything up to end of file opcode
+        if version >= FIRST_CHECKSUM_VERSION {
+            let computed = input.crc();
+            let expected = input.read_u64_le().await?;
+            if expected != 0 && expected != computed {
+                return Err(MiniRedisError::InvalidRdbChecksum { expected, computed });
             }
         }
 
         Ok(output)
     }
+
+    /// Serialize content using the same format as `Rdb::read`.
+    pub async fn write<W: AsyncWrite + Unpin>(&self, output: &mut W) -> io::Result<()> {
+        // Content is serialized in memory first to compute its checksum
+        let mut payload = Vec::new();
+        self.write_content(&mut payload).await?;
+        let checksum = crc64::crc64(&payload);
+
+        output.write_all(&payload).await?;
+        output.write_u64_le(checksum).await?;
+        output.flush().await
+    }
+
+    async fn write_content<W: AsyncWrite + Unpin>(&self, output: &mut W) -> io::Result<()> {
+        output.write_all(b"REDIS").await?;
+        output
+            .write_all(format!("{:04}", self.version).as_bytes())
+            .await?;
+
+        // Auxiliary fields
+        let aux_fields = [
+            (b"redis-ver".as_slice(), &self.aux_redis_ver),
+            (b"redis-bits".as_slice(), &self.aux_redis_bits),
+            (b"ctime".as_slice(), &self.aux_ctime),
+            (b"used-mem".as_slice(), &self.aux_used_men),
+        ];
+        for (key, value) in aux_fields {
+            if let Some(value) = value {
+                output.write_u8(0xFA).await?;
+                RedisString::new(key).write(output).await?;
+                RedisString::new(value.as_bytes()).write(output).await?;
+            }
+        }
+
+        for (index, db) in &self.databases {
+            if db.is_empty() {
+                continue;
+            }
+
+            // Select DB & resize DB
+            output.write_u8(0xFE).await?;
+            LengthEncoding::Fixed(*index).write(output).await?;
+            output.write_u8(0xFB).await?;
+            LengthEncoding::Fixed(db.values.len()).write(output).await?;
+            LengthEncoding::Fixed(db.expiry.len()).write(output).await?;
+
+            // Key / values
+            for (key, value) in &db.values {
+                if let Some(expire_at) = db.expiry.get(key) {
+                    output.write_u8(0xFC).await?;
+                    output.write_u64_le(*expire_at).await?;
+                }
+
+                write_value(output, key, value).await?;
+            }
+        }
+
+        // End of file
+        output.write_u8(0xFF).await
+    }
+}
+
+/// Read a size of the RESIZEDB opcode, rejecting negative values.
+async fn read_resize_db_size<R: AsyncRead + Unpin>(input: &mut R) -> Result<usize, MiniRedisError> {
+    usize::try_from(read_integer(input).await?)
+        .map_err(|_| MiniRedisError::InvalidRdbValue("Invalid RESIZEDB size".to_string()))
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+async fn read_value<R: AsyncRead + Unpin>(
+    input: &mut R,
+    value_type: u8,
+) -> Result<Value, MiniRedisError> {
+    match value_type {
+        TYPE_STRING => Ok(Value::String(RedisString::read(input).await?)),
+        TYPE_LIST => {
+            let len = read_integer(input).await? as usize;
+            let mut list = VecDeque::new();
+            for _ in 0..len {
+                list.push_back(RedisString::read(input).await?);
+            }
+            Ok(Value::List(list))
+        }
+        TYPE_LIST_QUICKLIST_2 => {
+            let node_count = read_integer(input).await?;
+            let mut list = VecDeque::new();
+            for _ in 0..node_count {
+                let container = read_integer(input).await?;
+                let node = RedisString::read(input).await?;
+                if container == QUICKLIST_NODE_PLAIN {
+                    list.push_back(node);
+                } else {
+                    list.extend(read_listpack(node.as_slice())?);
+                }
+            }
+            Ok(Value::List(list))
+        }
+        TYPE_SET => {
+            let len = read_integer(input).await? as usize;
+            let mut set = Set::new();
+            for _ in 0..len {
+                set.insert(RedisString::read(input).await?);
+            }
+            Ok(Value::Set(set))
+        }
+        TYPE_SET_INTSET => {
+            let intset = RedisString::read(input).await?;
+            Ok(Value::Set(
+                read_intset(intset.as_slice())?.into_iter().collect(),
+            ))
+        }
+        TYPE_SET_LISTPACK => {
+            let listpack = RedisString::read(input).await?;
+            Ok(Value::Set(
+                read_listpack(listpack.as_slice())?.into_iter().collect(),
+            ))
+        }
+        TYPE_ZSET | TYPE_ZSET_2 => {
+            let len = read_integer(input).await? as usize;
+            let mut zset = SortedSet::new();
+            for _ in 0..len {
+                let member = RedisString::read(input).await?;
+                let score = if value_type == TYPE_ZSET {
+                    read_string_double(input).await?
+                } else {
+                    input.read_f64_le().await?
+                };
+                zset.insert(member, score);
+            }
+            Ok(Value::SortedSet(zset))
+        }
+        TYPE_ZSET_LISTPACK => {
+            let listpack = RedisString::read(input).await?;
+            let entries = read_listpack(listpack.as_slice())?;
+            let invalid =
+                || MiniRedisError::InvalidRdbValue("Invalid sorted set listpack".to_string());
+            if entries.len() % 2 != 0 {
+                return Err(invalid());
+            }
+            let mut zset = SortedSet::new();
+            for pair in entries.chunks(2) {
+                let score = Score::parse(pair[1].as_slice()).ok_or_else(invalid)?;
+                zset.insert(pair[0].clone(), score.0);
+            }
+            Ok(Value::SortedSet(zset))
+        }
+        TYPE_HASH => {
+            let len = read_integer(input).await? as usize;
+            let mut hash = Hash::new();
+            for _ in 0..len {
+                let field = RedisString::read(input).await?;
+                hash.insert(field, RedisString::read(input).await?);
+            }
+            Ok(Value::Hash(hash))
+        }
+        TYPE_HASH_LISTPACK => {
+            let entries = read_listpack(RedisString::read(input).await?.as_slice())?;
+            if entries.len() % 2 != 0 {
+                return Err(MiniRedisError::InvalidRdbValue(
+                    "Hash listpack has an odd number of entries".to_string(),
+                ));
+            }
+            let mut entries = entries.into_iter();
+            let mut hash = Hash::new();
+            while let (Some(field), Some(value)) = (entries.next(), entries.next()) {
+                hash.insert(field, value);
+            }
+            Ok(Value::Hash(hash))
+        }
+        TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+            let mut stream = Stream::default();
+            let node_count = read_integer(input).await?;
+            for _ in 0..node_count {
+                let master_id = RedisString::read(input).await?;
+                let master_id = <[u8; 16]>::try_from(master_id.as_slice()).map_err(|_| {
+                    MiniRedisError::InvalidRdbValue("Invalid stream node ID".to_string())
+                })?;
+                let master_id = stream_id_from_bytes(master_id);
+                let listpack = read_listpack(RedisString::read(input).await?.as_slice())?;
+                read_stream_listpack(&mut stream, master_id, &listpack)?;
+            }
+
+            let _len = read_integer(input).await?;
+            stream.last_id = read_stream_id(input).await?;
+            if value_type == TYPE_STREAM_LISTPACKS {
+                stream.entries_added = stream.entries.len() as u64;
+            } else {
+                let _first_id = read_stream_id(input).await?;
+                stream.max_deleted_id = read_stream_id(input).await?;
+                stream.entries_added = read_integer(input).await? as u64;
+            }
+
+            let group_count = read_integer(input).await?;
+            for _ in 0..group_count {
+                let name = RedisString::read(input).await?;
+                let group = read_consumer_group(input, value_type).await?;
+                stream.groups.insert(name, group);
+            }
+            Ok(Value::Stream(stream))
+        }
+        _ => unreachable!("Value type {value_type} is not supported"),
+    }
+}
+
+async fn write_value<W: AsyncWrite + Unpin>(
+    output: &mut W,
+    key: &RedisString,
+    value: &Value,
+) -> io::Result<()> {
+    match value {
+        Value::String(value) => {
+            output.write_u8(TYPE_STRING).await?;
+            key.write(output).await?;
+            value.write(output).await
+        }
+        Value::List(list) => {
+            output.write_u8(TYPE_LIST).await?;
+            key.write(output).await?;
+            LengthEncoding::Fixed(list.len()).write(output).await?;
+            for element in list {
+                element.write(output).await?;
+            }
+            Ok(())
+        }
+        Value::Set(set) => {
+            output.write_u8(TYPE_SET).await?;
+            key.write(output).await?;
+            LengthEncoding::Fixed(set.len()).write(output).await?;
+            for member in set.iter() {
+                member.write(output).await?;
+            }
+            Ok(())
+        }
+        Value::SortedSet(zset) => {
+            output.write_u8(TYPE_ZSET_2).await?;
+            key.write(output).await?;
+            LengthEncoding::Fixed(zset.len()).write(output).await?;
+            for (member, score) in zset.iter() {
+                member.write(output).await?;
+                output.write_f64_le(score).await?;
+            }
+            Ok(())
+        }
+        Value::Hash(hash) => {
+            output.write_u8(TYPE_HASH).await?;
+            key.write(output).await?;
+            LengthEncoding::Fixed(hash.len()).write(output).await?;
+            for (field, value) in hash.iter() {
+                field.write(output).await?;
+                value.write(output).await?;
+            }
+            Ok(())
+        }
+        Value::Stream(stream) => {
+            output.write_u8(TYPE_STREAM_LISTPACKS_3).await?;
+            key.write(output).await?;
+
+            let entries: Vec<_> = stream.entries.iter().collect();
+            let nodes = entries.chunks(STREAM_NODE_MAX_ENTRIES);
+            LengthEncoding::Fixed(nodes.len()).write(output).await?;
+            for node in nodes {
+                let master_id = *node[0].0;
+                let mut node_key = master_id.ms.to_be_bytes().to_vec();
+                node_key.extend(master_id.seq.to_be_bytes());
+                RedisString::from(node_key).write(output).await?;
+                let listpack = write_listpack(&stream_listpack(master_id, node));
+                RedisString::from(listpack).write(output).await?;
+            }
+
+            let (first_id, last_id, max_deleted_id) =
+                (stream.first_id(), stream.last_id, stream.max_deleted_id);
+            let metadata = [
+                stream.entries.len() as u64,
+                last_id.ms,
+                last_id.seq,
+                first_id.ms,
+                first_id.seq,
+                max_deleted_id.ms,
+                max_deleted_id.seq,
+                stream.entries_added,
+            ];
+            for value in metadata {
+                LengthEncoding::Fixed(value as usize).write(output).await?;
+            }
+
+            LengthEncoding::Fixed(stream.groups.len())
+                .write(output)
+                .await?;
+            for (name, group) in &stream.groups {
+                name.write(output).await?;
+                write_consumer_group(output, group).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn stream_id_from_bytes(bytes: [u8; 16]) -> StreamId {
+    StreamId {
+        ms: u64::from_be_bytes(bytes[..8].try_into().expect("Slice has 8 bytes")),
+        seq: u64::from_be_bytes(bytes[8..].try_into().expect("Slice has 8 bytes")),
+    }
+}
+
+/// Read a stream ID stored as 16 big endian bytes, without length.
+async fn read_raw_stream_id<R: AsyncRead + Unpin>(
+    input: &mut R,
+) -> Result<StreamId, MiniRedisError> {
+    let mut bytes = [0; 16];
+    input.read_exact(&mut bytes).await?;
+    Ok(stream_id_from_bytes(bytes))
+}
+
+async fn write_raw_stream_id<W: AsyncWrite + Unpin>(
+    output: &mut W,
+    id: StreamId,
+) -> io::Result<()> {
+    output.write_u64(id.ms).await?;
+    output.write_u64(id.seq).await
+}
+
+/// Read a consumer group following its name.
+///
+/// Entries read count was added in second stream format, and consumers active time in third.
+async fn read_consumer_group<R: AsyncRead + Unpin>(
+    input: &mut R,
+    value_type: u8,
+) -> Result<ConsumerGroup, MiniRedisError> {
+    let last_id = read_stream_id(input).await?;
+    // Unknown count is stored as -1
+    let entries_read = match value_type {
+        TYPE_STREAM_LISTPACKS => None,
+        _ => u64::try_from(read_integer(input).await?).ok(),
+    };
+    let mut group = ConsumerGroup::new(last_id, entries_read);
+
+    // Group pending entries do not store their consumer, which is only known from consumers
+    let mut deliveries = BTreeMap::new();
+    let pending_count = read_integer(input).await?;
+    for _ in 0..pending_count {
+        let id = read_raw_stream_id(input).await?;
+        let delivery_time = input.read_u64_le().await?;
+        let delivery_count = read_integer(input).await? as u64;
+        deliveries.insert(id, (delivery_time, delivery_count));
+    }
+
+    let consumer_count = read_integer(input).await?;
+    for _ in 0..consumer_count {
+        let name = RedisString::read(input).await?;
+        let seen_time = input.read_u64_le().await?;
+        let active_time = match value_type {
+            TYPE_STREAM_LISTPACKS_3 => u64::try_from(input.read_i64_le().await?).ok(),
+            _ => Some(seen_time),
+        };
+        let mut consumer = Consumer {
+            seen_time,
+            active_time,
+            ..Default::default()
+        };
+        let pending_count = read_integer(input).await?;
+        for _ in 0..pending_count {
+            let id = read_raw_stream_id(input).await?;
+            let (delivery_time, delivery_count) = deliveries.remove(&id).ok_or_else(|| {
+                MiniRedisError::InvalidRdbValue(
+                    "Consumer pending entry is not pending in its group".to_string(),
+                )
+            })?;
+            let entry = PendingEntry {
+                consumer: name.clone(),
+                delivery_time,
+                delivery_count,
+            };
+            group.pending.insert(id, entry);
+            consumer.pending.insert(id);
+        }
+        group.consumers.insert(name, consumer);
+    }
+
+    if !deliveries.is_empty() {
+        return Err(MiniRedisError::InvalidRdbValue(
+            "Group pending entry has no consumer".to_string(),
+        ));
+    }
+    Ok(group)
+}
+
+async fn write_consumer_group<W: AsyncWrite + Unpin>(
+    output: &mut W,
+    group: &ConsumerGroup,
+) -> io::Result<()> {
+    LengthEncoding::Fixed(group.last_id.ms as usize)
+        .write(output)
+        .await?;
+    LengthEncoding::Fixed(group.last_id.seq as usize)
+        .write(output)
+        .await?;
+    let entries_read = group.entries_read.unwrap_or(u64::MAX);
+    LengthEncoding::Fixed(entries_read as usize)
+        .write(output)
+        .await?;
+
+    LengthEncoding::Fixed(group.pending.len())
+        .write(output)
+        .await?;
+    for (id, entry) in &group.pending {
+        write_raw_stream_id(output, *id).await?;
+        output.write_u64_le(entry.delivery_time).await?;
+        LengthEncoding::Fixed(entry.delivery_count as usize)
+            .write(output)
+            .await?;
+    }
+
+    LengthEncoding::Fixed(group.consumers.len())
+        .write(output)
+        .await?;
+    for (name, consumer) in &group.consumers {
+        name.write(output).await?;
+        output.write_u64_le(consumer.seen_time).await?;
+        let active_time = consumer.active_time.map_or(-1, |time| time as i64);
+        output.write_i64_le(active_time).await?;
+        LengthEncoding::Fixed(consumer.pending.len())
+            .write(output)
+            .await?;
+        for id in &consumer.pending {
+            write_raw_stream_id(output, *id).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn read_stream_id<R: AsyncRead + Unpin>(input: &mut R) -> Result<StreamId, MiniRedisError> {
+    let ms = read_integer(input).await? as u64;
+    let seq = read_integer(input).await? as u64;
+    Ok(StreamId { ms, seq })
+}
+
+/// Decode entries of a stream listpack, whose IDs are relative to the master ID.
+fn read_stream_listpack(
+    stream: &mut Stream,
+    master_id: StreamId,
+    elements: &[RedisString],
+) -> Result<(), MiniRedisError> {
+    let invalid = || MiniRedisError::InvalidRdbValue("Invalid stream listpack".to_string());
+    let int = |element: Option<&RedisString>| -> Result<i64, MiniRedisError> {
+        element
+            .and_then(|element| std::str::from_utf8(element.as_slice()).ok()?.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let mut elements = elements.iter();
+
+    // Master entry: counts of valid and deleted entries, then fields shared by entries
+    let _count = int(elements.next())?;
+    let _deleted = int(elements.next())?;
+    let master_field_count = int(elements.next())?;
+    let master_fields: Vec<_> = elements
+        .by_ref()
+        .take(master_field_count as usize)
+        .collect();
+    if master_fields.len() as i64 != master_field_count || int(elements.next())? != 0 {
+        return Err(invalid());
+    }
+
+    while let Some(flags) = elements.next() {
+        let flags = int(Some(flags))?;
+        let id = StreamId {
+            ms: master_id.ms.wrapping_add(int(elements.next())? as u64),
+            seq: master_id.seq.wrapping_add(int(elements.next())? as u64),
+        };
+        let mut fields = Vec::new();
+        if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+            for field in &master_fields {
+                let value = elements.next().ok_or_else(invalid)?;
+                fields.push(((*field).clone(), value.clone()));
+            }
+        } else {
+            for _ in 0..int(elements.next())? {
+                let field = elements.next().ok_or_else(invalid)?;
+                let value = elements.next().ok_or_else(invalid)?;
+                fields.push((field.clone(), value.clone()));
+            }
+        }
+        // Count of elements of the entry, used to iterate backward
+        int(elements.next())?;
+
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            stream.entries.insert(id, fields);
+        }
+    }
+    Ok(())
+}
+
+/// Encode stream entries in a listpack, the fields of the first entry being shared.
+fn stream_listpack(
+    master_id: StreamId,
+    entries: &[(&StreamId, &Vec<(RedisString, RedisString)>)],
+) -> Vec<RedisString> {
+    let master_fields: Vec<_> = entries[0].1.iter().map(|(field, _)| field).collect();
+    let mut elements = vec![
+        RedisString::from_integer(entries.len() as i64),
+        RedisString::from_integer(0),
+        RedisString::from_integer(master_fields.len() as i64),
+    ];
+    elements.extend(master_fields.iter().map(|field| (*field).clone()));
+    elements.push(RedisString::from_integer(0));
+
+    for (id, fields) in entries {
+        let same_fields = fields
+            .iter()
+            .map(|(field, _)| field)
+            .eq(master_fields.iter().copied());
+        let flags = if same_fields {
+            STREAM_ITEM_FLAG_SAMEFIELDS
+        } else {
+            0
+        };
+        elements.push(RedisString::from_integer(flags));
+        elements.push(RedisString::from_integer(
+            id.ms.wrapping_sub(master_id.ms) as i64
+        ));
+        elements.push(RedisString::from_integer(
+            id.seq.wrapping_sub(master_id.seq) as i64,
+        ));
+        if same_fields {
+            elements.extend(fields.iter().map(|(_, value)| value.clone()));
+        } else {
+            elements.push(RedisString::from_integer(fields.len() as i64));
+            elements.extend(
+                fields
+                    .iter()
+                    .flat_map(|(field, value)| [field.clone(), value.clone()]),
+            );
+        }
+        let element_count = if same_fields {
+            fields.len() + 3
+        } else {
+            2 * fields.len() + 4
+        };
+        elements.push(RedisString::from_integer(element_count as i64));
+    }
+    elements
+}
+
+/// Read a double stored as text prefixed by its length, used by old sorted sets.
+async fn read_string_double<R: AsyncRead + Unpin>(input: &mut R) -> Result<f64, MiniRedisError> {
+    match input.read_u8().await? {
+        253 => Err(MiniRedisError::InvalidRdbValue(
+            "Sorted set score is NaN".to_string(),
+        )),
+        254 => Ok(f64::INFINITY),
+        255 => Ok(f64::NEG_INFINITY),
+        len => {
+            let mut buf = vec![0; len as usize];
+            input.read_exact(&mut buf).await?;
+            Score::parse(&buf)
+                .map(|score| score.0)
+                .ok_or_else(|| MiniRedisError::InvalidRdbValue("Invalid double".to_string()))
+        }
+    }
+}
+
+/// Decode members of an intset, the compact encoding of small integer sets.
+fn read_intset(data: &[u8]) -> Result<Vec<RedisString>, MiniRedisError> {
+    let invalid = || MiniRedisError::InvalidRdbValue("Invalid intset".to_string());
+    let header = |pos: usize| -> Result<usize, MiniRedisError> {
+        let bytes = data.get(pos..pos + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("Slice has 4 bytes")) as usize)
+    };
+
+    let width = header(0)?;
+    let len = header(4)?;
+    if !matches!(width, 2 | 4 | 8) || data.len() != 8 + width * len {
+        return Err(invalid());
+    }
+
+    Ok(data[8..]
+        .chunks(width)
+        .map(|bytes| {
+            let mut buf = [0_u8; 8];
+            buf[..width].copy_from_slice(bytes);
+            // Sign extend from highest byte
+            let shift = 64 - 8 * width as u32;
+            RedisString::from_integer(i64::from_le_bytes(buf) << shift >> shift)
+        })
+        .collect())
+}
+
+/// Summary of a valid RDB file, as reported by `redis-check-rdb`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RdbSummary {
+    pub version: u32,
+    /// Auxiliary fields present in the file, by name.
+    pub aux_fields: Vec<(&'static str, String)>,
+    pub checksum: RdbChecksum,
+    pub databases: Vec<RdbDatabaseSummary>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RdbChecksum {
+    /// Version predates checksums.
+    Absent,
+    /// Written as 0 by a server having checksum disabled.
+    Disabled,
+    Verified,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RdbDatabaseSummary {
+    pub index: usize,
+    pub keys: usize,
+    pub expires: usize,
+    pub already_expired: usize,
+}
+
+/// Validate RDB content and summarize it.
+pub async fn check_rdb(data: &[u8]) -> Result<RdbSummary, MiniRedisError> {
+    let rdb = Rdb::read(&mut &data[..]).await?;
+
+    let aux_fields = [
+        ("redis-ver", rdb.aux_redis_ver),
+        ("redis-bits", rdb.aux_redis_bits),
+        ("ctime", rdb.aux_ctime),
+        ("used-mem", rdb.aux_used_men),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect();
+
+    let checksum = if rdb.version < FIRST_CHECKSUM_VERSION {
+        RdbChecksum::Absent
+    } else if data.ends_with(&[0; 8]) {
+        RdbChecksum::Disabled
+    } else {
+        RdbChecksum::Verified
+    };
+
+    let now = now_unix_millis();
+    let databases = rdb
+        .databases
+        .iter()
+        .map(|(index, db)| RdbDatabaseSummary {
+            index: *index,
+            keys: db.values.len(),
+            expires: db.expiry.len(),
+            already_expired: db.expiry.values().filter(|x| **x <= now).count(),
+        })
+        .collect();
+
+    Ok(RdbSummary {
+        version: rdb.version,
+        aux_fields,
+        checksum,
+        databases,
+    })
+}
+
+/// Decode entries of a listpack, the compact encoding of small collections.
+pub fn read_listpack(data: &[u8]) -> Result<Vec<RedisString>, MiniRedisError> {
+    let invalid = || MiniRedisError::InvalidRdbValue("Invalid listpack".to_string());
+    let bytes = |pos: usize, len: usize| data.get(pos..pos + len).ok_or_else(invalid);
+    let int_le = |pos: usize, len: usize| -> Result<i64, MiniRedisError> {
+        let mut buf = [0_u8; 8];
+        buf[..len].copy_from_slice(bytes(pos, len)?);
+        // Sign extend from highest read byte
+        let shift = 64 - 8 * len as u32;
+        Ok(i64::from_le_bytes(buf) << shift >> shift)
+    };
+
+    // Skip total bytes and elements count header
+    let mut pos = 6;
+    let mut output = Vec::new();
+    loop {
+        let b0 = *data.get(pos).ok_or_else(invalid)?;
+        let (entry, len) = if b0 == 0xFF {
+            return Ok(output);
+        } else if b0 & 0x80 == 0 {
+            (RedisString::from_integer((b0 & 0x7F) as i64), 1)
+        } else if b0 & 0xC0 == 0x80 {
+            let str_len = (b0 & 0x3F) as usize;
+            (bytes(pos + 1, str_len)?.into(), 1 + str_len)
+        } else if b0 & 0xE0 == 0xC0 {
+            let value = ((b0 & 0x1F) as i64) << 8 | *data.get(pos + 1).ok_or_else(invalid)? as i64;
+            (RedisString::from_integer(value << 51 >> 51), 2)
+        } else if b0 & 0xF0 == 0xE0 {
+            let str_len =
+                ((b0 & 0x0F) as usize) << 8 | *data.get(pos + 1).ok_or_else(invalid)? as usize;
+            (bytes(pos + 2, str_len)?.into(), 2 + str_len)
+        } else {
+            match b0 {
+                0xF0 => {
+                    let str_len = int_le(pos + 1, 4)? as u32 as usize;
+                    (bytes(pos + 5, str_len)?.into(), 5 + str_len)
+                }
+                0xF1 => (RedisString::from_integer(int_le(pos + 1, 2)?), 3),
+                0xF2 => (RedisString::from_integer(int_le(pos + 1, 3)?), 4),
+                0xF3 => (RedisString::from_integer(int_le(pos + 1, 4)?), 5),
+                0xF4 => (RedisString::from_integer(int_le(pos + 1, 8)?), 9),
+                _ => return Err(invalid()),
+            }
+        };
+
+        output.push(entry);
+
+        // Skip entry and its back length
+        pos += len + backlen_size(len);
+    }
+}
+
+/// Encode entries in a listpack, storing integers in their compact form.
+pub fn write_listpack(entries: &[RedisString]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for entry in entries {
+        let start = body.len();
+        let bytes = entry.as_slice();
+        // Only integers written back identically are stored as integers
+        let integer = std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|text| text.parse::<i64>().ok())
+            .filter(|value| value.to_string().as_bytes() == bytes);
+
+        match integer {
+            Some(value @ 0..=127) => body.push(value as u8),
+            Some(value @ -4096..=4095) => {
+                let value = value as u16 & 0x1FFF;
+                body.extend([0xC0 | (value >> 8) as u8, value as u8]);
+            }
+            Some(value) => {
+                let (encoding, len) = if i16::try_from(value).is_ok() {
+                    (0xF1, 2)
+                } else if (-(1 << 23)..1 << 23).contains(&value) {
+                    (0xF2, 3)
+                } else if i32::try_from(value).is_ok() {
+                    (0xF3, 4)
+                } else {
+                    (0xF4, 8)
+                };
+                body.push(encoding);
+                body.extend(&value.to_le_bytes()[..len]);
+            }
+            None if bytes.len() < 1 << 6 => {
+                body.push(0x80 | bytes.len() as u8);
+                body.extend(bytes);
+            }
+            None if bytes.len() < 1 << 12 => {
+                body.extend([0xE0 | (bytes.len() >> 8) as u8, bytes.len() as u8]);
+                body.extend(bytes);
+            }
+            None => {
+                body.push(0xF0);
+                body.extend((bytes.len() as u32).to_le_bytes());
+                body.extend(bytes);
+            }
+        }
+
+        // Back length is stored from most significant 7 bits, all but the first byte
+        // having their highest bit set
+        let len = body.len() - start;
+        let size = backlen_size(len);
+        for i in (0..size).rev() {
+            let bits = (len >> (7 * i)) as u8 & 0x7F;
+            body.push(if i == size - 1 { bits } else { bits | 0x80 });
+        }
+    }
+
+    let total_len = 6 + body.len() + 1;
+    let mut output = Vec::with_capacity(total_len);
+    output.extend((total_len as u32).to_le_bytes());
+    output.extend((entries.len().min(u16::MAX as usize) as u16).to_le_bytes());
+    output.extend(body);
+    output.push(0xFF);
+    output
+}
+
+/// Number of bytes storing the length of a listpack entry after it.
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct RedisString(Vec<u8>);
 
 impl RedisString {
@@ -118,35 +989,64 @@ impl RedisString {
         &self.0
     }
 
+    pub fn as_mut_vec(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+
+    pub fn from_integer(value: i64) -> Self {
+        Self(value.to_string().into_bytes())
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(&self, output: &mut W) -> io::Result<()> {
+        LengthEncoding::Fixed(self.0.len()).write(output).await?;
+        output.write_all(&self.0).await
+    }
+
     pub async fn read<R: AsyncRead + Unpin>(input: &mut R) -> Result<Self, MiniRedisError> {
         match LengthEncoding::read(input).await? {
-            LengthEncoding::Fixed(len) => {
-                let mut payload = vec![0_u8; len];
-                input.read_exact(&mut payload).await?;
-                Ok(Self(payload))
-            }
+            LengthEncoding::Fixed(len) => Ok(Self(read_bytes(input, len).await?)),
             LengthEncoding::Int8 => {
-                let value = input.read_u8().await?;
-                let mut payload = Vec::with_capacity(3);
+                let value = input.read_i8().await?;
+                let mut payload = Vec::with_capacity(4);
                 write!(payload, "{value}").expect("Fail to write in memory number");
                 Ok(Self(payload))
             }
             LengthEncoding::Int16 => {
-                let value = input.read_u16_le().await?;
-                let mut payload = Vec::with_capacity(5);
+                let value = input.read_i16_le().await?;
+                let mut payload = Vec::with_capacity(6);
                 write!(payload, "{value}").expect("Fail to write in memory number");
                 Ok(Self(payload))
             }
             LengthEncoding::Int32 => {
-                let value = input.read_u32_le().await?;
-                let mut payload = Vec::with_capacity(10);
+                let value = input.read_i32_le().await?;
+                let mut payload = Vec::with_capacity(11);
                 write!(payload, "{value}").expect("Fail to write in memory number");
                 Ok(Self(payload))
             }
+            LengthEncoding::Lzf => {
+                let compressed_len = read_integer(input).await? as usize;
+                let len = read_integer(input).await? as usize;
+                let compressed = read_bytes(input, compressed_len).await?;
+                Ok(Self(lzf::decompress(&compressed, len)?))
+            }
         }
     }
 }
 
+/// Read exactly `len` bytes, growing the buffer with data actually read so a
+/// corrupted length fails on end of input rather than on allocation.
+async fn read_bytes<R: AsyncRead + Unpin>(
+    input: &mut R,
+    len: usize,
+) -> Result<Vec<u8>, MiniRedisError> {
+    let mut payload = Vec::new();
+    input.take(len as u64).read_to_end(&mut payload).await?;
+    if payload.len() != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "early eof").into());
+    }
+    Ok(payload)
+}
+
 impl From<Vec<u8>> for RedisString {
     fn from(value: Vec<u8>) -> Self {
         Self(value)
@@ -192,6 +1092,7 @@ pub enum LengthEncoding {
     Int8,
     Int16,
     Int32,
+    Lzf,
 }
 
 impl LengthEncoding {
@@ -206,39 +1107,59 @@ impl LengthEncoding {
                     u16::from_be_bytes([b0 & 0b0011_1111, b1]) as usize
                 ))
             }
-            0b1000_0000 => {
-                let b1 = input.read_u8().await?;
-                let b2 = input.read_u8().await?;
-                let b3 = input.read_u8().await?;
-                Ok(Self::Fixed(
-                    u32::from_be_bytes([b0 & 0b0011_1111, b1, b2, b3]) as usize,
-                ))
-            }
+            0b1000_0000 => match b0 {
+                0x80 => Ok(Self::Fixed(input.read_u32().await? as usize)),
+                0x81 => Ok(Self::Fixed(input.read_u64().await? as usize)),
+                _ => Err(MiniRedisError::UnsupportedLengthEncoding),
+            },
             0b1100_0000 => match b0 & 0b0011_1111 {
                 0 => Ok(Self::Int8),
                 1 => Ok(Self::Int16),
                 2 => Ok(Self::Int32),
+                3 => Ok(Self::Lzf),
                 _ => Err(MiniRedisError::UnsupportedLengthEncoding),
             },
             _ => unreachable!("Bit mask did not works ?"),
         }
     }
+
+    pub async fn write<W: AsyncWrite + Unpin>(&self, output: &mut W) -> io::Result<()> {
+        match *self {
+            Self::Fixed(len) if len < 1 << 6 => output.write_u8(len as u8).await,
+            Self::Fixed(len) if len < 1 << 14 => {
+                output.write_u16(len as u16 | 0b0100_0000 << 8).await
+            }
+            Self::Fixed(len) if len <= u32::MAX as usize => {
+                output.write_u8(0x80).await?;
+                output.write_u32(len as u32).await
+            }
+            Self::Fixed(len) => {
+                output.write_u8(0x81).await?;
+                output.write_u64(len as u64).await
+            }
+            Self::Int8 => output.write_u8(0b1100_0000).await,
+            Self::Int16 => output.write_u8(0b1100_0001).await,
+            Self::Int32 => output.write_u8(0b1100_0010).await,
+            Self::Lzf => output.write_u8(0b1100_0011).await,
+        }
+    }
 }
 
 pub async fn read_integer<R: AsyncRead + Unpin>(input: &mut R) -> Result<i64, MiniRedisError> {
     match LengthEncoding::read(input).await? {
         LengthEncoding::Fixed(x) => Ok(x as i64),
         LengthEncoding::Int8 => {
-            let value = input.read_u8().await?;
+            let value = input.read_i8().await?;
             Ok(value.into())
         }
         LengthEncoding::Int16 => {
-            let value = input.read_u16_le().await?;
+            let value = input.read_i16_le().await?;
             Ok(value.into())
         }
         LengthEncoding::Int32 => {
-            let value = input.read_u32_le().await?;
+            let value = input.read_i32_le().await?;
             Ok(value.into())
         }
+        LengthEncoding::Lzf => Err(MiniRedisError::UnsupportedLengthEncoding),
     }
 }
@@ -0,0 +1,412 @@
+use std::{
+    collections::{hash_map::RandomState, VecDeque},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, Mutex, Notify, RwLock},
+    time::{self, Instant},
+};
+
+use crate::{
+    database,
+    error::MiniRedisError,
+    rdb::Rdb,
+    resp2::{self, Message},
+    response::Response,
+    ServerMode,
+};
+
+pub const DEFAULT_BACKLOG_SIZE: usize = 1024 * 1024;
+
+/// Replication state shared by all connections.
+#[derive(Debug)]
+pub struct Replication {
+    role: ServerMode,
+    master_replid: RwLock<String>,
+    master_repl_offset: AtomicUsize,
+    replicas: Mutex<Vec<ReplicaHandle>>,
+    backlog: Mutex<Option<Backlog>>,
+    backlog_size: AtomicUsize,
+    acks: Arc<Notify>,
+    /// Database selected by last command sent to replicas.
+    selected_db: Mutex<Option<usize>>,
+}
+
+/// Master side view of a connected replica.
+#[derive(Debug)]
+struct ReplicaHandle {
+    sender: mpsc::UnboundedSender<Arc<[u8]>>,
+    ack_offset: Arc<AtomicUsize>,
+}
+
+/// Connection of a newly registered replica.
+#[derive(Debug)]
+pub struct ReplicaLink {
+    pub sync: ReplicaSync,
+    pub receiver: mpsc::UnboundedReceiver<Arc<[u8]>>,
+    pub acknowledger: ReplicaAcknowledger,
+}
+
+/// Report replica acknowledgements to `WAIT` callers.
+#[derive(Debug)]
+pub struct ReplicaAcknowledger {
+    ack_offset: Arc<AtomicUsize>,
+    acks: Arc<Notify>,
+}
+
+impl ReplicaAcknowledger {
+    /// Record offset replica reported with `REPLCONF ACK`.
+    pub fn ack(&self, offset: usize) {
+        self.ack_offset.store(offset, Ordering::SeqCst);
+        self.acks.notify_waiters();
+    }
+}
+
+/// How a replica has been synchronized on registration.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplicaSync {
+    /// Replica must load an RDB snapshot taken at given offset.
+    Full { replid: String, offset: usize },
+    /// Replica must apply bytes it missed from the backlog.
+    Partial { replid: String, missing: Vec<u8> },
+}
+
+/// Circular buffer keeping the last propagated bytes.
+#[derive(Debug)]
+struct Backlog {
+    buffer: VecDeque<u8>,
+    first_byte_offset: usize,
+}
+
+impl Backlog {
+    fn new(master_repl_offset: usize) -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            first_byte_offset: master_repl_offset + 1,
+        }
+    }
+
+    fn push(&mut self, data: &[u8], size: usize) {
+        self.buffer.extend(data);
+        self.shrink(size);
+    }
+
+    fn shrink(&mut self, size: usize) {
+        let overflow = self.buffer.len().saturating_sub(size);
+        self.buffer.drain(..overflow);
+        self.first_byte_offset += overflow;
+    }
+
+    /// Get bytes starting from offset, if they are still available.
+    fn since(&self, offset: usize) -> Option<Vec<u8>> {
+        let start = offset.checked_sub(self.first_byte_offset)?;
+        if start > self.buffer.len() {
+            return None;
+        }
+        Some(self.buffer.range(start..).copied().collect())
+    }
+}
+
+impl Replication {
+    pub fn new(role: ServerMode) -> Self {
+        // Replicas will receive their ID from master
+        let master_replid = match role {
+            ServerMode::Master => generate_replid(),
+            ServerMode::Slave => String::new(),
+        };
+
+        Self {
+            role,
+            master_replid: RwLock::new(master_replid),
+            master_repl_offset: AtomicUsize::new(0),
+            replicas: Mutex::new(Vec::new()),
+            backlog: Mutex::new(None),
+            backlog_size: AtomicUsize::new(DEFAULT_BACKLOG_SIZE),
+            acks: Arc::new(Notify::new()),
+            selected_db: Mutex::new(None),
+        }
+    }
+
+    pub async fn set_backlog_size(&self, size: usize) {
+        let mut backlog = self.backlog.lock().await;
+        self.backlog_size.store(size, Ordering::SeqCst);
+        if let Some(backlog) = backlog.as_mut() {
+            backlog.shrink(size);
+        }
+    }
+
+    pub fn role(&self) -> &ServerMode {
+        &self.role
+    }
+
+    pub fn offset(&self) -> usize {
+        self.master_repl_offset.load(Ordering::SeqCst)
+    }
+
+    /// Count bytes processed from master stream.
+    pub fn add_offset(&self, count: usize) {
+        self.master_repl_offset.fetch_add(count, Ordering::SeqCst);
+    }
+
+    /// Reset replication ID and offset after a full resynchronization with master.
+    pub async fn set_master(&self, replid: String, offset: usize) {
+        *self.master_replid.write().await = replid;
+        self.master_repl_offset.store(offset, Ordering::SeqCst);
+    }
+
+    pub async fn replid(&self) -> String {
+        self.master_replid.read().await.clone()
+    }
+
+    /// Register a new replica from its `PSYNC` arguments.
+    ///
+    /// Partial resynchronization is used when replica asks for our replication ID
+    /// and for an offset still present in the backlog.
+    pub async fn register_replica(&self, psync_replid: &[u8], psync_offset: i64) -> ReplicaLink {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let replid = self.replid().await;
+
+        // Hold locks so no command is propagated while registering
+        let mut selected_db = self.selected_db.lock().await;
+        let mut replicas = self.replicas.lock().await;
+        let mut backlog = self.backlog.lock().await;
+        let backlog = backlog.get_or_insert_with(|| Backlog::new(self.offset()));
+
+        let missing = match usize::try_from(psync_offset) {
+            Ok(offset) if psync_replid == replid.as_bytes() => backlog.since(offset),
+            _ => None,
+        };
+        let (sync, synced_offset) = match missing {
+            Some(missing) => {
+                let synced_offset = self.offset() - missing.len();
+                (ReplicaSync::Partial { replid, missing }, synced_offset)
+            }
+            None => {
+                // Replica starts from database 0 after loading the snapshot
+                *selected_db = None;
+                (
+                    ReplicaSync::Full {
+                        replid,
+                        offset: self.offset(),
+                    },
+                    self.offset(),
+                )
+            }
+        };
+
+        let ack_offset = Arc::new(AtomicUsize::new(synced_offset));
+        replicas.push(ReplicaHandle {
+            sender,
+            ack_offset: ack_offset.clone(),
+        });
+
+        ReplicaLink {
+            sync,
+            receiver,
+            acknowledger: ReplicaAcknowledger {
+                ack_offset,
+                acks: self.acks.clone(),
+            },
+        }
+    }
+
+    /// Forget a replica whose connection closed, so it no longer counts for `WAIT`.
+    pub async fn unregister_replica(&self, acknowledger: &ReplicaAcknowledger) {
+        self.replicas
+            .lock()
+            .await
+            .retain(|replica| !Arc::ptr_eq(&replica.ack_offset, &acknowledger.ack_offset));
+    }
+
+    pub async fn replica_count(&self) -> usize {
+        self.replicas.lock().await.len()
+    }
+
+    /// Count connected replicas which have processed stream up to offset.
+    async fn acknowledged_count(&self, offset: usize) -> usize {
+        self.replicas
+            .lock()
+            .await
+            .iter()
+            .filter(|replica| !replica.sender.is_closed())
+            .filter(|replica| replica.ack_offset.load(Ordering::SeqCst) >= offset)
+            .count()
+    }
+
+    /// Wait until enough replicas acknowledged current offset or timeout (in ms) expires.
+    ///
+    /// A zero timeout waits forever, as for Redis `WAIT`.
+    pub async fn wait(&self, numreplicas: usize, timeout: u64) -> usize {
+        let target = self.offset();
+        let deadline = Instant::now() + Duration::from_millis(timeout);
+
+        let count = self.acknowledged_count(target).await;
+        if count >= numreplicas {
+            return count;
+        }
+
+        // Futures must be created before asking for acks so none is missed
+        let mut notified = Box::pin(self.acks.notified());
+        self.propagate(&Message::command(&[b"REPLCONF", b"GETACK", b"*"]))
+            .await;
+
+        loop {
+            if timeout == 0 {
+                notified.as_mut().await;
+            } else if time::timeout_at(deadline, notified.as_mut()).await.is_err() {
+                return self.acknowledged_count(target).await;
+            }
+
+            notified = Box::pin(self.acks.notified());
+            let count = self.acknowledged_count(target).await;
+            if count >= numreplicas {
+                return count;
+            }
+        }
+    }
+
+    /// Propagate a write command applied to given database.
+    pub async fn propagate_write(&self, db: usize, msg: &Message) {
+        // Hold lock until command is sent so replicas see commands in the right database
+        let mut selected_db = self.selected_db.lock().await;
+        let mut data = Vec::new();
+        if *selected_db != Some(db) {
+            data = database::select_command(db).to_bytes().await;
+            *selected_db = Some(db);
+        }
+        data.extend(msg.to_bytes().await);
+
+        self.send(data.into()).await;
+    }
+
+    /// Forward a command to every connected replica, whatever database they have selected.
+    pub async fn propagate(&self, msg: &Message) {
+        self.send(msg.to_bytes().await.into()).await;
+    }
+
+    async fn send(&self, data: Arc<[u8]>) {
+        // Hold lock while updating offset so replicas see a consistent stream
+        let mut replicas = self.replicas.lock().await;
+        if let Some(backlog) = self.backlog.lock().await.as_mut() {
+            backlog.push(&data, self.backlog_size.load(Ordering::SeqCst));
+        }
+        self.master_repl_offset
+            .fetch_add(data.len(), Ordering::SeqCst);
+        replicas.retain(|replica| replica.sender.send(data.clone()).is_ok());
+    }
+
+    pub async fn info(&self) -> Response {
+        let backlog = self.backlog.lock().await;
+
+        Response::InfoReplication {
+            role: self.role,
+            master_replid: self.master_replid.read().await.clone(),
+            master_repl_offset: self.offset(),
+            repl_backlog_active: backlog.is_some().into(),
+            repl_backlog_size: self.backlog_size.load(Ordering::SeqCst),
+            repl_backlog_first_byte_offset: backlog
+                .as_ref()
+                .map(|backlog| backlog.first_byte_offset)
+                .unwrap_or_default(),
+            repl_backlog_histlen: backlog
+                .as_ref()
+                .map(|backlog| backlog.buffer.len())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Generate a random 40 characters replication ID.
+fn generate_replid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+
+    // Each `RandomState` is seeded with random keys by the standard library
+    let mut output = String::with_capacity(48);
+    for i in 0..3 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(nanos ^ i);
+        output.push_str(&format!("{:016x}", hasher.finish()));
+    }
+
+    output.truncate(40);
+    output
+}
+
+/// Result of a successful handshake with master.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FullResync {
+    pub replid: String,
+    pub offset: usize,
+    pub rdb: Rdb,
+}
+
+/// Perform replica side of the replication handshake.
+///
+/// Once it returns, reader is positioned on the first propagated command.
+///
+/// Full resynchronization is always requested with `PSYNC ? -1`: replicas do not reconnect
+/// to their master, so they never have a replication ID and offset to resume from. Partial
+/// resynchronization is only served by masters.
+pub async fn handshake<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    listening_port: u16,
+) -> Result<FullResync, MiniRedisError>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    Message::command(&[b"PING"]).write(writer).await?;
+    expect_reply(reader, "PONG").await?;
+
+    let port = listening_port.to_string();
+    Message::command(&[b"REPLCONF", b"listening-port", port.as_bytes()])
+        .write(writer)
+        .await?;
+    expect_reply(reader, "OK").await?;
+
+    Message::command(&[b"REPLCONF", b"capa", b"psync2"])
+        .write(writer)
+        .await?;
+    expect_reply(reader, "OK").await?;
+
+    Message::command(&[b"PSYNC", b"?", b"-1"])
+        .write(writer)
+        .await?;
+    let (replid, offset) = match Message::read(reader).await? {
+        Message::Text(text) => match text.split(' ').collect::<Vec<_>>()[..] {
+            ["FULLRESYNC", replid, offset] => (replid.to_string(), offset.parse()?),
+            _ => return Err(MiniRedisError::InvalidHandshake(text)),
+        },
+        msg => return Err(MiniRedisError::InvalidHandshake(format!("{msg:?}"))),
+    };
+
+    let payload = resp2::read_unterminated_binary(reader).await?;
+    let rdb = Rdb::read(&mut payload.as_slice()).await?;
+
+    Ok(FullResync {
+        replid,
+        offset,
+        rdb,
+    })
+}
+
+async fn expect_reply<R>(reader: &mut R, expected: &str) -> Result<(), MiniRedisError>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    match Message::read(reader).await? {
+        Message::Text(text) if text.eq_ignore_ascii_case(expected) => Ok(()),
+        msg => Err(MiniRedisError::InvalidHandshake(format!("{msg:?}"))),
+    }
+}
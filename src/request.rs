@@ -1,25 +1,257 @@
+use std::str::FromStr;
+
 use tokio::io::AsyncRead;
 
-use crate::{error::MiniRedisError, rdb::RedisString, resp2::Message};
+use crate::{
+    database::{
+        parse_float, parse_integer, BitFieldOp, BitFieldType, BitOperation, BitOverflow, BitUnit,
+        ExpireOptions, LcsOptions, LexBound, ListEnd, PendingRange, ScanOptions, Score, ScoreBound,
+        SetCondition, SetExpiry, SetOptions, StreamId, StreamTrim, StreamTrimOptions, XAddId,
+        XAddOptions, XClaimOptions, ZAddOptions, ZRange, ZRangeBy, MAX_BIT_OFFSET,
+        STREAM_NODE_MAX_ENTRIES,
+    },
+    error::MiniRedisError,
+    rdb::RedisString,
+    resp2::Message,
+};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Request {
     Ping,
     Echo(RedisString),
     Get(RedisString),
-    Set(RedisString, RedisString),
-    SetExpire(RedisString, RedisString, u64),
-    Keys,
+    Set(RedisString, RedisString, SetOptions),
+    /// Key, milliseconds from now and conditions.
+    Expire(RedisString, i64, ExpireOptions),
+    /// Key, unix time in milliseconds and conditions.
+    PExpireAt(RedisString, u64, ExpireOptions),
+    Ttl(RedisString),
+    PTtl(RedisString),
+    ExpireTime(RedisString),
+    PExpireTime(RedisString),
+    Persist(RedisString),
+    /// Glob pattern.
+    Keys(RedisString),
+    /// Cursor and options.
+    Scan(u64, ScanOptions),
+    HScan(RedisString, u64, ScanOptions),
+    SScan(RedisString, u64, ScanOptions),
+    ZScan(RedisString, u64, ScanOptions),
+    Append(RedisString, RedisString),
+    StrLen(RedisString),
+    GetRange(RedisString, i64, i64),
+    SetRange(RedisString, usize, RedisString),
+    GetDel(RedisString),
+    /// Key and expiry, removed if `None` and unchanged if `SetExpiry::Keep`.
+    GetEx(RedisString, Option<SetExpiry>),
+    MGet(Vec<RedisString>),
+    MSet(Vec<(RedisString, RedisString)>),
+    MSetNx(Vec<(RedisString, RedisString)>),
+    SetNx(RedisString, RedisString),
+    Lcs(RedisString, RedisString, LcsOptions),
+    /// Key and increment of `INCR`, `DECR`, `INCRBY` and `DECRBY`.
+    IncrBy(RedisString, i64),
+    IncrByFloat(RedisString, RedisString),
+    /// Key, bit offset and bit.
+    SetBit(RedisString, usize, bool),
+    GetBit(RedisString, usize),
+    /// Key and inclusive range, the whole string if `None`.
+    BitCount(RedisString, Option<(i64, i64, BitUnit)>),
+    /// Key, bit, start, end and unit of the range.
+    BitPos(RedisString, bool, i64, Option<i64>, BitUnit),
+    /// Operation, destination and source keys.
+    BitOp(BitOperation, RedisString, Vec<RedisString>),
+    BitField(RedisString, Vec<BitFieldOp>),
+    /// Only holds `GET` and `OVERFLOW` subcommands.
+    BitFieldRo(RedisString, Vec<BitFieldOp>),
+    Del(Vec<RedisString>),
+    Unlink(Vec<RedisString>),
+    Exists(Vec<RedisString>),
+    Touch(Vec<RedisString>),
+    Rename(RedisString, RedisString),
+    RenameNx(RedisString, RedisString),
+    /// Source, destination, destination database and whether to replace it.
+    Copy(RedisString, RedisString, Option<usize>, bool),
+    RandomKey,
+    DbSize,
     ConfigGet(RedisString),
     UnhandledCommand,
+    /// Known command with invalid arguments.
+    Invalid(MiniRedisError),
     InfoReplication,
+    InfoStats,
+    /// `INFO` of every section.
+    InfoAll,
+    ReplConf(Vec<RedisString>),
+    ReplConfGetAck,
+    ReplConfAck(usize),
+    Psync(RedisString, i64),
+    Wait(usize, u64),
+    ConfigSet(RedisString, RedisString),
+    Save,
+    BgSave,
+    LastSave,
+    BgRewriteAof,
+    Shutdown(Option<bool>),
+    Select(usize),
+    SwapDb(usize, usize),
+    Move(RedisString, usize),
+    FlushDb,
+    FlushAll,
+    LPush(RedisString, Vec<RedisString>),
+    RPush(RedisString, Vec<RedisString>),
+    LPop(RedisString, Option<usize>),
+    RPop(RedisString, Option<usize>),
+    LLen(RedisString),
+    LRange(RedisString, i64, i64),
+    LIndex(RedisString, i64),
+    LSet(RedisString, i64, RedisString),
+    LRem(RedisString, i64, RedisString),
+    LTrim(RedisString, i64, i64),
+    /// Key, insert before pivot, pivot, element.
+    LInsert(RedisString, bool, RedisString, RedisString),
+    LMove(RedisString, RedisString, ListEnd, ListEnd),
+    /// Keys and timeout in milliseconds, zero blocking forever.
+    BLPop(Vec<RedisString>, u64),
+    BRPop(Vec<RedisString>, u64),
+    BLMove(RedisString, RedisString, ListEnd, ListEnd, u64),
+    HSet(RedisString, Vec<(RedisString, RedisString)>),
+    HSetNx(RedisString, RedisString, RedisString),
+    HGet(RedisString, RedisString),
+    HMGet(RedisString, Vec<RedisString>),
+    HDel(RedisString, Vec<RedisString>),
+    HExists(RedisString, RedisString),
+    HLen(RedisString),
+    HStrLen(RedisString, RedisString),
+    HKeys(RedisString),
+    HVals(RedisString),
+    HGetAll(RedisString),
+    HIncrBy(RedisString, RedisString, i64),
+    /// Key, field and increment, checked to be a valid float.
+    HIncrByFloat(RedisString, RedisString, RedisString),
+    /// Key, optional count and whether values are returned.
+    HRandField(RedisString, Option<i64>, bool),
+    SAdd(RedisString, Vec<RedisString>),
+    SRem(RedisString, Vec<RedisString>),
+    SMembers(RedisString),
+    SIsMember(RedisString, RedisString),
+    SMIsMember(RedisString, Vec<RedisString>),
+    SCard(RedisString),
+    SPop(RedisString, Option<usize>),
+    SRandMember(RedisString, Option<i64>),
+    SMove(RedisString, RedisString, RedisString),
+    SInter(Vec<RedisString>),
+    SUnion(Vec<RedisString>),
+    SDiff(Vec<RedisString>),
+    SInterStore(RedisString, Vec<RedisString>),
+    SUnionStore(RedisString, Vec<RedisString>),
+    SDiffStore(RedisString, Vec<RedisString>),
+    /// Keys and limit, zero meaning no limit.
+    SInterCard(Vec<RedisString>, usize),
+    /// Key, conditions, whether changed members are counted, and scores with members.
+    ZAdd(RedisString, ZAddOptions, bool, Vec<(Score, RedisString)>),
+    /// `ZADD` with `INCR` option.
+    ZAddIncr(RedisString, ZAddOptions, Score, RedisString),
+    ZIncrBy(RedisString, Score, RedisString),
+    ZRem(RedisString, Vec<RedisString>),
+    ZScore(RedisString, RedisString),
+    ZCard(RedisString),
+    /// Key, member and whether score is returned.
+    ZRank(RedisString, RedisString, bool),
+    ZRevRank(RedisString, RedisString, bool),
+    /// Key, range and whether scores are returned.
+    ZRange(RedisString, ZRange, bool),
+    ZRangeStore(RedisString, RedisString, ZRange),
+    ZCount(RedisString, ScoreBound, ScoreBound),
+    ZPopMin(RedisString, Option<usize>),
+    ZPopMax(RedisString, Option<usize>),
+    Type(RedisString),
+    XAdd(
+        RedisString,
+        XAddOptions,
+        XAddId,
+        Vec<(RedisString, RedisString)>,
+    ),
+    XLen(RedisString),
+    /// Key, first and last IDs, and maximum count.
+    XRange(RedisString, StreamId, StreamId, Option<usize>),
+    /// Key, first and last IDs, and maximum count, entries being returned from the last one.
+    XRevRange(RedisString, StreamId, StreamId, Option<usize>),
+    XDel(RedisString, Vec<StreamId>),
+    XTrim(RedisString, StreamTrimOptions),
+    /// Keys with the ID after which entries are read, `None` standing for `$`, maximum count
+    /// per stream and timeout in milliseconds when blocking.
+    XRead(
+        Vec<(RedisString, Option<StreamId>)>,
+        Option<usize>,
+        Option<u64>,
+    ),
+    /// Key, last ID, entries added and maximum deleted ID.
+    XSetId(RedisString, StreamId, Option<u64>, Option<StreamId>),
+    /// Key, group, last delivered ID with `None` standing for `$`, whether stream is created
+    /// if missing, and entries read.
+    XGroupCreate(
+        RedisString,
+        RedisString,
+        Option<StreamId>,
+        bool,
+        Option<u64>,
+    ),
+    XGroupDestroy(RedisString, RedisString),
+    /// Key, group, last delivered ID with `None` standing for `$`, and entries read.
+    XGroupSetId(RedisString, RedisString, Option<StreamId>, Option<u64>),
+    /// Key, group and consumer.
+    XGroupCreateConsumer(RedisString, RedisString, RedisString),
+    XGroupDelConsumer(RedisString, RedisString, RedisString),
+    /// Group, consumer, keys with the ID after which pending entries are read, `None` standing
+    /// for `>`, maximum count per stream, timeout in milliseconds when blocking, and whether
+    /// entries are read without being pending (`NOACK`).
+    XReadGroup(
+        RedisString,
+        RedisString,
+        Vec<(RedisString, Option<StreamId>)>,
+        Option<usize>,
+        Option<u64>,
+        bool,
+    ),
+    XAck(RedisString, RedisString, Vec<StreamId>),
+    /// Key, group, and range of the extended form.
+    XPending(RedisString, RedisString, Option<PendingRange>),
+    /// Key, group, consumer, minimum idle time, IDs and options.
+    XClaim(
+        RedisString,
+        RedisString,
+        RedisString,
+        u64,
+        Vec<StreamId>,
+        XClaimOptions,
+    ),
+    /// Key, group, consumer, minimum idle time, start ID, count and whether only IDs are
+    /// returned.
+    XAutoClaim(
+        RedisString,
+        RedisString,
+        RedisString,
+        u64,
+        StreamId,
+        usize,
+        bool,
+    ),
+    XInfoStream(RedisString),
+    XInfoGroups(RedisString),
+    /// Key and group.
+    XInfoConsumers(RedisString, RedisString),
 }
 
 impl Request {
     pub async fn read<R: AsyncRead + Unpin + Send>(reader: &mut R) -> Result<Self, MiniRedisError> {
         let msg = Message::read(reader).await?;
+        Ok(Self::parse(&msg))
+    }
 
-        Ok(match &msg {
+    /// Decode request from an already read message.
+    pub fn parse(msg: &Message) -> Self {
+        match msg {
             Message::Array(args) => match &args[..] {
                 // Debug commands
                 [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"PING") => Self::Ping,
@@ -29,6 +261,21 @@ impl Request {
                 {
                     Self::InfoReplication
                 }
+                [Message::Binary(arg1), Message::Binary(arg2)]
+                    if arg1.eq_ignore_ascii_case(b"INFO")
+                        && arg2.eq_ignore_ascii_case(b"stats") =>
+                {
+                    Self::InfoStats
+                }
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"INFO") => Self::InfoAll,
+                [Message::Binary(arg1), Message::Binary(arg2)]
+                    if arg1.eq_ignore_ascii_case(b"INFO")
+                        && [b"all".as_slice(), b"default", b"everything"]
+                            .iter()
+                            .any(|section| arg2.eq_ignore_ascii_case(section)) =>
+                {
+                    Self::InfoAll
+                }
                 [Message::Binary(arg1), Message::Binary(data)]
                     if arg1.eq_ignore_ascii_case(b"ECHO") =>
                 {
@@ -40,25 +287,379 @@ impl Request {
                 {
                     Self::Get(RedisString::new(key))
                 }
-                [Message::Binary(arg1), Message::Binary(key), Message::Binary(value)]
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(value), options @ ..]
                     if arg1.eq_ignore_ascii_case(b"SET") =>
                 {
-                    Self::Set(RedisString::new(key), RedisString::new(value))
+                    match parse_set_options(options) {
+                        Ok(options) => {
+                            Self::Set(RedisString::new(key), RedisString::new(value), options)
+                        }
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"GETSET") =>
+                {
+                    let options = SetOptions {
+                        get: true,
+                        ..Default::default()
+                    };
+                    Self::Set(RedisString::new(key), RedisString::new(value), options)
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(time), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"SETEX")
+                        || arg1.eq_ignore_ascii_case(b"PSETEX") =>
+                {
+                    let (option, command) = if arg1.eq_ignore_ascii_case(b"SETEX") {
+                        (&b"EX"[..], "setex")
+                    } else {
+                        (&b"PX"[..], "psetex")
+                    };
+                    match parse_set_expiry(option, time, command) {
+                        Ok(expiry) => {
+                            let options = SetOptions {
+                                expiry: Some(expiry),
+                                ..Default::default()
+                            };
+                            Self::Set(RedisString::new(key), RedisString::new(value), options)
+                        }
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"SETNX") =>
+                {
+                    Self::SetNx(RedisString::new(key), RedisString::new(value))
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"APPEND") =>
+                {
+                    Self::Append(RedisString::new(key), RedisString::new(value))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"STRLEN") =>
+                {
+                    Self::StrLen(RedisString::new(key))
                 }
-                [Message::Binary(arg1), Message::Binary(key), Message::Binary(value), Message::Binary(arg_px), Message::Binary(expiry_raw)]
-                    if arg1.eq_ignore_ascii_case(b"SET") && arg_px.eq_ignore_ascii_case(b"PX") =>
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(start), Message::Binary(end)]
+                    if arg1.eq_ignore_ascii_case(b"GETRANGE") =>
                 {
-                    let ms_delta = String::from_utf8_lossy(expiry_raw)
-                        .parse()
-                        .unwrap_or_default();
-                    Self::SetExpire(RedisString::new(key), RedisString::new(value), ms_delta)
+                    match (parse_number(start), parse_number(end)) {
+                        (Some(start), Some(end)) => {
+                            Self::GetRange(RedisString::new(key), start, end)
+                        }
+                        _ => Self::Invalid(MiniRedisError::NotInteger),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(offset), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"SETRANGE") =>
+                {
+                    match parse_number::<i64>(offset) {
+                        Some(offset) if offset >= 0 => Self::SetRange(
+                            RedisString::new(key),
+                            offset as usize,
+                            RedisString::new(value),
+                        ),
+                        Some(_) => Self::Invalid(MiniRedisError::OffsetOutOfRange),
+                        None => Self::Invalid(MiniRedisError::NotInteger),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"GETDEL") =>
+                {
+                    Self::GetDel(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"GETEX") =>
+                {
+                    match parse_getex_expiry(options) {
+                        Ok(expiry) => Self::GetEx(RedisString::new(key), expiry),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), keys @ ..] if arg1.eq_ignore_ascii_case(b"MGET") => {
+                    match parse_strings(keys) {
+                        Some(keys) if !keys.is_empty() => Self::MGet(keys),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"MSET")
+                        || arg1.eq_ignore_ascii_case(b"MSETNX") =>
+                {
+                    match parse_strings(args) {
+                        Some(args) if !args.is_empty() && args.len() % 2 == 0 => {
+                            let pairs = args
+                                .chunks(2)
+                                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                                .collect();
+                            if arg1.eq_ignore_ascii_case(b"MSET") {
+                                Self::MSet(pairs)
+                            } else {
+                                Self::MSetNx(pairs)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key1), Message::Binary(key2), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"LCS") =>
+                {
+                    match parse_lcs_options(options) {
+                        Ok(options) => {
+                            Self::Lcs(RedisString::new(key1), RedisString::new(key2), options)
+                        }
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"INCR") || arg1.eq_ignore_ascii_case(b"DECR") =>
+                {
+                    let increment = if arg1.eq_ignore_ascii_case(b"INCR") {
+                        1
+                    } else {
+                        -1
+                    };
+                    Self::IncrBy(RedisString::new(key), increment)
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(increment)]
+                    if arg1.eq_ignore_ascii_case(b"INCRBY")
+                        || arg1.eq_ignore_ascii_case(b"DECRBY") =>
+                {
+                    let key = RedisString::new(key);
+                    match parse_integer(increment) {
+                        Some(increment) if arg1.eq_ignore_ascii_case(b"INCRBY") => {
+                            Self::IncrBy(key, increment)
+                        }
+                        Some(decrement) => match decrement.checked_neg() {
+                            Some(increment) => Self::IncrBy(key, increment),
+                            None => Self::Invalid(MiniRedisError::NotInteger),
+                        },
+                        None => Self::Invalid(MiniRedisError::NotInteger),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(increment)]
+                    if arg1.eq_ignore_ascii_case(b"INCRBYFLOAT") =>
+                {
+                    match parse_float(increment) {
+                        Some(_) => {
+                            Self::IncrByFloat(RedisString::new(key), RedisString::new(increment))
+                        }
+                        None => Self::Invalid(MiniRedisError::NotFloat),
+                    }
+                }
+                // Bitmaps
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(offset), Message::Binary(bit)]
+                    if arg1.eq_ignore_ascii_case(b"SETBIT") =>
+                {
+                    let bit = match &bit[..] {
+                        b"0" => false,
+                        b"1" => true,
+                        _ => return Self::Invalid(MiniRedisError::InvalidBit),
+                    };
+                    match parse_bit_offset(offset, None) {
+                        Ok(offset) => Self::SetBit(RedisString::new(key), offset, bit),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(offset)]
+                    if arg1.eq_ignore_ascii_case(b"GETBIT") =>
+                {
+                    match parse_bit_offset(offset, None) {
+                        Ok(offset) => Self::GetBit(RedisString::new(key), offset),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), range @ ..]
+                    if arg1.eq_ignore_ascii_case(b"BITCOUNT") =>
+                {
+                    let range = match range {
+                        [] => Ok(None),
+                        [Message::Binary(start), Message::Binary(end), unit @ ..] => {
+                            parse_bit_range(start, end, unit).map(Some)
+                        }
+                        _ => Err(MiniRedisError::SyntaxError),
+                    };
+                    match range {
+                        Ok(range) => Self::BitCount(RedisString::new(key), range),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(bit), range @ ..]
+                    if arg1.eq_ignore_ascii_case(b"BITPOS") =>
+                {
+                    let bit = match parse_integer(bit) {
+                        Some(0) => false,
+                        Some(1) => true,
+                        Some(_) => return Self::Invalid(MiniRedisError::InvalidBitPosBit),
+                        None => return Self::Invalid(MiniRedisError::NotInteger),
+                    };
+                    let range = match range {
+                        [] => Ok((0, None, BitUnit::Byte)),
+                        [Message::Binary(start)] => parse_integer(start)
+                            .map(|start| (start, None, BitUnit::Byte))
+                            .ok_or(MiniRedisError::NotInteger),
+                        [Message::Binary(start), Message::Binary(end), unit @ ..] => {
+                            parse_bit_range(start, end, unit)
+                                .map(|(start, end, unit)| (start, Some(end), unit))
+                        }
+                        _ => Err(MiniRedisError::SyntaxError),
+                    };
+                    match range {
+                        Ok((start, end, unit)) => {
+                            Self::BitPos(RedisString::new(key), bit, start, end, unit)
+                        }
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(operation), Message::Binary(destination), keys @ ..]
+                    if arg1.eq_ignore_ascii_case(b"BITOP") =>
+                {
+                    let operation = [
+                        (&b"AND"[..], BitOperation::And),
+                        (b"OR", BitOperation::Or),
+                        (b"XOR", BitOperation::Xor),
+                        (b"NOT", BitOperation::Not),
+                    ]
+                    .into_iter()
+                    .find(|(name, _)| operation.eq_ignore_ascii_case(name))
+                    .map(|(_, operation)| operation);
+                    match (operation, parse_strings(keys)) {
+                        (_, None) => Self::UnhandledCommand,
+                        (_, Some(keys)) if keys.is_empty() => Self::UnhandledCommand,
+                        (None, _) => Self::Invalid(MiniRedisError::SyntaxError),
+                        (Some(BitOperation::Not), Some(keys)) if keys.len() != 1 => {
+                            Self::Invalid(MiniRedisError::BitOpNotSingleKey)
+                        }
+                        (Some(operation), Some(keys)) => {
+                            Self::BitOp(operation, RedisString::new(destination), keys)
+                        }
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"BITFIELD")
+                        || arg1.eq_ignore_ascii_case(b"BITFIELD_RO") =>
+                {
+                    let read_only = arg1.eq_ignore_ascii_case(b"BITFIELD_RO");
+                    match parse_bitfield(args, read_only) {
+                        Ok(ops) if read_only => Self::BitFieldRo(RedisString::new(key), ops),
+                        Ok(ops) => Self::BitField(RedisString::new(key), ops),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                // Expiry
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(time), options @ ..]
+                    if [&b"EXPIRE"[..], b"PEXPIRE", b"EXPIREAT", b"PEXPIREAT"]
+                        .iter()
+                        .any(|name| arg1.eq_ignore_ascii_case(name)) =>
+                {
+                    parse_expire(arg1, RedisString::new(key), time, options)
+                        .unwrap_or_else(Self::Invalid)
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"TTL") =>
+                {
+                    Self::Ttl(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"PTTL") =>
+                {
+                    Self::PTtl(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"EXPIRETIME") =>
+                {
+                    Self::ExpireTime(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"PEXPIRETIME") =>
+                {
+                    Self::PExpireTime(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"PERSIST") =>
+                {
+                    Self::Persist(RedisString::new(key))
                 }
                 // Keys
                 [Message::Binary(arg1), Message::Binary(pattern)]
-                    if arg1.eq_ignore_ascii_case(b"KEYS") && pattern == b"*" =>
+                    if arg1.eq_ignore_ascii_case(b"KEYS") =>
                 {
-                    Self::Keys
+                    Self::Keys(RedisString::new(pattern))
                 }
+                [Message::Binary(arg1), Message::Binary(cursor), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"SCAN") =>
+                {
+                    match parse_scan(cursor, options, true) {
+                        Ok((cursor, options)) => Self::Scan(cursor, options),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(cursor), options @ ..]
+                    if [&b"HSCAN"[..], b"SSCAN", b"ZSCAN"]
+                        .iter()
+                        .any(|name| arg1.eq_ignore_ascii_case(name)) =>
+                {
+                    let key = RedisString::new(key);
+                    match parse_scan(cursor, options, false) {
+                        Ok((cursor, options)) if arg1.eq_ignore_ascii_case(b"HSCAN") => {
+                            Self::HScan(key, cursor, options)
+                        }
+                        Ok((cursor, options)) if arg1.eq_ignore_ascii_case(b"SSCAN") => {
+                            Self::SScan(key, cursor, options)
+                        }
+                        Ok((cursor, options)) => Self::ZScan(key, cursor, options),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+
+                [Message::Binary(arg1), keys @ ..]
+                    if [&b"DEL"[..], b"UNLINK", b"EXISTS", b"TOUCH"]
+                        .iter()
+                        .any(|name| arg1.eq_ignore_ascii_case(name)) =>
+                {
+                    match parse_strings(keys) {
+                        Some(keys) if !keys.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"DEL") {
+                                Self::Del(keys)
+                            } else if arg1.eq_ignore_ascii_case(b"UNLINK") {
+                                Self::Unlink(keys)
+                            } else if arg1.eq_ignore_ascii_case(b"EXISTS") {
+                                Self::Exists(keys)
+                            } else {
+                                Self::Touch(keys)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(source), Message::Binary(destination)]
+                    if arg1.eq_ignore_ascii_case(b"RENAME") =>
+                {
+                    Self::Rename(RedisString::new(source), RedisString::new(destination))
+                }
+                [Message::Binary(arg1), Message::Binary(source), Message::Binary(destination)]
+                    if arg1.eq_ignore_ascii_case(b"RENAMENX") =>
+                {
+                    Self::RenameNx(RedisString::new(source), RedisString::new(destination))
+                }
+                [Message::Binary(arg1), Message::Binary(source), Message::Binary(destination), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"COPY") =>
+                {
+                    match parse_copy_options(options) {
+                        Ok((index, replace)) => Self::Copy(
+                            RedisString::new(source),
+                            RedisString::new(destination),
+                            index,
+                            replace,
+                        ),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"RANDOMKEY") => {
+                    Self::RandomKey
+                }
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"DBSIZE") => Self::DbSize,
 
                 // Config get
                 [Message::Binary(arg1), Message::Binary(arg2), Message::Binary(key)]
@@ -67,6 +668,741 @@ impl Request {
                 {
                     Self::ConfigGet(RedisString::new(key))
                 }
+                [Message::Binary(arg1), Message::Binary(arg2), Message::Binary(key), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"CONFIG")
+                        && arg2.eq_ignore_ascii_case(b"SET") =>
+                {
+                    Self::ConfigSet(
+                        RedisString::new(&key.to_ascii_lowercase()),
+                        RedisString::new(value),
+                    )
+                }
+
+                // Replication
+                [Message::Binary(arg1), Message::Binary(arg2), Message::Binary(_)]
+                    if arg1.eq_ignore_ascii_case(b"REPLCONF")
+                        && arg2.eq_ignore_ascii_case(b"GETACK") =>
+                {
+                    Self::ReplConfGetAck
+                }
+                [Message::Binary(arg1), Message::Binary(arg2), Message::Binary(offset)]
+                    if arg1.eq_ignore_ascii_case(b"REPLCONF")
+                        && arg2.eq_ignore_ascii_case(b"ACK") =>
+                {
+                    match String::from_utf8_lossy(offset).parse() {
+                        Ok(offset) => Self::ReplConfAck(offset),
+                        Err(_) => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), args @ ..] if arg1.eq_ignore_ascii_case(b"REPLCONF") => {
+                    let mut output = Vec::with_capacity(args.len());
+                    for arg in args {
+                        match arg {
+                            Message::Binary(arg) => output.push(RedisString::new(arg)),
+                            _ => return Self::UnhandledCommand,
+                        }
+                    }
+                    Self::ReplConf(output)
+                }
+                [Message::Binary(arg1), Message::Binary(replid), Message::Binary(offset)]
+                    if arg1.eq_ignore_ascii_case(b"PSYNC") =>
+                {
+                    match String::from_utf8_lossy(offset).parse() {
+                        Ok(offset) => Self::Psync(RedisString::new(replid), offset),
+                        Err(_) => Self::UnhandledCommand,
+                    }
+                }
+
+                [Message::Binary(arg1), Message::Binary(numreplicas), Message::Binary(timeout)]
+                    if arg1.eq_ignore_ascii_case(b"WAIT") =>
+                {
+                    match (
+                        String::from_utf8_lossy(numreplicas).parse(),
+                        String::from_utf8_lossy(timeout).parse(),
+                    ) {
+                        (Ok(numreplicas), Ok(timeout)) => Self::Wait(numreplicas, timeout),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+
+                // Persistence
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"SAVE") => Self::Save,
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"BGSAVE") => Self::BgSave,
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"LASTSAVE") => Self::LastSave,
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"BGREWRITEAOF") => {
+                    Self::BgRewriteAof
+                }
+                [Message::Binary(arg1)] if arg1.eq_ignore_ascii_case(b"SHUTDOWN") => {
+                    Self::Shutdown(None)
+                }
+                [Message::Binary(arg1), Message::Binary(arg2)]
+                    if arg1.eq_ignore_ascii_case(b"SHUTDOWN") =>
+                {
+                    if arg2.eq_ignore_ascii_case(b"SAVE") {
+                        Self::Shutdown(Some(true))
+                    } else if arg2.eq_ignore_ascii_case(b"NOSAVE") {
+                        Self::Shutdown(Some(false))
+                    } else {
+                        Self::UnhandledCommand
+                    }
+                }
+
+                // Databases
+                [Message::Binary(arg1), Message::Binary(index)]
+                    if arg1.eq_ignore_ascii_case(b"SELECT") =>
+                {
+                    match parse_db_index(index) {
+                        Ok(index) => Self::Select(index),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(index1), Message::Binary(index2)]
+                    if arg1.eq_ignore_ascii_case(b"SWAPDB") =>
+                {
+                    match (parse_db_index(index1), parse_db_index(index2)) {
+                        (Ok(index1), Ok(index2)) => Self::SwapDb(index1, index2),
+                        (Err(e), _) | (_, Err(e)) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(index)]
+                    if arg1.eq_ignore_ascii_case(b"MOVE") =>
+                {
+                    match parse_db_index(index) {
+                        Ok(index) => Self::Move(RedisString::new(key), index),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                // Flush is always synchronous, mode argument is ignored
+                [Message::Binary(arg1), mode @ ..]
+                    if arg1.eq_ignore_ascii_case(b"FLUSHDB") && is_flush_mode(mode) =>
+                {
+                    Self::FlushDb
+                }
+                [Message::Binary(arg1), mode @ ..]
+                    if arg1.eq_ignore_ascii_case(b"FLUSHALL") && is_flush_mode(mode) =>
+                {
+                    Self::FlushAll
+                }
+
+                // Lists
+                [Message::Binary(arg1), Message::Binary(key), elements @ ..]
+                    if arg1.eq_ignore_ascii_case(b"LPUSH")
+                        || arg1.eq_ignore_ascii_case(b"RPUSH") =>
+                {
+                    match parse_strings(elements) {
+                        Some(elements) if !elements.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"LPUSH") {
+                                Self::LPush(RedisString::new(key), elements)
+                            } else {
+                                Self::RPush(RedisString::new(key), elements)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), count @ ..]
+                    if arg1.eq_ignore_ascii_case(b"LPOP") || arg1.eq_ignore_ascii_case(b"RPOP") =>
+                {
+                    let count = match count {
+                        [] => None,
+                        [Message::Binary(count)] => match parse_number(count) {
+                            Some(count) => Some(count),
+                            None => return Self::UnhandledCommand,
+                        },
+                        _ => return Self::UnhandledCommand,
+                    };
+                    if arg1.eq_ignore_ascii_case(b"LPOP") {
+                        Self::LPop(RedisString::new(key), count)
+                    } else {
+                        Self::RPop(RedisString::new(key), count)
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"LLEN") =>
+                {
+                    Self::LLen(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(start), Message::Binary(stop)]
+                    if arg1.eq_ignore_ascii_case(b"LRANGE")
+                        || arg1.eq_ignore_ascii_case(b"LTRIM") =>
+                {
+                    match (parse_number(start), parse_number(stop)) {
+                        (Some(start), Some(stop)) if arg1.eq_ignore_ascii_case(b"LRANGE") => {
+                            Self::LRange(RedisString::new(key), start, stop)
+                        }
+                        (Some(start), Some(stop)) => {
+                            Self::LTrim(RedisString::new(key), start, stop)
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(index)]
+                    if arg1.eq_ignore_ascii_case(b"LINDEX") =>
+                {
+                    match parse_number(index) {
+                        Some(index) => Self::LIndex(RedisString::new(key), index),
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(number), Message::Binary(element)]
+                    if arg1.eq_ignore_ascii_case(b"LSET") || arg1.eq_ignore_ascii_case(b"LREM") =>
+                {
+                    match parse_number(number) {
+                        Some(index) if arg1.eq_ignore_ascii_case(b"LSET") => {
+                            Self::LSet(RedisString::new(key), index, RedisString::new(element))
+                        }
+                        Some(count) => {
+                            Self::LRem(RedisString::new(key), count, RedisString::new(element))
+                        }
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(position), Message::Binary(pivot), Message::Binary(element)]
+                    if arg1.eq_ignore_ascii_case(b"LINSERT") =>
+                {
+                    let before = if position.eq_ignore_ascii_case(b"BEFORE") {
+                        true
+                    } else if position.eq_ignore_ascii_case(b"AFTER") {
+                        false
+                    } else {
+                        return Self::UnhandledCommand;
+                    };
+                    Self::LInsert(
+                        RedisString::new(key),
+                        before,
+                        RedisString::new(pivot),
+                        RedisString::new(element),
+                    )
+                }
+                [Message::Binary(arg1), Message::Binary(source), Message::Binary(destination), Message::Binary(from), Message::Binary(to)]
+                    if arg1.eq_ignore_ascii_case(b"LMOVE") =>
+                {
+                    match (ListEnd::parse(from), ListEnd::parse(to)) {
+                        (Some(from), Some(to)) => Self::LMove(
+                            RedisString::new(source),
+                            RedisString::new(destination),
+                            from,
+                            to,
+                        ),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), keys @ .., Message::Binary(timeout)]
+                    if arg1.eq_ignore_ascii_case(b"BLPOP")
+                        || arg1.eq_ignore_ascii_case(b"BRPOP") =>
+                {
+                    match (parse_strings(keys), parse_timeout(timeout)) {
+                        (Some(keys), Some(timeout)) if !keys.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"BLPOP") {
+                                Self::BLPop(keys, timeout)
+                            } else {
+                                Self::BRPop(keys, timeout)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(source), Message::Binary(destination), Message::Binary(from), Message::Binary(to), Message::Binary(timeout)]
+                    if arg1.eq_ignore_ascii_case(b"BLMOVE") =>
+                {
+                    match (
+                        ListEnd::parse(from),
+                        ListEnd::parse(to),
+                        parse_timeout(timeout),
+                    ) {
+                        (Some(from), Some(to), Some(timeout)) => Self::BLMove(
+                            RedisString::new(source),
+                            RedisString::new(destination),
+                            from,
+                            to,
+                            timeout,
+                        ),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), pairs @ ..]
+                    if arg1.eq_ignore_ascii_case(b"HSET") =>
+                {
+                    match parse_strings(pairs) {
+                        Some(pairs) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+                            let pairs = pairs
+                                .chunks(2)
+                                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                                .collect();
+                            Self::HSet(RedisString::new(key), pairs)
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(field), Message::Binary(value)]
+                    if arg1.eq_ignore_ascii_case(b"HSETNX") =>
+                {
+                    Self::HSetNx(
+                        RedisString::new(key),
+                        RedisString::new(field),
+                        RedisString::new(value),
+                    )
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(field)]
+                    if arg1.eq_ignore_ascii_case(b"HGET")
+                        || arg1.eq_ignore_ascii_case(b"HEXISTS")
+                        || arg1.eq_ignore_ascii_case(b"HSTRLEN") =>
+                {
+                    let (key, field) = (RedisString::new(key), RedisString::new(field));
+                    if arg1.eq_ignore_ascii_case(b"HGET") {
+                        Self::HGet(key, field)
+                    } else if arg1.eq_ignore_ascii_case(b"HEXISTS") {
+                        Self::HExists(key, field)
+                    } else {
+                        Self::HStrLen(key, field)
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), fields @ ..]
+                    if arg1.eq_ignore_ascii_case(b"HMGET")
+                        || arg1.eq_ignore_ascii_case(b"HDEL") =>
+                {
+                    match parse_strings(fields) {
+                        Some(fields) if !fields.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"HMGET") {
+                                Self::HMGet(RedisString::new(key), fields)
+                            } else {
+                                Self::HDel(RedisString::new(key), fields)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"HLEN") =>
+                {
+                    Self::HLen(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"HKEYS") =>
+                {
+                    Self::HKeys(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"HVALS") =>
+                {
+                    Self::HVals(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"HGETALL") =>
+                {
+                    Self::HGetAll(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(field), Message::Binary(increment)]
+                    if arg1.eq_ignore_ascii_case(b"HINCRBY") =>
+                {
+                    match parse_number(increment) {
+                        Some(increment) => {
+                            Self::HIncrBy(RedisString::new(key), RedisString::new(field), increment)
+                        }
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(field), Message::Binary(increment)]
+                    if arg1.eq_ignore_ascii_case(b"HINCRBYFLOAT") =>
+                {
+                    match parse_float(increment) {
+                        Some(_) => Self::HIncrByFloat(
+                            RedisString::new(key),
+                            RedisString::new(field),
+                            RedisString::new(increment),
+                        ),
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"HRANDFIELD") =>
+                {
+                    let key = RedisString::new(key);
+                    match options {
+                        [] => Self::HRandField(key, None, false),
+                        [Message::Binary(count)] => match parse_random_count(count) {
+                            Some(Ok(count)) => Self::HRandField(key, Some(count), false),
+                            Some(Err(e)) => Self::Invalid(e),
+                            None => Self::UnhandledCommand,
+                        },
+                        [Message::Binary(count), Message::Binary(with_values)]
+                            if with_values.eq_ignore_ascii_case(b"WITHVALUES") =>
+                        {
+                            match parse_random_count(count) {
+                                // Fields and values would not fit in a reply
+                                Some(Ok(count)) if count < -i64::MAX / 2 => {
+                                    Self::Invalid(MiniRedisError::ValueOutOfRange)
+                                }
+                                Some(Ok(count)) => Self::HRandField(key, Some(count), true),
+                                Some(Err(e)) => Self::Invalid(e),
+                                None => Self::UnhandledCommand,
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), members @ ..]
+                    if arg1.eq_ignore_ascii_case(b"SADD")
+                        || arg1.eq_ignore_ascii_case(b"SREM")
+                        || arg1.eq_ignore_ascii_case(b"SMISMEMBER") =>
+                {
+                    let key = RedisString::new(key);
+                    match parse_strings(members) {
+                        Some(members) if !members.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"SADD") {
+                                Self::SAdd(key, members)
+                            } else if arg1.eq_ignore_ascii_case(b"SREM") {
+                                Self::SRem(key, members)
+                            } else {
+                                Self::SMIsMember(key, members)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"SMEMBERS") =>
+                {
+                    Self::SMembers(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(member)]
+                    if arg1.eq_ignore_ascii_case(b"SISMEMBER") =>
+                {
+                    Self::SIsMember(RedisString::new(key), RedisString::new(member))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"SCARD") =>
+                {
+                    Self::SCard(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), count @ ..]
+                    if arg1.eq_ignore_ascii_case(b"SPOP")
+                        || arg1.eq_ignore_ascii_case(b"SRANDMEMBER") =>
+                {
+                    let key = RedisString::new(key);
+                    let pop = arg1.eq_ignore_ascii_case(b"SPOP");
+                    match count {
+                        [] if pop => Self::SPop(key, None),
+                        [] => Self::SRandMember(key, None),
+                        [Message::Binary(count)] if pop => match parse_number(count) {
+                            Some(count) => Self::SPop(key, Some(count)),
+                            None => Self::UnhandledCommand,
+                        },
+                        [Message::Binary(count)] => match parse_random_count(count) {
+                            Some(Ok(count)) => Self::SRandMember(key, Some(count)),
+                            Some(Err(e)) => Self::Invalid(e),
+                            None => Self::UnhandledCommand,
+                        },
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(source), Message::Binary(destination), Message::Binary(member)]
+                    if arg1.eq_ignore_ascii_case(b"SMOVE") =>
+                {
+                    Self::SMove(
+                        RedisString::new(source),
+                        RedisString::new(destination),
+                        RedisString::new(member),
+                    )
+                }
+                [Message::Binary(arg1), keys @ ..]
+                    if arg1.eq_ignore_ascii_case(b"SINTER")
+                        || arg1.eq_ignore_ascii_case(b"SUNION")
+                        || arg1.eq_ignore_ascii_case(b"SDIFF") =>
+                {
+                    match parse_strings(keys) {
+                        Some(keys) if !keys.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"SINTER") {
+                                Self::SInter(keys)
+                            } else if arg1.eq_ignore_ascii_case(b"SUNION") {
+                                Self::SUnion(keys)
+                            } else {
+                                Self::SDiff(keys)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(destination), keys @ ..]
+                    if arg1.eq_ignore_ascii_case(b"SINTERSTORE")
+                        || arg1.eq_ignore_ascii_case(b"SUNIONSTORE")
+                        || arg1.eq_ignore_ascii_case(b"SDIFFSTORE") =>
+                {
+                    let destination = RedisString::new(destination);
+                    match parse_strings(keys) {
+                        Some(keys) if !keys.is_empty() => {
+                            if arg1.eq_ignore_ascii_case(b"SINTERSTORE") {
+                                Self::SInterStore(destination, keys)
+                            } else if arg1.eq_ignore_ascii_case(b"SUNIONSTORE") {
+                                Self::SUnionStore(destination, keys)
+                            } else {
+                                Self::SDiffStore(destination, keys)
+                            }
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(numkeys), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"SINTERCARD") =>
+                {
+                    let Some(numkeys) = parse_number::<usize>(numkeys)
+                        .filter(|numkeys| *numkeys > 0 && *numkeys <= args.len())
+                    else {
+                        return Self::UnhandledCommand;
+                    };
+                    let (keys, options) = args.split_at(numkeys);
+                    let limit = match options {
+                        [] => Some(0),
+                        [Message::Binary(option), Message::Binary(limit)]
+                            if option.eq_ignore_ascii_case(b"LIMIT") =>
+                        {
+                            parse_number(limit)
+                        }
+                        _ => None,
+                    };
+                    match (parse_strings(keys), limit) {
+                        (Some(keys), Some(limit)) => Self::SInterCard(keys, limit),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"ZADD") =>
+                {
+                    parse_zadd(RedisString::new(key), args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(increment), Message::Binary(member)]
+                    if arg1.eq_ignore_ascii_case(b"ZINCRBY") =>
+                {
+                    match Score::parse(increment) {
+                        Some(increment) => Self::ZIncrBy(
+                            RedisString::new(key),
+                            increment,
+                            RedisString::new(member),
+                        ),
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), members @ ..]
+                    if arg1.eq_ignore_ascii_case(b"ZREM") =>
+                {
+                    match parse_strings(members) {
+                        Some(members) if !members.is_empty() => {
+                            Self::ZRem(RedisString::new(key), members)
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(member)]
+                    if arg1.eq_ignore_ascii_case(b"ZSCORE") =>
+                {
+                    Self::ZScore(RedisString::new(key), RedisString::new(member))
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"ZCARD") =>
+                {
+                    Self::ZCard(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(member), with_score @ ..]
+                    if arg1.eq_ignore_ascii_case(b"ZRANK")
+                        || arg1.eq_ignore_ascii_case(b"ZREVRANK") =>
+                {
+                    let with_score = match with_score {
+                        [] => false,
+                        [Message::Binary(option)] if option.eq_ignore_ascii_case(b"WITHSCORE") => {
+                            true
+                        }
+                        _ => return Self::UnhandledCommand,
+                    };
+                    let (key, member) = (RedisString::new(key), RedisString::new(member));
+                    if arg1.eq_ignore_ascii_case(b"ZRANK") {
+                        Self::ZRank(key, member, with_score)
+                    } else {
+                        Self::ZRevRank(key, member, with_score)
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(start), Message::Binary(stop), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"ZRANGE") =>
+                {
+                    match parse_zrange(start, stop, options) {
+                        Some((range, with_scores)) => {
+                            Self::ZRange(RedisString::new(key), range, with_scores)
+                        }
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(destination), Message::Binary(source), Message::Binary(start), Message::Binary(stop), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"ZRANGESTORE") =>
+                {
+                    match parse_zrange(start, stop, options) {
+                        Some((range, false)) => Self::ZRangeStore(
+                            RedisString::new(destination),
+                            RedisString::new(source),
+                            range,
+                        ),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(min), Message::Binary(max)]
+                    if arg1.eq_ignore_ascii_case(b"ZCOUNT") =>
+                {
+                    match (ScoreBound::parse(min), ScoreBound::parse(max)) {
+                        (Some(min), Some(max)) => Self::ZCount(RedisString::new(key), min, max),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), count @ ..]
+                    if arg1.eq_ignore_ascii_case(b"ZPOPMIN")
+                        || arg1.eq_ignore_ascii_case(b"ZPOPMAX") =>
+                {
+                    let count = match count {
+                        [] => None,
+                        [Message::Binary(count)] => match parse_number(count) {
+                            Some(count) => Some(count),
+                            None => return Self::UnhandledCommand,
+                        },
+                        _ => return Self::UnhandledCommand,
+                    };
+                    if arg1.eq_ignore_ascii_case(b"ZPOPMIN") {
+                        Self::ZPopMin(RedisString::new(key), count)
+                    } else {
+                        Self::ZPopMax(RedisString::new(key), count)
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"TYPE") =>
+                {
+                    Self::Type(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XADD") =>
+                {
+                    parse_xadd(RedisString::new(key), args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"XLEN") =>
+                {
+                    Self::XLen(RedisString::new(key))
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(first), Message::Binary(second), count @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XRANGE")
+                        || arg1.eq_ignore_ascii_case(b"XREVRANGE") =>
+                {
+                    let count = match count {
+                        [] => None,
+                        [Message::Binary(option), Message::Binary(count)]
+                            if option.eq_ignore_ascii_case(b"COUNT") =>
+                        {
+                            match parse_number(count) {
+                                Some(count) => Some(count),
+                                None => return Self::UnhandledCommand,
+                            }
+                        }
+                        _ => return Self::UnhandledCommand,
+                    };
+                    let key = RedisString::new(key);
+                    if arg1.eq_ignore_ascii_case(b"XRANGE") {
+                        match (StreamId::parse_start(first), StreamId::parse_end(second)) {
+                            (Some(start), Some(end)) => Self::XRange(key, start, end, count),
+                            _ => Self::UnhandledCommand,
+                        }
+                    } else {
+                        match (StreamId::parse_start(second), StreamId::parse_end(first)) {
+                            (Some(start), Some(end)) => Self::XRevRange(key, start, end, count),
+                            _ => Self::UnhandledCommand,
+                        }
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), ids @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XDEL") =>
+                {
+                    let ids: Option<Vec<_>> = parse_strings(ids).and_then(|ids| {
+                        ids.iter()
+                            .map(|id| StreamId::parse(id.as_slice(), 0))
+                            .collect()
+                    });
+                    match ids {
+                        Some(ids) if !ids.is_empty() => Self::XDel(RedisString::new(key), ids),
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XTRIM") =>
+                {
+                    match parse_stream_trim(args) {
+                        Ok((trim, [])) => Self::XTrim(RedisString::new(key), trim),
+                        Ok(_) => Self::Invalid(MiniRedisError::SyntaxError),
+                        Err(e) => Self::Invalid(e),
+                    }
+                }
+                [Message::Binary(arg1), args @ ..] if arg1.eq_ignore_ascii_case(b"XREAD") => {
+                    parse_xread(args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(last_id), options @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XSETID") =>
+                {
+                    match parse_xsetid(last_id, options) {
+                        Some((last_id, entries_added, max_deleted_id)) => Self::XSetId(
+                            RedisString::new(key),
+                            last_id,
+                            entries_added,
+                            max_deleted_id,
+                        ),
+                        None => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(subcommand), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XGROUP") =>
+                {
+                    parse_xgroup(subcommand, args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), args @ ..] if arg1.eq_ignore_ascii_case(b"XREADGROUP") => {
+                    parse_xreadgroup(args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(group), ids @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XACK") =>
+                {
+                    match parse_stream_ids(ids) {
+                        Some(ids) if !ids.is_empty() => {
+                            Self::XAck(RedisString::new(key), RedisString::new(group), ids)
+                        }
+                        _ => Self::UnhandledCommand,
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(key), Message::Binary(group), args @ ..]
+                    if arg1.eq_ignore_ascii_case(b"XPENDING") =>
+                {
+                    let range = match args {
+                        [] => None,
+                        args => match parse_pending_range(args) {
+                            Some(range) => Some(range),
+                            None => return Self::UnhandledCommand,
+                        },
+                    };
+                    Self::XPending(RedisString::new(key), RedisString::new(group), range)
+                }
+                [Message::Binary(arg1), args @ ..] if arg1.eq_ignore_ascii_case(b"XCLAIM") => {
+                    parse_xclaim(args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), args @ ..] if arg1.eq_ignore_ascii_case(b"XAUTOCLAIM") => {
+                    parse_xautoclaim(args).unwrap_or(Self::UnhandledCommand)
+                }
+                [Message::Binary(arg1), Message::Binary(subcommand), Message::Binary(key)]
+                    if arg1.eq_ignore_ascii_case(b"XINFO") =>
+                {
+                    if subcommand.eq_ignore_ascii_case(b"STREAM") {
+                        Self::XInfoStream(RedisString::new(key))
+                    } else if subcommand.eq_ignore_ascii_case(b"GROUPS") {
+                        Self::XInfoGroups(RedisString::new(key))
+                    } else {
+                        Self::UnhandledCommand
+                    }
+                }
+                [Message::Binary(arg1), Message::Binary(subcommand), Message::Binary(key), Message::Binary(group)]
+                    if arg1.eq_ignore_ascii_case(b"XINFO")
+                        && subcommand.eq_ignore_ascii_case(b"CONSUMERS") =>
+                {
+                    Self::XInfoConsumers(RedisString::new(key), RedisString::new(group))
+                }
 
                 // Unhandled command
                 _ => {
@@ -78,6 +1414,976 @@ impl Request {
                 eprintln!("Unhandled command: {msg:?}");
                 Self::UnhandledCommand
             }
+        }
+    }
+
+    /// Check if request modifies the database.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Self::Set(..)
+                | Self::Expire(..)
+                | Self::PExpireAt(..)
+                | Self::Persist(..)
+                | Self::SwapDb(..)
+                | Self::Move(..)
+                | Self::Append(..)
+                | Self::SetRange(..)
+                | Self::GetDel(..)
+                | Self::GetEx(_, None | Some(SetExpiry::In(_) | SetExpiry::At(_)))
+                | Self::MSet(..)
+                | Self::MSetNx(..)
+                | Self::SetNx(..)
+                | Self::IncrBy(..)
+                | Self::IncrByFloat(..)
+                | Self::SetBit(..)
+                | Self::BitOp(..)
+                | Self::BitField(..)
+                | Self::Del(..)
+                | Self::Unlink(..)
+                | Self::Rename(..)
+                | Self::RenameNx(..)
+                | Self::Copy(..)
+                | Self::FlushDb
+                | Self::FlushAll
+                | Self::LPush(..)
+                | Self::RPush(..)
+                | Self::LPop(..)
+                | Self::RPop(..)
+                | Self::LSet(..)
+                | Self::LRem(..)
+                | Self::LTrim(..)
+                | Self::LInsert(..)
+                | Self::LMove(..)
+                | Self::BLPop(..)
+                | Self::BRPop(..)
+                | Self::BLMove(..)
+                | Self::HSet(..)
+                | Self::HSetNx(..)
+                | Self::HDel(..)
+                | Self::HIncrBy(..)
+                | Self::HIncrByFloat(..)
+                | Self::SAdd(..)
+                | Self::SRem(..)
+                | Self::SPop(..)
+                | Self::SMove(..)
+                | Self::SInterStore(..)
+                | Self::SUnionStore(..)
+                | Self::SDiffStore(..)
+                | Self::ZAdd(..)
+                | Self::ZAddIncr(..)
+                | Self::ZIncrBy(..)
+                | Self::ZRem(..)
+                | Self::ZRangeStore(..)
+                | Self::ZPopMin(..)
+                | Self::ZPopMax(..)
+                | Self::XAdd(..)
+                | Self::XDel(..)
+                | Self::XTrim(..)
+                | Self::XSetId(..)
+                | Self::XGroupCreate(..)
+                | Self::XGroupDestroy(..)
+                | Self::XGroupSetId(..)
+                | Self::XGroupCreateConsumer(..)
+                | Self::XGroupDelConsumer(..)
+                | Self::XReadGroup(..)
+                | Self::XAck(..)
+                | Self::XClaim(..)
+                | Self::XAutoClaim(..)
+        )
+    }
+
+    /// Check if request may wait for other clients to add elements.
+    pub fn is_blocking(&self) -> bool {
+        matches!(
+            self,
+            Self::BLPop(..)
+                | Self::BRPop(..)
+                | Self::BLMove(..)
+                | Self::XRead(_, _, Some(_))
+                | Self::XReadGroup(_, _, _, _, Some(_), _)
+        )
+    }
+
+    /// Keys watched by a blocking request and its timeout in milliseconds.
+    pub fn blocking_keys(&self) -> Option<(Vec<RedisString>, u64)> {
+        match self {
+            Self::BLPop(keys, timeout) | Self::BRPop(keys, timeout) => {
+                Some((keys.clone(), *timeout))
+            }
+            Self::BLMove(source, _, _, _, timeout) => Some((vec![source.clone()], *timeout)),
+            Self::XRead(streams, _, Some(timeout))
+            | Self::XReadGroup(_, _, streams, _, Some(timeout), _) => Some((
+                streams.iter().map(|(key, _)| key.clone()).collect(),
+                *timeout,
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(input: &[u8]) -> Option<T> {
+    std::str::from_utf8(input).ok()?.parse().ok()
+}
+
+/// Parse the count of `HRANDFIELD` and `SRANDMEMBER`, which must have an opposite.
+fn parse_random_count(input: &[u8]) -> Option<Result<i64, MiniRedisError>> {
+    match parse_number(input)? {
+        i64::MIN => Some(Err(MiniRedisError::ValueOutOfRange)),
+        count => Some(Ok(count)),
+    }
+}
+
+/// Parse a database index, whose upper bound is checked when running the command.
+fn parse_db_index(input: &[u8]) -> Result<usize, MiniRedisError> {
+    let index = parse_integer(input).ok_or(MiniRedisError::NotInteger)?;
+    usize::try_from(index).map_err(|_| MiniRedisError::DbIndexOutOfRange)
+}
+
+/// Parse a timeout in seconds, possibly fractional, to milliseconds.
+fn parse_timeout(input: &[u8]) -> Option<u64> {
+    let seconds: f64 = parse_number(input)?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some((seconds * 1000.0) as u64)
+}
+
+/// Parse `SET` options, given in any order.
+fn parse_set_options(args: &[Message]) -> Result<SetOptions, MiniRedisError> {
+    let mut options = SetOptions::default();
+    let mut args = args;
+    while let [Message::Binary(option), rest @ ..] = args {
+        args = rest;
+        let condition = if option.eq_ignore_ascii_case(b"NX") {
+            Some(SetCondition::Missing)
+        } else if option.eq_ignore_ascii_case(b"XX") {
+            Some(SetCondition::Existing)
+        } else {
+            None
+        };
+        if condition.is_some() {
+            if options.condition.is_some() {
+                return Err(MiniRedisError::SyntaxError);
+            }
+            options.condition = condition;
+            continue;
+        }
+        if option.eq_ignore_ascii_case(b"GET") {
+            options.get = true;
+            continue;
+        }
+
+        if options.expiry.is_some() {
+            return Err(MiniRedisError::SyntaxError);
+        }
+        if option.eq_ignore_ascii_case(b"KEEPTTL") {
+            options.expiry = Some(SetExpiry::Keep);
+            continue;
+        }
+        let [Message::Binary(time), rest @ ..] = args else {
+            return Err(MiniRedisError::SyntaxError);
+        };
+        args = rest;
+        options.expiry = Some(parse_set_expiry(option, time, "set")?);
+    }
+    if !args.is_empty() {
+        return Err(MiniRedisError::SyntaxError);
+    }
+    Ok(options)
+}
+
+/// Parse destination database and `REPLACE` option of `COPY`.
+fn parse_copy_options(args: &[Message]) -> Result<(Option<usize>, bool), MiniRedisError> {
+    let (mut index, mut replace) = (None, false);
+    let mut args = args;
+    while let [Message::Binary(option), rest @ ..] = args {
+        args = rest;
+        if option.eq_ignore_ascii_case(b"REPLACE") {
+            replace = true;
+        } else if option.eq_ignore_ascii_case(b"DB") {
+            let [Message::Binary(value), rest @ ..] = args else {
+                return Err(MiniRedisError::SyntaxError);
+            };
+            args = rest;
+            index = Some(parse_number(value).ok_or(MiniRedisError::NotInteger)?);
+        } else {
+            return Err(MiniRedisError::SyntaxError);
+        }
+    }
+    if !args.is_empty() {
+        return Err(MiniRedisError::SyntaxError);
+    }
+    Ok((index, replace))
+}
+
+/// Parse cursor and options of `SCAN` commands, `TYPE` being only allowed when scanning keys.
+fn parse_scan(
+    cursor: &[u8],
+    args: &[Message],
+    keys: bool,
+) -> Result<(u64, ScanOptions), MiniRedisError> {
+    let cursor = parse_number(cursor).ok_or(MiniRedisError::InvalidCursor)?;
+
+    let mut options = ScanOptions::default();
+    let mut args = args;
+    while let [Message::Binary(option), Message::Binary(value), rest @ ..] = args {
+        args = rest;
+        if option.eq_ignore_ascii_case(b"MATCH") {
+            options.pattern = Some(RedisString::new(value));
+        } else if option.eq_ignore_ascii_case(b"COUNT") {
+            let count: i64 = parse_number(value).ok_or(MiniRedisError::NotInteger)?;
+            if count < 1 {
+                return Err(MiniRedisError::SyntaxError);
+            }
+            options.count = count as usize;
+        } else if keys && option.eq_ignore_ascii_case(b"TYPE") {
+            options.type_name = Some(String::from_utf8_lossy(value).to_string());
+        } else {
+            return Err(MiniRedisError::SyntaxError);
+        }
+    }
+    if !args.is_empty() {
+        return Err(MiniRedisError::SyntaxError);
+    }
+    Ok((cursor, options))
+}
+
+/// Parse `EXPIRE`, `PEXPIRE`, `EXPIREAT` or `PEXPIREAT` time and conditions.
+fn parse_expire(
+    command: &[u8],
+    key: RedisString,
+    time: &[u8],
+    args: &[Message],
+) -> Result<Request, MiniRedisError> {
+    let time: i64 = parse_number(time).ok_or(MiniRedisError::NotInteger)?;
+
+    let mut options = ExpireOptions::default();
+    for arg in args {
+        let Message::Binary(option) = arg else {
+            return Err(MiniRedisError::SyntaxError);
+        };
+        if option.eq_ignore_ascii_case(b"NX") {
+            options.nx = true;
+        } else if option.eq_ignore_ascii_case(b"XX") {
+            options.xx = true;
+        } else if option.eq_ignore_ascii_case(b"GT") {
+            options.gt = true;
+        } else if option.eq_ignore_ascii_case(b"LT") {
+            options.lt = true;
+        } else {
+            let option = String::from_utf8_lossy(option).to_string();
+            return Err(MiniRedisError::UnsupportedOption(option));
+        }
+    }
+    if options.nx && (options.xx || options.gt || options.lt) {
+        return Err(MiniRedisError::IncompatibleOptions("NX and XX, GT or LT"));
+    }
+    if options.gt && options.lt {
+        return Err(MiniRedisError::IncompatibleOptions("GT and LT"));
+    }
+
+    let in_seconds =
+        command.eq_ignore_ascii_case(b"EXPIRE") || command.eq_ignore_ascii_case(b"EXPIREAT");
+    let invalid =
+        || MiniRedisError::InvalidExpireTime(String::from_utf8_lossy(command).to_lowercase());
+    let millis = if in_seconds {
+        time.checked_mul(1000).ok_or_else(invalid)?
+    } else {
+        time
+    };
+
+    let relative =
+        command.eq_ignore_ascii_case(b"EXPIRE") || command.eq_ignore_ascii_case(b"PEXPIRE");
+    if relative {
+        Ok(Request::Expire(key, millis, options))
+    } else {
+        // Times before the epoch are past all the same
+        Ok(Request::PExpireAt(key, millis.max(0) as u64, options))
+    }
+}
+
+/// Parse `EX`, `PX`, `EXAT` or `PXAT` expiry of `SET` and similar commands.
+fn parse_set_expiry(
+    option: &[u8],
+    time: &[u8],
+    command: &str,
+) -> Result<SetExpiry, MiniRedisError> {
+    let in_seconds = option.eq_ignore_ascii_case(b"EX") || option.eq_ignore_ascii_case(b"EXAT");
+    let relative = option.eq_ignore_ascii_case(b"EX") || option.eq_ignore_ascii_case(b"PX");
+    if !in_seconds && !relative && !option.eq_ignore_ascii_case(b"PXAT") {
+        return Err(MiniRedisError::SyntaxError);
+    }
+    let time: i64 = parse_number(time).ok_or(MiniRedisError::NotInteger)?;
+    let invalid = || MiniRedisError::InvalidExpireTime(command.to_string());
+    if time <= 0 {
+        return Err(invalid());
+    }
+    let millis = if in_seconds {
+        time.checked_mul(1000).ok_or_else(invalid)?
+    } else {
+        time
+    } as u64;
+    Ok(if relative {
+        SetExpiry::In(millis)
+    } else {
+        SetExpiry::At(millis)
+    })
+}
+
+/// Parse expiry of `GETEX`: `None` to remove it, `SetExpiry::Keep` when not changed.
+fn parse_getex_expiry(args: &[Message]) -> Result<Option<SetExpiry>, MiniRedisError> {
+    match args {
+        [] => Ok(Some(SetExpiry::Keep)),
+        [Message::Binary(option)] if option.eq_ignore_ascii_case(b"PERSIST") => Ok(None),
+        [Message::Binary(option), Message::Binary(time)] => {
+            Ok(Some(parse_set_expiry(option, time, "getex")?))
+        }
+        _ => Err(MiniRedisError::SyntaxError),
+    }
+}
+
+/// Parse `LCS` options.
+fn parse_lcs_options(args: &[Message]) -> Result<LcsOptions, MiniRedisError> {
+    let mut options = LcsOptions::default();
+    let mut args = args;
+    while let [Message::Binary(option), rest @ ..] = args {
+        args = rest;
+        if option.eq_ignore_ascii_case(b"LEN") {
+            options.len = true;
+        } else if option.eq_ignore_ascii_case(b"IDX") {
+            options.idx = true;
+        } else if option.eq_ignore_ascii_case(b"WITHMATCHLEN") {
+            options.with_match_len = true;
+        } else if option.eq_ignore_ascii_case(b"MINMATCHLEN") {
+            let [Message::Binary(value), rest @ ..] = args else {
+                return Err(MiniRedisError::SyntaxError);
+            };
+            args = rest;
+            let min_match_len: i64 = parse_number(value).ok_or(MiniRedisError::NotInteger)?;
+            options.min_match_len = min_match_len.max(0) as usize;
+        } else {
+            return Err(MiniRedisError::SyntaxError);
+        }
+    }
+    if !args.is_empty() {
+        return Err(MiniRedisError::SyntaxError);
+    }
+    if options.len && options.idx {
+        return Err(MiniRedisError::LcsLenAndIdx);
+    }
+    Ok(options)
+}
+
+/// Parse a bit offset, `#N` meaning N times the width of a `BITFIELD` field.
+fn parse_bit_offset(input: &[u8], width: Option<u32>) -> Result<usize, MiniRedisError> {
+    let offset = match (input, width) {
+        ([b'#', index @ ..], Some(width)) => {
+            parse_integer(index).and_then(|index| index.checked_mul(i64::from(width)))
+        }
+        _ => parse_integer(input),
+    };
+    match offset {
+        Some(offset) if (0..MAX_BIT_OFFSET as i64).contains(&offset) => Ok(offset as usize),
+        _ => Err(MiniRedisError::InvalidBitOffset),
+    }
+}
+
+/// Parse the range of `BITCOUNT` and `BITPOS`, with its optional `BYTE` or `BIT` unit.
+fn parse_bit_range(
+    start: &[u8],
+    end: &[u8],
+    unit: &[Message],
+) -> Result<(i64, i64, BitUnit), MiniRedisError> {
+    let (Some(start), Some(end)) = (parse_integer(start), parse_integer(end)) else {
+        return Err(MiniRedisError::NotInteger);
+    };
+    match unit {
+        [] => Ok((start, end, BitUnit::Byte)),
+        [Message::Binary(unit)] if unit.eq_ignore_ascii_case(b"BYTE") => {
+            Ok((start, end, BitUnit::Byte))
+        }
+        [Message::Binary(unit)] if unit.eq_ignore_ascii_case(b"BIT") => {
+            Ok((start, end, BitUnit::Bit))
+        }
+        _ => Err(MiniRedisError::SyntaxError),
+    }
+}
+
+/// Parse a `BITFIELD` type such as `i16` or `u8`, up to `i64` and `u63`.
+fn parse_bitfield_type(input: &[u8]) -> Result<BitFieldType, MiniRedisError> {
+    let (signed, bits) = match input {
+        [b'i' | b'I', bits @ ..] => (true, bits),
+        [b'u' | b'U', bits @ ..] => (false, bits),
+        _ => return Err(MiniRedisError::InvalidBitFieldType),
+    };
+    let max_bits = if signed { 64 } else { 63 };
+    match parse_number::<u32>(bits) {
+        Some(bits) if (1..=max_bits).contains(&bits) => Ok(BitFieldType { signed, bits }),
+        _ => Err(MiniRedisError::InvalidBitFieldType),
+    }
+}
+
+/// Parse `BITFIELD` subcommands, only allowing reads for `BITFIELD_RO`.
+fn parse_bitfield(args: &[Message], read_only: bool) -> Result<Vec<BitFieldOp>, MiniRedisError> {
+    let mut ops = Vec::new();
+    let mut args = args;
+    while let [Message::Binary(subcommand), rest @ ..] = args {
+        if subcommand.eq_ignore_ascii_case(b"OVERFLOW") {
+            let [Message::Binary(behaviour), rest @ ..] = rest else {
+                return Err(MiniRedisError::SyntaxError);
+            };
+            args = rest;
+            let overflow = if behaviour.eq_ignore_ascii_case(b"WRAP") {
+                BitOverflow::Wrap
+            } else if behaviour.eq_ignore_ascii_case(b"SAT") {
+                BitOverflow::Sat
+            } else if behaviour.eq_ignore_ascii_case(b"FAIL") {
+                BitOverflow::Fail
+            } else {
+                return Err(MiniRedisError::InvalidOverflowType);
+            };
+            ops.push(BitFieldOp::Overflow(overflow));
+            continue;
+        }
+
+        let is_get = subcommand.eq_ignore_ascii_case(b"GET");
+        let is_write =
+            subcommand.eq_ignore_ascii_case(b"SET") || subcommand.eq_ignore_ascii_case(b"INCRBY");
+        let (field, offset, rest) = match rest {
+            [Message::Binary(field), Message::Binary(offset), rest @ ..] if is_get || is_write => {
+                (field, offset, rest)
+            }
+            _ => return Err(MiniRedisError::SyntaxError),
+        };
+        let field = parse_bitfield_type(field)?;
+        let offset = parse_bit_offset(offset, Some(field.bits))?;
+        if is_get {
+            args = rest;
+            ops.push(BitFieldOp::Get(field, offset));
+            continue;
+        }
+
+        let [Message::Binary(value), rest @ ..] = rest else {
+            return Err(MiniRedisError::SyntaxError);
+        };
+        args = rest;
+        let value = parse_integer(value).ok_or(MiniRedisError::NotInteger)?;
+        if read_only {
+            return Err(MiniRedisError::BitFieldReadOnly);
+        }
+        if subcommand.eq_ignore_ascii_case(b"SET") {
+            ops.push(BitFieldOp::Set(field, offset, value));
+        } else {
+            ops.push(BitFieldOp::IncrBy(field, offset, value));
+        }
+    }
+    if !args.is_empty() {
+        return Err(MiniRedisError::SyntaxError);
+    }
+    Ok(ops)
+}
+
+/// Parse `ZADD` options followed by scores and members.
+fn parse_zadd(key: RedisString, args: &[Message]) -> Option<Request> {
+    let mut options = ZAddOptions::default();
+    let (mut changed, mut increment) = (false, false);
+    let mut args = args;
+    while let [Message::Binary(option), rest @ ..] = args {
+        if option.eq_ignore_ascii_case(b"NX") {
+            options.nx = true;
+        } else if option.eq_ignore_ascii_case(b"XX") {
+            options.xx = true;
+        } else if option.eq_ignore_ascii_case(b"GT") {
+            options.gt = true;
+        } else if option.eq_ignore_ascii_case(b"LT") {
+            options.lt = true;
+        } else if option.eq_ignore_ascii_case(b"CH") {
+            changed = true;
+        } else if option.eq_ignore_ascii_case(b"INCR") {
+            increment = true;
+        } else {
+            break;
+        }
+        args = rest;
+    }
+    if (options.nx && (options.xx || options.gt || options.lt)) || (options.gt && options.lt) {
+        return None;
+    }
+
+    let args = parse_strings(args)?;
+    if args.is_empty() || args.len() % 2 != 0 {
+        return None;
+    }
+    let mut members = Vec::with_capacity(args.len() / 2);
+    for pair in args.chunks(2) {
+        members.push((Score::parse(pair[0].as_slice())?, pair[1].clone()));
+    }
+
+    if !increment {
+        return Some(Request::ZAdd(key, options, changed, members));
+    }
+    // Increment supports a single score and member
+    let [(score, member)] = <[_; 1]>::try_from(members).ok()?;
+    Some(Request::ZAddIncr(key, options, score, member))
+}
+
+/// Parse range and options of `ZRANGE`, returning whether scores are requested.
+fn parse_zrange(start: &[u8], stop: &[u8], options: &[Message]) -> Option<(ZRange, bool)> {
+    let (mut by_score, mut by_lex, mut rev, mut with_scores) = (false, false, false, false);
+    let mut limit = None;
+    let mut options = options;
+    while let [Message::Binary(option), rest @ ..] = options {
+        options = rest;
+        if option.eq_ignore_ascii_case(b"BYSCORE") {
+            by_score = true;
+        } else if option.eq_ignore_ascii_case(b"BYLEX") {
+            by_lex = true;
+        } else if option.eq_ignore_ascii_case(b"REV") {
+            rev = true;
+        } else if option.eq_ignore_ascii_case(b"WITHSCORES") {
+            with_scores = true;
+        } else if option.eq_ignore_ascii_case(b"LIMIT") {
+            let [Message::Binary(offset), Message::Binary(count), rest @ ..] = options else {
+                return None;
+            };
+            limit = Some((parse_number(offset)?, parse_number(count)?));
+            options = rest;
+        } else {
+            return None;
+        }
+    }
+    if !options.is_empty() || (by_score && by_lex) || (limit.is_some() && !by_score && !by_lex) {
+        return None;
+    }
+
+    // Reversed score and lexicographical ranges start from maximum
+    let (min, max) = if rev { (stop, start) } else { (start, stop) };
+    let by = if by_score {
+        ZRangeBy::Score(ScoreBound::parse(min)?, ScoreBound::parse(max)?)
+    } else if by_lex {
+        ZRangeBy::Lex(LexBound::parse(min)?, LexBound::parse(max)?)
+    } else {
+        ZRangeBy::Rank(parse_number(start)?, parse_number(stop)?)
+    };
+    Some((ZRange { by, rev, limit }, with_scores))
+}
+
+/// Parse `XADD` options followed by ID, fields and values.
+fn parse_xadd(key: RedisString, args: &[Message]) -> Option<Request> {
+    let mut options = XAddOptions::default();
+    let mut args = args;
+    loop {
+        match args {
+            [Message::Binary(option), rest @ ..] if option.eq_ignore_ascii_case(b"NOMKSTREAM") => {
+                options.nomkstream = true;
+                args = rest;
+            }
+            [Message::Binary(option), ..]
+                if option.eq_ignore_ascii_case(b"MAXLEN")
+                    || option.eq_ignore_ascii_case(b"MINID") =>
+            {
+                let (trim, rest) = match parse_stream_trim(args) {
+                    Ok(parsed) => parsed,
+                    Err(e) => return Some(Request::Invalid(e)),
+                };
+                options.trim = Some(trim);
+                args = rest;
+            }
+            _ => break,
+        }
+    }
+
+    let [Message::Binary(id), pairs @ ..] = args else {
+        return None;
+    };
+    let pairs = parse_strings(pairs)?;
+    if pairs.is_empty() || pairs.len() % 2 != 0 {
+        return None;
+    }
+    let fields = pairs
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    Some(Request::XAdd(key, options, XAddId::parse(id)?, fields))
+}
+
+/// Parse `MAXLEN` or `MINID` trimming with its `LIMIT`, returning remaining arguments.
+fn parse_stream_trim(args: &[Message]) -> Result<(StreamTrimOptions, &[Message]), MiniRedisError> {
+    let [Message::Binary(strategy), rest @ ..] = args else {
+        return Err(MiniRedisError::SyntaxError);
+    };
+    let (approximate, rest) = match rest {
+        [Message::Binary(operator), rest @ ..] if operator == b"=" => (false, rest),
+        [Message::Binary(operator), rest @ ..] if operator == b"~" => (true, rest),
+        rest => (false, rest),
+    };
+    let [Message::Binary(threshold), rest @ ..] = rest else {
+        return Err(MiniRedisError::SyntaxError);
+    };
+
+    let trim = if strategy.eq_ignore_ascii_case(b"MAXLEN") {
+        StreamTrim::MaxLen(parse_number(threshold).ok_or(MiniRedisError::NotInteger)?)
+    } else if strategy.eq_ignore_ascii_case(b"MINID") {
+        StreamTrim::MinId(StreamId::parse(threshold, 0).ok_or(MiniRedisError::InvalidStreamId)?)
+    } else {
+        return Err(MiniRedisError::SyntaxError);
+    };
+    // Like Redis, approximate trimming removes at most 100 nodes by default
+    let (limit, rest) = match rest {
+        [Message::Binary(option), Message::Binary(limit), rest @ ..]
+            if option.eq_ignore_ascii_case(b"LIMIT") =>
+        {
+            if !approximate {
+                return Err(MiniRedisError::StreamLimitWithoutApprox);
+            }
+            (parse_number(limit).ok_or(MiniRedisError::NotInteger)?, rest)
+        }
+        rest if approximate => (100 * STREAM_NODE_MAX_ENTRIES, rest),
+        rest => (0, rest),
+    };
+    let options = StreamTrimOptions {
+        trim,
+        approximate,
+        limit,
+    };
+    Ok((options, rest))
+}
+
+/// Parse `XREAD` options followed by keys and IDs.
+fn parse_xread(args: &[Message]) -> Option<Request> {
+    let (mut count, mut block) = (None, None);
+    let mut args = args;
+    let streams = loop {
+        let [Message::Binary(option), rest @ ..] = args else {
+            return None;
+        };
+        if option.eq_ignore_ascii_case(b"STREAMS") {
+            break parse_strings(rest)?;
+        }
+        let [Message::Binary(value), rest @ ..] = rest else {
+            return None;
+        };
+        if option.eq_ignore_ascii_case(b"COUNT") {
+            count = Some(parse_number(value)?);
+        } else if option.eq_ignore_ascii_case(b"BLOCK") {
+            block = Some(parse_number(value)?);
+        } else {
+            return None;
+        }
+        args = rest;
+    };
+
+    let streams = parse_stream_keys(&streams, b"$")?;
+    Some(Request::XRead(streams, count, block))
+}
+
+/// Split keys from their IDs, `special` ID being parsed as `None`.
+fn parse_stream_keys(
+    streams: &[RedisString],
+    special: &[u8],
+) -> Option<Vec<(RedisString, Option<StreamId>)>> {
+    if streams.is_empty() || streams.len() % 2 == 1 {
+        return None;
+    }
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+    let mut output = Vec::with_capacity(keys.len());
+    for (key, id) in keys.iter().zip(ids) {
+        let id = match id.as_slice() {
+            id if id == special => None,
+            id => Some(StreamId::parse(id, 0)?),
+        };
+        output.push((key.clone(), id));
+    }
+    Some(output)
+}
+
+fn parse_stream_ids(args: &[Message]) -> Option<Vec<StreamId>> {
+    args.iter()
+        .map(|arg| match arg {
+            Message::Binary(id) => StreamId::parse(id, 0),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse an `ENTRIESREAD` count, where `-1` stands for an unknown count.
+fn parse_entries_read(input: &[u8]) -> Option<Option<u64>> {
+    match parse_number::<i64>(input)? {
+        -1 => Some(None),
+        count => Some(Some(u64::try_from(count).ok()?)),
+    }
+}
+
+/// Parse `XGROUP` subcommand and its arguments.
+fn parse_xgroup(subcommand: &[u8], args: &[Message]) -> Option<Request> {
+    let args = parse_strings(args)?;
+    let group_id = |id: &RedisString| match id.as_slice() {
+        b"$" => Some(None),
+        id => StreamId::parse(id, 0).map(Some),
+    };
+
+    match args.as_slice() {
+        [key, group, id, options @ ..]
+            if subcommand.eq_ignore_ascii_case(b"CREATE")
+                || subcommand.eq_ignore_ascii_case(b"SETID") =>
+        {
+            let create = subcommand.eq_ignore_ascii_case(b"CREATE");
+            let (mut mkstream, mut entries_read) = (false, None);
+            let mut options = options;
+            loop {
+                match options {
+                    [] => break,
+                    [option, rest @ ..]
+                        if create && option.as_slice().eq_ignore_ascii_case(b"MKSTREAM") =>
+                    {
+                        mkstream = true;
+                        options = rest;
+                    }
+                    [option, count, rest @ ..]
+                        if option.as_slice().eq_ignore_ascii_case(b"ENTRIESREAD") =>
+                    {
+                        entries_read = parse_entries_read(count.as_slice())?;
+                        options = rest;
+                    }
+                    _ => return None,
+                }
+            }
+
+            let (key, group, id) = (key.clone(), group.clone(), group_id(id)?);
+            if create {
+                Some(Request::XGroupCreate(
+                    key,
+                    group,
+                    id,
+                    mkstream,
+                    entries_read,
+                ))
+            } else {
+                Some(Request::XGroupSetId(key, group, id, entries_read))
+            }
+        }
+        [key, group] if subcommand.eq_ignore_ascii_case(b"DESTROY") => {
+            Some(Request::XGroupDestroy(key.clone(), group.clone()))
+        }
+        [key, group, consumer] if subcommand.eq_ignore_ascii_case(b"CREATECONSUMER") => Some(
+            Request::XGroupCreateConsumer(key.clone(), group.clone(), consumer.clone()),
+        ),
+        [key, group, consumer] if subcommand.eq_ignore_ascii_case(b"DELCONSUMER") => Some(
+            Request::XGroupDelConsumer(key.clone(), group.clone(), consumer.clone()),
+        ),
+        _ => None,
+    }
+}
+
+/// Parse `XREADGROUP` group, consumer and options followed by keys and IDs.
+fn parse_xreadgroup(args: &[Message]) -> Option<Request> {
+    let [Message::Binary(option), Message::Binary(group), Message::Binary(consumer), args @ ..] =
+        args
+    else {
+        return None;
+    };
+    let mut args = args;
+    if !option.eq_ignore_ascii_case(b"GROUP") {
+        return None;
+    }
+
+    let (mut count, mut block, mut noack) = (None, None, false);
+    let streams = loop {
+        let [Message::Binary(option), rest @ ..] = args else {
+            return None;
+        };
+        if option.eq_ignore_ascii_case(b"STREAMS") {
+            break parse_strings(rest)?;
+        }
+        if option.eq_ignore_ascii_case(b"NOACK") {
+            noack = true;
+            args = rest;
+            continue;
+        }
+        let [Message::Binary(value), rest @ ..] = rest else {
+            return None;
+        };
+        if option.eq_ignore_ascii_case(b"COUNT") {
+            count = Some(parse_number(value)?);
+        } else if option.eq_ignore_ascii_case(b"BLOCK") {
+            block = Some(parse_number(value)?);
+        } else {
+            return None;
+        }
+        args = rest;
+    };
+
+    let streams = parse_stream_keys(&streams, b">")?;
+    Some(Request::XReadGroup(
+        RedisString::new(group),
+        RedisString::new(consumer),
+        streams,
+        count,
+        block,
+        noack,
+    ))
+}
+
+/// Parse the extended form of `XPENDING`.
+fn parse_pending_range(args: &[Message]) -> Option<PendingRange> {
+    let args = parse_strings(args)?;
+    let (min_idle, args) = match args.as_slice() {
+        [option, min_idle, rest @ ..] if option.as_slice().eq_ignore_ascii_case(b"IDLE") => {
+            (Some(parse_number(min_idle.as_slice())?), rest)
+        }
+        args => (None, args),
+    };
+    let (start, end, count, consumer) = match args {
+        [start, end, count] => (start, end, count, None),
+        [start, end, count, consumer] => (start, end, count, Some(consumer.clone())),
+        _ => return None,
+    };
+    Some(PendingRange {
+        min_idle,
+        start: StreamId::parse_start(start.as_slice())?,
+        end: StreamId::parse_end(end.as_slice())?,
+        count: parse_number(count.as_slice())?,
+        consumer,
+    })
+}
+
+/// Parse `XCLAIM` arguments, IDs ending at the first option.
+fn parse_xclaim(args: &[Message]) -> Option<Request> {
+    let args = parse_strings(args)?;
+    let [key, group, consumer, min_idle, rest @ ..] = args.as_slice() else {
+        return None;
+    };
+    let id_count = rest
+        .iter()
+        .take_while(|id| StreamId::parse(id.as_slice(), 0).is_some())
+        .count();
+    let (ids, mut options) = rest.split_at(id_count);
+    if ids.is_empty() {
+        return None;
+    }
+    let ids = ids
+        .iter()
+        .map(|id| StreamId::parse(id.as_slice(), 0))
+        .collect::<Option<_>>()?;
+
+    let mut claim = XClaimOptions::default();
+    loop {
+        match options {
+            [] => break,
+            [option, rest @ ..] if option.as_slice().eq_ignore_ascii_case(b"FORCE") => {
+                claim.force = true;
+                options = rest;
+            }
+            [option, rest @ ..] if option.as_slice().eq_ignore_ascii_case(b"JUSTID") => {
+                claim.justid = true;
+                options = rest;
+            }
+            [option, value, rest @ ..] => {
+                let option = option.as_slice();
+                let value = value.as_slice();
+                if option.eq_ignore_ascii_case(b"IDLE") {
+                    claim.idle = Some(parse_number(value)?);
+                } else if option.eq_ignore_ascii_case(b"TIME") {
+                    claim.time = Some(parse_number(value)?);
+                } else if option.eq_ignore_ascii_case(b"RETRYCOUNT") {
+                    claim.retry_count = Some(parse_number(value)?);
+                } else if option.eq_ignore_ascii_case(b"LASTID") {
+                    claim.last_id = Some(StreamId::parse(value, 0)?);
+                } else {
+                    return None;
+                }
+                options = rest;
+            }
+            _ => return None,
+        }
+    }
+
+    Some(Request::XClaim(
+        key.clone(),
+        group.clone(),
+        consumer.clone(),
+        parse_number(min_idle.as_slice())?,
+        ids,
+        claim,
+    ))
+}
+
+/// Parse `XAUTOCLAIM` arguments, returning 100 entries by default.
+fn parse_xautoclaim(args: &[Message]) -> Option<Request> {
+    let args = parse_strings(args)?;
+    let [key, group, consumer, min_idle, start, options @ ..] = args.as_slice() else {
+        return None;
+    };
+    let (mut count, mut justid) = (100, false);
+    let mut options = options;
+    loop {
+        match options {
+            [] => break,
+            [option, rest @ ..] if option.as_slice().eq_ignore_ascii_case(b"JUSTID") => {
+                justid = true;
+                options = rest;
+            }
+            [option, value, rest @ ..] if option.as_slice().eq_ignore_ascii_case(b"COUNT") => {
+                count = parse_number(value.as_slice())?;
+                options = rest;
+            }
+            _ => return None,
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+
+    Some(Request::XAutoClaim(
+        key.clone(),
+        group.clone(),
+        consumer.clone(),
+        parse_number(min_idle.as_slice())?,
+        StreamId::parse_start(start.as_slice())?,
+        count,
+        justid,
+    ))
+}
+
+/// Parse `XSETID` last ID and its options.
+fn parse_xsetid(
+    last_id: &[u8],
+    options: &[Message],
+) -> Option<(StreamId, Option<u64>, Option<StreamId>)> {
+    let last_id = StreamId::parse(last_id, 0)?;
+    let (mut entries_added, mut max_deleted_id) = (None, None);
+    let mut options = options;
+    while let [Message::Binary(option), Message::Binary(value), rest @ ..] = options {
+        if option.eq_ignore_ascii_case(b"ENTRIESADDED") {
+            entries_added = Some(parse_number(value)?);
+        } else if option.eq_ignore_ascii_case(b"MAXDELETEDID") {
+            max_deleted_id = Some(StreamId::parse(value, 0)?);
+        } else {
+            return None;
+        }
+        options = rest;
+    }
+    options
+        .is_empty()
+        .then_some((last_id, entries_added, max_deleted_id))
+}
+
+fn parse_strings(args: &[Message]) -> Option<Vec<RedisString>> {
+    args.iter()
+        .map(|arg| match arg {
+            Message::Binary(arg) => Some(RedisString::new(arg)),
+            _ => None,
         })
+        .collect()
+}
+
+fn is_flush_mode(args: &[Message]) -> bool {
+    match args {
+        [] => true,
+        [Message::Binary(mode)] => {
+            mode.eq_ignore_ascii_case(b"ASYNC") || mode.eq_ignore_ascii_case(b"SYNC")
+        }
+        _ => false,
     }
 }
@@ -12,6 +12,7 @@ pub enum Message {
     Binary(Vec<u8>),
     Null,
     Array(Vec<Message>),
+    NullArray,
 }
 
 impl Message {
@@ -27,6 +28,20 @@ impl Message {
         Self::Binary(content.to_vec())
     }
 
+    /// Build a command as an array of binary arguments.
+    pub fn command(args: &[&[u8]]) -> Self {
+        Self::Array(args.iter().map(|arg| Self::bin(arg)).collect())
+    }
+
+    /// Encode message in memory.
+    pub async fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write(&mut output)
+            .await
+            .expect("Fail to write in memory message");
+        output
+    }
+
     /// Read reader and decode its content.
     ///
     /// Async recursive function are not supported out of the box.
@@ -86,7 +101,7 @@ impl Message {
 
                     // Handle null array
                     if elem_count < 0 {
-                        return Ok(Message::Null);
+                        return Ok(Message::NullArray);
                     }
 
                     // Parse each elements
@@ -141,6 +156,9 @@ impl Message {
                         item.write(writer).await?;
                     }
                 }
+                Message::NullArray => {
+                    writer.write_all(b"*-1\r\n").await?;
+                }
             };
 
             writer.flush().await?;
@@ -149,6 +167,38 @@ impl Message {
     }
 }
 
+/// Read a binary payload which is not followed by CRLF.
+///
+/// This is how RDB snapshots are transferred during replication.
+pub async fn read_unterminated_binary<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Vec<u8>, MiniRedisError> {
+    let msg_type = reader.read_u8().await?;
+    if msg_type != b'$' {
+        return Err(MiniRedisError::InvalidMessageType(msg_type.into()));
+    }
+
+    let data_len_raw = read_until_crlf(reader).await?;
+    let data_len_text = String::from_utf8(data_len_raw)?;
+    let data_len: usize = data_len_text.parse()?;
+
+    let mut data = vec![0_u8; data_len];
+    reader.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+/// Write a binary payload without trailing CRLF.
+pub async fn write_unterminated_binary<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    data: &[u8],
+) -> io::Result<()> {
+    writer
+        .write_all(format!("${}\r\n", data.len()).as_bytes())
+        .await?;
+    writer.write_all(data).await?;
+    writer.flush().await
+}
+
 async fn read_until_crlf<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<u8>, MiniRedisError> {
     let mut output = Vec::with_capacity(128);
 
@@ -2,7 +2,7 @@ use std::io;
 
 use tokio::io::AsyncWrite;
 
-use crate::{rdb::RedisString, resp2::Message, ServerMode};
+use crate::{error::MiniRedisError, rdb::RedisString, resp2::Message, ServerMode};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Response {
@@ -18,10 +18,22 @@ pub enum Response {
         repl_backlog_first_byte_offset: usize,
         repl_backlog_histlen: usize,
     },
+    InfoStats {
+        expired_keys: u64,
+    },
+    /// Several `INFO` sections, separated by an empty line.
+    InfoSections(Vec<Response>),
+    FullResync(String, usize),
+    Continue(String),
     // Get & Set response
     Ok,
+    Status(String),
+    Integer(i64),
     NoContent,
     Content(RedisString),
+    Array(Vec<Response>),
+    /// Nil reply of commands returning arrays, such as a `BLPOP` timeout.
+    NullArray,
     // Key matches
     KeyMatches(Vec<RedisString>),
     // Config get
@@ -32,7 +44,11 @@ pub enum Response {
 
 impl Response {
     pub async fn write<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
-        let msg = match self {
+        self.to_message().write(writer).await
+    }
+
+    fn to_message(&self) -> Message {
+        match self {
             Response::Pong => Message::text("PONG"),
             Response::Echo(data) => Message::bin(data.as_slice()),
             Response::InfoReplication {
@@ -61,9 +77,33 @@ impl Response {
                 );
                 Message::bin(data.as_bytes())
             }
+            Response::InfoStats { expired_keys } => {
+                let data = format!("expired_keys:{expired_keys}\n");
+                Message::bin(data.as_bytes())
+            }
+            Response::InfoSections(sections) => {
+                let mut data = Vec::new();
+                for section in sections {
+                    if !data.is_empty() {
+                        data.push(b'\n');
+                    }
+                    if let Message::Binary(section) = section.to_message() {
+                        data.extend(section);
+                    }
+                }
+                Message::Binary(data)
+            }
+            Response::FullResync(replid, offset) => {
+                Message::Text(format!("FULLRESYNC {replid} {offset}"))
+            }
+            Response::Continue(replid) => Message::Text(format!("CONTINUE {replid}")),
             Response::Ok => Message::text("OK"),
+            Response::Status(text) => Message::text(text),
+            Response::Integer(value) => Message::Integer(*value),
             Response::NoContent => Message::Null,
             Response::Content(data) => Message::bin(data.as_slice()),
+            Response::Array(items) => Message::Array(items.iter().map(Self::to_message).collect()),
+            Response::NullArray => Message::NullArray,
             Response::KeyMatches(keys) => Message::Array(
                 keys.iter()
                     .map(|key| Message::bin(key.as_slice()))
@@ -74,8 +114,18 @@ impl Response {
                 Message::bin(value.as_slice()),
             ]),
             Response::Error(msg) => Message::error(msg),
-        };
+        }
+    }
+}
 
-        msg.write(writer).await
+impl From<MiniRedisError> for Response {
+    fn from(err: MiniRedisError) -> Self {
+        match err {
+            // Error already has its own prefix
+            MiniRedisError::WrongType
+            | MiniRedisError::BusyGroup
+            | MiniRedisError::NoGroup { .. } => Self::Error(err.to_string()),
+            err => Self::Error(format!("ERR {err}")),
+        }
     }
 }
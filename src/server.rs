@@ -0,0 +1,1795 @@
+use std::{
+    ffi::OsStr,
+    future::{self, Future},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    fs,
+    sync::{Mutex, Notify},
+    time::{self, Instant},
+};
+
+use crate::{
+    aof::{self, Aof, FsyncPolicy},
+    database::{
+        self, Database, ExpireOptions, GroupEntry, KeyWaiter, LcsMatch, LcsOptions, ListEnd,
+        SetCondition, SetExpiry, SetOperation, SetOptions, StreamEntry, StreamId, StreamTrim,
+        StreamTrimOptions, XAddOptions, XClaimOptions, ZAddOptions, ZRangeBy,
+    },
+    error::MiniRedisError,
+    persistence::{Persistence, SavePoint},
+    rdb::{Rdb, RedisString},
+    replication::{ReplicaLink, ReplicaSync, Replication},
+    request::Request,
+    resp2::Message,
+    response::Response,
+    ServerMode,
+};
+
+pub const DEFAULT_DATABASES: usize = 16;
+
+/// Keys with an expiry sampled at each step of the active expiration cycle.
+const ACTIVE_EXPIRE_SAMPLE: usize = 20;
+
+/// Shared state of a running server.
+#[derive(Debug)]
+pub struct Server {
+    pub databases: Vec<Database>,
+    pub config: Database,
+    pub replication: Replication,
+    pub persistence: Persistence,
+    pub aof: Aof,
+    /// Held while a write is applied and propagated, so that AOF and replicas receive writes
+    /// in the order they were applied and no snapshot is taken in between.
+    write_order: Mutex<()>,
+    shutdown: Notify,
+}
+
+impl Server {
+    pub fn new(role: ServerMode, databases: usize) -> Self {
+        Self {
+            databases: (0..databases).map(|_| Database::new()).collect(),
+            config: Database::new(),
+            replication: Replication::new(role),
+            persistence: Persistence::new(),
+            aof: Aof::new(),
+            write_order: Mutex::new(()),
+            shutdown: Notify::new(),
+        }
+    }
+
+    /// Total number of write operations applied to databases since startup.
+    pub fn changes(&self) -> u64 {
+        self.databases.iter().map(Database::changes).sum()
+    }
+
+    /// Number of keys removed because they expired since startup.
+    pub fn expired_keys(&self) -> u64 {
+        self.databases.iter().map(Database::expired_keys).sum()
+    }
+
+    /// Remove expired keys that are never accessed.
+    ///
+    /// Each database is sampled until at most a quarter of sampled keys are expired, or the
+    /// time budget is spent. Expiry times are absolute, so replicas and AOF replay end up
+    /// without these keys as well.
+    pub async fn active_expire_cycle(&self, budget: Duration) {
+        let start = Instant::now();
+        for db in &self.databases {
+            loop {
+                let (sampled, expired) = db.expire_sample(ACTIVE_EXPIRE_SAMPLE).await;
+                if start.elapsed() >= budget {
+                    return;
+                }
+                if sampled == 0 || expired * 4 <= sampled {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Import RDB content into the databases.
+    pub async fn load_rdb(&self, rdb: Rdb) -> Result<(), MiniRedisError> {
+        for (index, content) in rdb.databases {
+            let Some(db) = self.databases.get(index) else {
+                return Err(MiniRedisError::InvalidConfig(format!(
+                    "RDB contains database {index} but only {} databases are configured",
+                    self.databases.len()
+                )));
+            };
+
+            for (key, value) in content.values {
+                db.set_value(key, value).await;
+            }
+            for (key, value) in content.expiry {
+                db.expire_at_millis(key, value).await;
+            }
+        }
+
+        // Loaded content is already on disk
+        self.persistence.mark_clean(self.changes());
+        Ok(())
+    }
+
+    /// Replay commands from AOF file.
+    ///
+    /// Incomplete last command is removed from the file.
+    pub async fn load_aof(&self, path: &Path) -> Result<(), MiniRedisError> {
+        let content = aof::read_aof_file(path).await?;
+        if content.truncated {
+            eprintln!(
+                "AOF file truncated, keeping first {} bytes",
+                content.valid_len
+            );
+            let file = fs::OpenOptions::new().write(true).open(path).await?;
+            file.set_len(content.valid_len as u64).await?;
+        }
+
+        let mut session = Session::default();
+        for command in &content.commands {
+            self.execute(Request::parse(command), &mut session).await;
+        }
+
+        self.persistence.mark_clean(self.changes());
+        Ok(())
+    }
+
+    /// Build an RDB snapshot of the databases.
+    pub async fn dump_rdb(&self) -> Rdb {
+        let databases = database::snapshot_all(&self.databases).await;
+        let ctime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Rdb {
+            version: 11,
+            aux_redis_ver: Some("7.2.0".to_string()),
+            aux_redis_bits: Some("64".to_string()),
+            aux_ctime: Some(ctime.to_string()),
+            aux_used_men: None,
+            databases,
+        }
+    }
+
+    /// Location of RDB file from `dir` and `dbfilename` config.
+    pub async fn rdb_path(&self) -> PathBuf {
+        let mut path = PathBuf::new();
+        if let Some(dir) = self.config_value(b"dir").await {
+            path.push(OsStr::from_bytes(dir.as_slice()));
+        }
+        match self.config_value(b"dbfilename").await {
+            Some(dbfilename) => path.push(OsStr::from_bytes(dbfilename.as_slice())),
+            None => path.push("dump.rdb"),
+        }
+        path
+    }
+
+    /// Write snapshot to disk and wait for completion.
+    pub async fn save(&self) -> Result<(), MiniRedisError> {
+        let changes = self.changes();
+        let rdb = self.dump_rdb().await;
+        self.persistence
+            .save(&rdb, &self.rdb_path().await, changes)
+            .await
+    }
+
+    /// Write snapshot to disk in background.
+    ///
+    /// Returns `false` if another background save is already running.
+    pub async fn bgsave(&self) -> bool {
+        if self.persistence.is_bgsave_in_progress() {
+            return false;
+        }
+
+        let changes = self.changes();
+        let rdb = self.dump_rdb().await;
+        self.persistence.bgsave(rdb, self.rdb_path().await, changes)
+    }
+
+    /// Start a background save if a save point is reached.
+    pub async fn bgsave_if_needed(&self) {
+        if self.persistence.should_save(self.changes()).await {
+            self.bgsave().await;
+        }
+    }
+
+    /// Rewrite AOF in background.
+    ///
+    /// Returns `false` if another rewrite is already running.
+    pub async fn bgrewriteaof(&self) -> bool {
+        let _order = self.write_order.lock().await;
+        self.aof
+            .bgrewrite(self.aof_path().await, &self.databases)
+            .await
+            .is_some()
+    }
+
+    /// Start an AOF rewrite if file grew too much.
+    pub async fn bgrewriteaof_if_needed(&self) {
+        if self.aof.is_enabled().await
+            && !self.aof.is_rewrite_in_progress().await
+            && self.aof.should_rewrite()
+        {
+            self.bgrewriteaof().await;
+        }
+    }
+
+    async fn config_value(&self, key: &[u8]) -> Option<RedisString> {
+        self.config.get(key).await.ok().flatten()
+    }
+
+    /// Update a config value, checking it is valid before.
+    pub async fn set_config(&self, key: &[u8], value: &[u8]) -> Result<(), MiniRedisError> {
+        match key {
+            b"save" => {
+                let save_points = SavePoint::parse_list(value)?;
+                self.persistence.set_save_points(save_points).await;
+            }
+            b"repl-backlog-size" => {
+                let size = std::str::from_utf8(value)?.parse()?;
+                self.replication.set_backlog_size(size).await;
+            }
+            b"appendonly" => match value.to_ascii_lowercase().as_slice() {
+                b"yes" => {
+                    let _order = self.write_order.lock().await;
+                    self.aof
+                        .enable(&self.aof_path().await, &self.databases)
+                        .await?
+                }
+                b"no" => self.aof.disable().await?,
+                _ => {
+                    return Err(MiniRedisError::InvalidConfig(format!(
+                        "Invalid appendonly: {}",
+                        String::from_utf8_lossy(value)
+                    )));
+                }
+            },
+            b"appendfsync" => {
+                let policy = FsyncPolicy::parse(value)?;
+                self.aof.set_fsync_policy(policy).await;
+            }
+            b"auto-aof-rewrite-percentage" => {
+                let percentage = std::str::from_utf8(value)?.parse()?;
+                self.aof.set_auto_rewrite_percentage(percentage);
+            }
+            b"auto-aof-rewrite-min-size" => {
+                let size = std::str::from_utf8(value)?.parse()?;
+                self.aof.set_auto_rewrite_min_size(size);
+            }
+            b"dir" | b"dbfilename" => {}
+            b"databases" => {
+                return Err(MiniRedisError::InvalidConfig(
+                    "can't set immutable config 'databases'".to_string(),
+                ));
+            }
+            _ => {
+                return Err(MiniRedisError::InvalidConfig(format!(
+                    "Unknown option '{}'",
+                    String::from_utf8_lossy(key)
+                )));
+            }
+        }
+
+        self.config.set(key, value).await;
+        Ok(())
+    }
+
+    /// Prepare server to stop.
+    ///
+    /// Without explicit choice, database is saved only if save points are configured.
+    pub async fn shutdown(&self, save: Option<bool>) -> Result<(), MiniRedisError> {
+        let save = match save {
+            Some(save) => save,
+            None => !self.persistence.save_points().await.is_empty(),
+        };
+        if save {
+            self.save().await?;
+        }
+        self.aof.disable().await?;
+        Ok(())
+    }
+
+    /// Wait until a client asked server to stop.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.notified().await;
+    }
+
+    /// Location of AOF file from `dir` and `appendfilename` config.
+    pub async fn aof_path(&self) -> PathBuf {
+        let mut path = PathBuf::new();
+        if let Some(dir) = self.config_value(b"dir").await {
+            path.push(OsStr::from_bytes(dir.as_slice()));
+        }
+        match self.config_value(b"appendfilename").await {
+            Some(filename) => path.push(OsStr::from_bytes(filename.as_slice())),
+            None => path.push("appendonly.aof"),
+        }
+        path
+    }
+
+    /// Check if request must be rejected when sent by a regular client.
+    pub fn is_readonly(&self, request: &Request) -> bool {
+        request.is_write() && self.replication.role() == &ServerMode::Slave
+    }
+
+    /// Register a replica from its `PSYNC` arguments, with the snapshot it must load when
+    /// it is fully resynchronized.
+    pub async fn register_replica(
+        &self,
+        psync_replid: &[u8],
+        psync_offset: i64,
+    ) -> (ReplicaLink, Option<Rdb>) {
+        // No write may be applied between the snapshot and the start of the stream
+        let _order = self.write_order.lock().await;
+        let link = self
+            .replication
+            .register_replica(psync_replid, psync_offset)
+            .await;
+        let rdb = match link.sync {
+            ReplicaSync::Full { .. } => Some(self.dump_rdb().await),
+            ReplicaSync::Partial { .. } => None,
+        };
+        (link, rdb)
+    }
+
+    /// Execute a client request and propagate it to AOF and replicas if it changed the database.
+    pub async fn execute_and_propagate(
+        &self,
+        request: Request,
+        msg: &Message,
+        session: &mut Session,
+    ) -> Response {
+        self.execute_logged(request, msg, session, true).await
+    }
+
+    /// Execute a command streamed by master, logging it to AOF if it changed the database.
+    pub async fn execute_replicated(
+        &self,
+        request: Request,
+        msg: &Message,
+        session: &mut Session,
+    ) -> Response {
+        self.execute_logged(request, msg, session, false).await
+    }
+
+    /// Execute a request, then log it to AOF and forward it to replicas if asked to, as a
+    /// single step for writes.
+    async fn execute_logged(
+        &self,
+        request: Request,
+        msg: &Message,
+        session: &mut Session,
+        to_replicas: bool,
+    ) -> Response {
+        let is_write = request.is_write();
+        let _order = if is_write {
+            Some(self.write_order.lock().await)
+        } else {
+            None
+        };
+
+        // Relative expiry must not be restarted when replaying AOF or on replicas
+        let (request, absolute_command) = match request {
+            Request::Set(
+                key,
+                value,
+                mut options @ SetOptions {
+                    expiry: Some(SetExpiry::In(ms_delta)),
+                    ..
+                },
+            ) => {
+                let expire_at = database::now_unix_millis().saturating_add(ms_delta);
+                options.expiry = Some(SetExpiry::At(expire_at));
+                let command = set_command(&key, &value, options);
+                (Request::Set(key, value, options), Some(command))
+            }
+            Request::GetEx(key, Some(SetExpiry::In(ms_delta))) => {
+                let expire_at = database::now_unix_millis().saturating_add(ms_delta);
+                let command = pexpireat_command(&key, expire_at, ExpireOptions::default());
+                (
+                    Request::GetEx(key, Some(SetExpiry::At(expire_at))),
+                    Some(command),
+                )
+            }
+            Request::Expire(key, ms_delta, options) => {
+                let expire_at = expire_at(ms_delta);
+                let command = pexpireat_command(&key, expire_at, options);
+                (Request::PExpireAt(key, expire_at, options), Some(command))
+            }
+            request => (request, None),
+        };
+
+        let effect = Effect::of(&request);
+        let response = self.execute(request, session).await;
+
+        if is_write && !matches!(response, Response::Error(_)) {
+            match effect {
+                Some(effect) => {
+                    if let Some(command) = effect.command(&response) {
+                        let replica_command = to_replicas.then_some(&command);
+                        self.propagate(session.db, &command, replica_command).await;
+                    }
+                }
+                None => {
+                    let command = absolute_command.as_ref().unwrap_or(msg);
+                    let replica_command = to_replicas.then_some(command);
+                    self.propagate(session.db, command, replica_command).await;
+                }
+            }
+        }
+
+        response
+    }
+
+    /// Execute a request waiting for elements added by other clients.
+    ///
+    /// Waits until request is served, its timeout expires, or `cancelled` completes, in which
+    /// case `None` is returned. Served writes are propagated as their non blocking equivalent.
+    pub async fn execute_blocking(
+        &self,
+        request: Request,
+        session: &Session,
+        cancelled: impl Future<Output = ()>,
+    ) -> Option<Response> {
+        let Some((keys, timeout)) = request.blocking_keys() else {
+            return Some(Response::Error("ERR command cannot block".to_string()));
+        };
+        let db = &self.databases[session.db];
+
+        // Streams read from `$` only return entries added while blocked
+        let request = match request {
+            Request::XRead(streams, count, block) => {
+                let mut resolved = Vec::with_capacity(streams.len());
+                for (key, id) in streams {
+                    let id = match id {
+                        Some(id) => id,
+                        None => match db.stream_last_id(&key).await {
+                            Ok(id) => id,
+                            Err(e) => return Some(e.into()),
+                        },
+                    };
+                    resolved.push((key, Some(id)));
+                }
+                Request::XRead(resolved, count, block)
+            }
+            request => request,
+        };
+
+        let waiter = db.wait_keys(keys);
+        let deadline = (timeout > 0).then(|| Instant::now() + Duration::from_millis(timeout));
+        tokio::pin!(cancelled);
+
+        loop {
+            let served = {
+                let _order = self.write_order.lock().await;
+                let served = self.serve_blocked(&waiter, &request).await;
+                if let Ok(Some((_, Some(command)))) = &served {
+                    self.propagate(session.db, command, Some(command)).await;
+                }
+                served
+            };
+            match served {
+                Ok(Some((response, _))) => return Some(response),
+                Ok(None) => {}
+                Err(e) => return Some(e.into()),
+            }
+
+            let expired = async {
+                match deadline {
+                    Some(deadline) => time::sleep_until(deadline).await,
+                    None => future::pending().await,
+                }
+            };
+            // Closed connection must not consume elements it was woken for
+            tokio::select! {
+                biased;
+                _ = &mut cancelled => return None,
+                _ = waiter.notified() => {}
+                _ = expired => return Some(not_served(&request)),
+            }
+        }
+    }
+
+    /// Try to serve a blocking request, returning its response and the command to propagate if
+    /// it changed the database.
+    async fn serve_blocked(
+        &self,
+        waiter: &KeyWaiter<'_>,
+        request: &Request,
+    ) -> Result<Option<(Response, Option<Message>)>, MiniRedisError> {
+        let (end, command): (_, &[u8]) = match request {
+            Request::BLPop(..) => (ListEnd::Left, b"LPOP"),
+            Request::BRPop(..) => (ListEnd::Right, b"RPOP"),
+            Request::BLMove(source, destination, from, to, _) => {
+                let element = waiter.move_to(destination, *from, *to).await?;
+                return Ok(element.map(|element| {
+                    let command = Message::command(&[
+                        b"LMOVE",
+                        source.as_slice(),
+                        destination.as_slice(),
+                        from.as_bytes(),
+                        to.as_bytes(),
+                    ]);
+                    (Response::Content(element), Some(command))
+                }));
+            }
+            Request::XRead(streams, count, _) => {
+                let streams = stream_read(waiter.database(), streams, *count).await?;
+                return Ok((!streams.is_empty()).then(|| (stream_read_response(streams), None)));
+            }
+            Request::XReadGroup(group, consumer, streams, count, _, noack) => {
+                let read = waiter
+                    .database()
+                    .stream_read_group(group, consumer, streams, *count, *noack)
+                    .await?;
+                return Ok((!read.is_empty()).then(|| {
+                    let command = xreadgroup_command(group, consumer, streams, *count, *noack);
+                    (stream_group_read_response(read), Some(command))
+                }));
+            }
+            _ => return Ok(None),
+        };
+
+        let popped = waiter.pop(end).await?;
+        Ok(popped.map(|(key, element)| {
+            let command = Message::command(&[command, key.as_slice()]);
+            (contents(vec![key, element]), Some(command))
+        }))
+    }
+
+    /// Append a write command to AOF and send it to replicas, if any command is given for
+    /// them.
+    async fn propagate(&self, db: usize, aof_command: &Message, replica_command: Option<&Message>) {
+        if let Err(e) = self.aof.append(db, aof_command).await {
+            eprintln!("AOF write error: {e}");
+        }
+
+        if let Some(replica_command) = replica_command {
+            self.replication.propagate_write(db, replica_command).await;
+        }
+    }
+
+    /// Apply request to server state and build matching response.
+    pub async fn execute(&self, request: Request, session: &mut Session) -> Response {
+        let db = &self.databases[session.db];
+
+        match request {
+            Request::Ping => Response::Pong,
+            Request::InfoReplication => self.replication.info().await,
+            Request::InfoStats => Response::InfoStats {
+                expired_keys: self.expired_keys(),
+            },
+            Request::InfoAll => Response::InfoSections(vec![
+                self.replication.info().await,
+                Response::InfoStats {
+                    expired_keys: self.expired_keys(),
+                },
+            ]),
+            Request::Echo(data) => Response::Echo(data),
+            Request::Get(key) => match db.get(key).await {
+                Ok(Some(data)) => Response::Content(data),
+                Ok(None) => Response::NoContent,
+                Err(e) => e.into(),
+            },
+            Request::Set(key, value, options) => to_response(
+                db.set_with_options(&key, value, options).await,
+                |(set, previous)| {
+                    if options.get {
+                        optional_content(previous)
+                    } else if set {
+                        Response::Ok
+                    } else {
+                        Response::NoContent
+                    }
+                },
+            ),
+            Request::SetNx(key, value) => {
+                let options = SetOptions {
+                    condition: Some(SetCondition::Missing),
+                    ..Default::default()
+                };
+                to_response(
+                    db.set_with_options(&key, value, options).await,
+                    |(set, _)| Response::Integer(set as i64),
+                )
+            }
+            Request::Append(key, value) => {
+                to_response(db.string_append(&key, &value).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::StrLen(key) => to_response(db.string_len(&key).await, |len| {
+                Response::Integer(len as i64)
+            }),
+            Request::GetRange(key, start, end) => {
+                to_response(db.string_range(&key, start, end).await, Response::Content)
+            }
+            Request::SetRange(key, offset, value) => {
+                to_response(db.string_set_range(&key, offset, &value).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::GetDel(key) => to_response(db.string_get_del(&key).await, optional_content),
+            Request::GetEx(key, expiry) => {
+                to_response(db.string_get_ex(&key, expiry).await, optional_content)
+            }
+            Request::MGet(keys) => Response::Array(
+                db.get_many(&keys)
+                    .await
+                    .into_iter()
+                    .map(optional_content)
+                    .collect(),
+            ),
+            Request::MSet(pairs) => {
+                db.set_many(pairs, false).await;
+                Response::Ok
+            }
+            Request::MSetNx(pairs) => Response::Integer(db.set_many(pairs, true).await as i64),
+            Request::IncrBy(key, increment) => {
+                to_response(db.string_incr_by(&key, increment).await, Response::Integer)
+            }
+            Request::IncrByFloat(key, increment) => {
+                let increment = database::parse_float(increment.as_slice()).unwrap_or_default();
+                to_response(
+                    db.string_incr_by_float(&key, increment).await,
+                    Response::Content,
+                )
+            }
+            Request::SetBit(key, offset, bit) => {
+                to_response(db.bit_set(&key, offset, bit).await, |previous| {
+                    Response::Integer(previous as i64)
+                })
+            }
+            Request::GetBit(key, offset) => to_response(db.bit_get(&key, offset).await, |bit| {
+                Response::Integer(bit as i64)
+            }),
+            Request::BitCount(key, range) => {
+                to_response(db.bit_count(&key, range).await, |count| {
+                    Response::Integer(count as i64)
+                })
+            }
+            Request::BitPos(key, bit, start, end, unit) => to_response(
+                db.bit_pos(&key, bit, start, end, unit).await,
+                Response::Integer,
+            ),
+            Request::BitOp(operation, destination, keys) => {
+                to_response(db.bit_op(operation, &destination, &keys).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::BitField(key, ops) | Request::BitFieldRo(key, ops) => {
+                to_response(db.bit_field(&key, &ops).await, |values| {
+                    Response::Array(
+                        values
+                            .into_iter()
+                            .map(|value| value.map_or(Response::NoContent, Response::Integer))
+                            .collect(),
+                    )
+                })
+            }
+            Request::Lcs(key1, key2, options) => {
+                to_response(db.string_lcs(&key1, &key2).await, |(sequence, matches)| {
+                    lcs_response(sequence, matches, options)
+                })
+            }
+            Request::Expire(key, ms_delta, options) => {
+                Response::Integer(db.expire(&key, expire_at(ms_delta), options).await as i64)
+            }
+            Request::PExpireAt(key, expire_at, options) => {
+                Response::Integer(db.expire(&key, expire_at, options).await as i64)
+            }
+            Request::Ttl(key) => ttl_response(db, &key, false, false).await,
+            Request::PTtl(key) => ttl_response(db, &key, true, false).await,
+            Request::ExpireTime(key) => ttl_response(db, &key, false, true).await,
+            Request::PExpireTime(key) => ttl_response(db, &key, true, true).await,
+            Request::Persist(key) => Response::Integer(db.persist(&key).await as i64),
+            Request::Keys(pattern) => {
+                Response::KeyMatches(db.keys_matching(pattern.as_slice()).await)
+            }
+            Request::Scan(cursor, options) => {
+                let (cursor, keys) = db.scan(cursor, &options).await;
+                scan_response(cursor, keys)
+            }
+            Request::HScan(key, cursor, options) => to_response(
+                db.hash_scan(&key, cursor, &options).await,
+                |(cursor, fields)| scan_response(cursor, flatten_pairs(fields)),
+            ),
+            Request::SScan(key, cursor, options) => to_response(
+                db.set_scan(&key, cursor, &options).await,
+                |(cursor, members)| scan_response(cursor, members),
+            ),
+            Request::ZScan(key, cursor, options) => to_response(
+                db.zset_scan(&key, cursor, &options).await,
+                |(cursor, members)| {
+                    let members = members
+                        .into_iter()
+                        .flat_map(|(member, score)| [member, database::format_float(score)])
+                        .collect();
+                    scan_response(cursor, members)
+                },
+            ),
+            Request::Del(keys) | Request::Unlink(keys) => {
+                Response::Integer(db.delete(&keys).await as i64)
+            }
+            Request::Exists(keys) | Request::Touch(keys) => {
+                Response::Integer(db.exists_count(&keys).await as i64)
+            }
+            Request::Rename(source, destination) => {
+                to_response(db.rename(&source, &destination, false).await, |_| {
+                    Response::Ok
+                })
+            }
+            Request::RenameNx(source, destination) => {
+                to_response(db.rename(&source, &destination, true).await, |renamed| {
+                    Response::Integer(renamed as i64)
+                })
+            }
+            Request::Copy(source, destination, index, replace) => {
+                let index = index.unwrap_or(session.db);
+                let Some(target) = self.databases.get(index) else {
+                    return db_index_out_of_range();
+                };
+                if index == session.db && source == destination {
+                    return Response::Error(
+                        "ERR source and destination objects are the same".to_string(),
+                    );
+                }
+                let Some((value, expire_at)) = db.get_with_expiry(&source).await else {
+                    return Response::Integer(0);
+                };
+                let copied = target
+                    .restore(&destination, value, expire_at, replace)
+                    .await;
+                Response::Integer(copied as i64)
+            }
+            Request::RandomKey => optional_content(db.random_key().await),
+            Request::DbSize => Response::Integer(db.size().await as i64),
+            Request::ConfigGet(key) => match self.config_value(key.as_slice()).await {
+                Some(value) => Response::ConfigGet(key, value),
+                None => Response::NoContent,
+            },
+            Request::ReplConf(_) => Response::Ok,
+            Request::ReplConfGetAck | Request::ReplConfAck(_) => Response::Error(
+                "ERR REPLCONF GETACK / ACK are reserved to replication link".to_string(),
+            ),
+            Request::Wait(numreplicas, timeout) => {
+                let count = self.replication.wait(numreplicas, timeout).await;
+                Response::Integer(count as i64)
+            }
+            Request::Psync(..) => {
+                Response::Error("ERR PSYNC must be sent from a replica connection".to_string())
+            }
+            Request::ConfigSet(key, value) => {
+                match self.set_config(key.as_slice(), value.as_slice()).await {
+                    Ok(()) => Response::Ok,
+                    Err(e) => Response::Error(format!("ERR {e}")),
+                }
+            }
+            Request::Save => match self.save().await {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::Error(format!("ERR {e}")),
+            },
+            Request::BgSave => {
+                if self.bgsave().await {
+                    Response::Status("Background saving started".to_string())
+                } else {
+                    Response::Error("ERR Background save already in progress".to_string())
+                }
+            }
+            Request::LastSave => Response::Integer(self.persistence.last_save() as i64),
+            Request::BgRewriteAof => {
+                if self.bgrewriteaof().await {
+                    Response::Status("Background append only file rewriting started".to_string())
+                } else {
+                    Response::Error(
+                        "ERR Background append only file rewriting already in progress".to_string(),
+                    )
+                }
+            }
+            Request::Shutdown(save) => match self.shutdown(save).await {
+                Ok(()) => {
+                    self.shutdown.notify_one();
+                    Response::Ok
+                }
+                Err(e) => Response::Error(format!("ERR Errors trying to SHUTDOWN: {e}")),
+            },
+            Request::Select(index) => {
+                if index >= self.databases.len() {
+                    return db_index_out_of_range();
+                }
+                session.db = index;
+                Response::Ok
+            }
+            Request::SwapDb(index1, index2) => {
+                if index1 >= self.databases.len() || index2 >= self.databases.len() {
+                    return db_index_out_of_range();
+                }
+                if index1 != index2 {
+                    let (first, second) = (index1.min(index2), index1.max(index2));
+                    self.databases[first].swap(&self.databases[second]).await;
+                }
+                Response::Ok
+            }
+            Request::Move(key, index) => {
+                let Some(target) = self.databases.get(index) else {
+                    return db_index_out_of_range();
+                };
+                if index == session.db {
+                    return Response::Error(
+                        "ERR source and destination objects are the same".to_string(),
+                    );
+                }
+                if target.exists(key.clone()).await {
+                    return Response::Integer(0);
+                }
+                let Some((value, expire_at)) = db.remove(key.clone()).await else {
+                    return Response::Integer(0);
+                };
+                target.restore(&key, value, expire_at, true).await;
+                Response::Integer(1)
+            }
+            Request::FlushDb => {
+                db.clear().await;
+                Response::Ok
+            }
+            Request::FlushAll => {
+                for db in &self.databases {
+                    db.clear().await;
+                }
+                Response::Ok
+            }
+            Request::LPush(key, elements) => {
+                to_response(db.list_push(&key, ListEnd::Left, elements).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::RPush(key, elements) => {
+                to_response(db.list_push(&key, ListEnd::Right, elements).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::LPop(key, count) => list_pop(db, &key, ListEnd::Left, count).await,
+            Request::RPop(key, count) => list_pop(db, &key, ListEnd::Right, count).await,
+            Request::LLen(key) => {
+                to_response(db.list_len(&key).await, |len| Response::Integer(len as i64))
+            }
+            Request::LRange(key, start, stop) => {
+                to_response(db.list_range(&key, start, stop).await, contents)
+            }
+            Request::LIndex(key, index) => {
+                to_response(db.list_index(&key, index).await, optional_content)
+            }
+            Request::LSet(key, index, element) => {
+                to_response(db.list_set(&key, index, element).await, |_| Response::Ok)
+            }
+            Request::LRem(key, count, element) => {
+                to_response(db.list_remove(&key, count, &element).await, |count| {
+                    Response::Integer(count as i64)
+                })
+            }
+            Request::LTrim(key, start, stop) => {
+                to_response(db.list_trim(&key, start, stop).await, |_| Response::Ok)
+            }
+            Request::LInsert(key, before, pivot, element) => to_response(
+                db.list_insert(&key, before, &pivot, element).await,
+                Response::Integer,
+            ),
+            Request::LMove(source, destination, from, to) => to_response(
+                db.list_move(&source, &destination, from, to).await,
+                optional_content,
+            ),
+            Request::HSet(key, pairs) => to_response(db.hash_set(&key, pairs).await, |added| {
+                Response::Integer(added as i64)
+            }),
+            Request::HSetNx(key, field, value) => {
+                to_response(db.hash_set_nx(&key, field, value).await, |added| {
+                    Response::Integer(added as i64)
+                })
+            }
+            Request::HGet(key, field) => {
+                to_response(db.hash_get(&key, &field).await, optional_content)
+            }
+            Request::HMGet(key, fields) => {
+                to_response(db.hash_get_many(&key, &fields).await, |values| {
+                    Response::Array(values.into_iter().map(optional_content).collect())
+                })
+            }
+            Request::HDel(key, fields) => {
+                to_response(db.hash_delete(&key, &fields).await, |count| {
+                    Response::Integer(count as i64)
+                })
+            }
+            Request::HExists(key, field) => {
+                to_response(db.hash_exists(&key, &field).await, |exists| {
+                    Response::Integer(exists as i64)
+                })
+            }
+            Request::HLen(key) => {
+                to_response(db.hash_len(&key).await, |len| Response::Integer(len as i64))
+            }
+            Request::HStrLen(key, field) => {
+                to_response(db.hash_strlen(&key, &field).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::HKeys(key) => to_response(db.hash_get_all(&key).await, |pairs| {
+                contents(pairs.into_iter().map(|(field, _)| field).collect())
+            }),
+            Request::HVals(key) => to_response(db.hash_get_all(&key).await, |pairs| {
+                contents(pairs.into_iter().map(|(_, value)| value).collect())
+            }),
+            Request::HGetAll(key) => to_response(db.hash_get_all(&key).await, |pairs| {
+                contents(flatten_pairs(pairs))
+            }),
+            Request::HIncrBy(key, field, increment) => to_response(
+                db.hash_incr_by(&key, field, increment).await,
+                Response::Integer,
+            ),
+            Request::HIncrByFloat(key, field, increment) => {
+                let increment = database::parse_float(increment.as_slice()).unwrap_or_default();
+                to_response(
+                    db.hash_incr_by_float(&key, field, increment).await,
+                    Response::Content,
+                )
+            }
+            Request::HRandField(key, count, with_values) => {
+                let result = db.hash_random_fields(&key, count.unwrap_or(1)).await;
+                to_response(result, |pairs| match count {
+                    None => optional_content(pairs.into_iter().next().map(|(field, _)| field)),
+                    Some(_) if with_values => contents(flatten_pairs(pairs)),
+                    Some(_) => contents(pairs.into_iter().map(|(field, _)| field).collect()),
+                })
+            }
+            Request::SAdd(key, members) => to_response(db.set_add(&key, members).await, |added| {
+                Response::Integer(added as i64)
+            }),
+            Request::SRem(key, members) => {
+                to_response(db.set_remove(&key, &members).await, |removed| {
+                    Response::Integer(removed as i64)
+                })
+            }
+            Request::SMembers(key) => to_response(db.set_members(&key).await, contents),
+            Request::SIsMember(key, member) => {
+                to_response(db.set_contains(&key, &[member]).await, |found| {
+                    Response::Integer(found[0] as i64)
+                })
+            }
+            Request::SMIsMember(key, members) => {
+                to_response(db.set_contains(&key, &members).await, |found| {
+                    Response::Array(
+                        found
+                            .into_iter()
+                            .map(|found| Response::Integer(found as i64))
+                            .collect(),
+                    )
+                })
+            }
+            Request::SCard(key) => {
+                to_response(db.set_len(&key).await, |len| Response::Integer(len as i64))
+            }
+            Request::SPop(key, count) => {
+                let result = db.set_pop(&key, count.unwrap_or(1)).await;
+                to_response(result, |members| match count {
+                    None => optional_content(members.into_iter().next()),
+                    Some(_) => contents(members),
+                })
+            }
+            Request::SRandMember(key, count) => {
+                let result = db.set_random_members(&key, count.unwrap_or(1)).await;
+                to_response(result, |members| match count {
+                    None => optional_content(members.into_iter().next()),
+                    Some(_) => contents(members),
+                })
+            }
+            Request::SMove(source, destination, member) => {
+                to_response(db.set_move(&source, &destination, member).await, |moved| {
+                    Response::Integer(moved as i64)
+                })
+            }
+            Request::SInter(keys) => to_response(
+                db.set_combine(SetOperation::Intersection, &keys).await,
+                contents,
+            ),
+            Request::SUnion(keys) => {
+                to_response(db.set_combine(SetOperation::Union, &keys).await, contents)
+            }
+            Request::SDiff(keys) => to_response(
+                db.set_combine(SetOperation::Difference, &keys).await,
+                contents,
+            ),
+            Request::SInterStore(destination, keys) => {
+                set_combine_store(db, SetOperation::Intersection, &destination, &keys).await
+            }
+            Request::SUnionStore(destination, keys) => {
+                set_combine_store(db, SetOperation::Union, &destination, &keys).await
+            }
+            Request::SDiffStore(destination, keys) => {
+                set_combine_store(db, SetOperation::Difference, &destination, &keys).await
+            }
+            Request::SInterCard(keys, limit) => {
+                to_response(db.set_intersection_len(&keys, limit).await, |len| {
+                    Response::Integer(len as i64)
+                })
+            }
+            Request::ZAdd(key, options, changed, members) => to_response(
+                db.zset_add(&key, options, changed, members).await,
+                |count| Response::Integer(count as i64),
+            ),
+            Request::ZAddIncr(key, options, increment, member) => to_response(
+                db.zset_incr(&key, options, increment, member).await,
+                |score| optional_content(score.map(database::format_float)),
+            ),
+            Request::ZIncrBy(key, increment, member) => to_response(
+                db.zset_incr(&key, ZAddOptions::default(), increment, member)
+                    .await,
+                |score| optional_content(score.map(database::format_float)),
+            ),
+            Request::ZRem(key, members) => {
+                to_response(db.zset_remove(&key, &members).await, |count| {
+                    Response::Integer(count as i64)
+                })
+            }
+            Request::ZScore(key, member) => {
+                to_response(db.zset_score(&key, &member).await, |score| {
+                    optional_content(score.map(database::format_float))
+                })
+            }
+            Request::ZCard(key) => {
+                to_response(db.zset_len(&key).await, |len| Response::Integer(len as i64))
+            }
+            Request::ZRank(key, member, with_score) => {
+                zset_rank(db, &key, &member, false, with_score).await
+            }
+            Request::ZRevRank(key, member, with_score) => {
+                zset_rank(db, &key, &member, true, with_score).await
+            }
+            Request::ZRange(key, range, with_scores) => {
+                to_response(db.zset_range(&key, &range).await, |members| {
+                    zset_members(members, with_scores)
+                })
+            }
+            Request::ZRangeStore(destination, source, range) => to_response(
+                db.zset_range_store(&destination, &source, &range).await,
+                |count| Response::Integer(count as i64),
+            ),
+            Request::ZCount(key, min, max) => to_response(
+                db.zset_count(&key, &ZRangeBy::Score(min, max)).await,
+                |count| Response::Integer(count as i64),
+            ),
+            Request::ZPopMin(key, count) => to_response(
+                db.zset_pop(&key, count.unwrap_or(1), false).await,
+                |members| zset_members(members, true),
+            ),
+            Request::ZPopMax(key, count) => to_response(
+                db.zset_pop(&key, count.unwrap_or(1), true).await,
+                |members| zset_members(members, true),
+            ),
+            Request::Type(key) => {
+                Response::Status(db.type_name(&key).await.unwrap_or("none").to_string())
+            }
+            Request::XAdd(key, options, id, fields) => {
+                to_response(db.stream_add(&key, options, id, fields).await, |id| {
+                    optional_content(id.map(RedisString::from))
+                })
+            }
+            Request::XLen(key) => to_response(db.stream_len(&key).await, |len| {
+                Response::Integer(len as i64)
+            }),
+            Request::XRange(key, start, end, count) => to_response(
+                db.stream_range(&key, start, end, count, false).await,
+                stream_entries,
+            ),
+            Request::XRevRange(key, start, end, count) => to_response(
+                db.stream_range(&key, start, end, count, true).await,
+                stream_entries,
+            ),
+            Request::XDel(key, ids) => to_response(db.stream_delete(&key, &ids).await, |count| {
+                Response::Integer(count as i64)
+            }),
+            Request::XTrim(key, trim) => to_response(db.stream_trim(&key, trim).await, |count| {
+                Response::Integer(count as i64)
+            }),
+            Request::XSetId(key, last_id, entries_added, max_deleted_id) => to_response(
+                db.stream_set_id(&key, last_id, entries_added, max_deleted_id)
+                    .await,
+                |_| Response::Ok,
+            ),
+            // Blocking requests are served immediately when not sent by a client
+            Request::XRead(streams, count, _) => {
+                to_response(stream_read(db, &streams, count).await, |streams| {
+                    if streams.is_empty() {
+                        Response::NullArray
+                    } else {
+                        stream_read_response(streams)
+                    }
+                })
+            }
+            Request::XGroupCreate(key, group, id, mkstream, entries_read) => to_response(
+                db.stream_group_create(&key, &group, id, mkstream, entries_read)
+                    .await,
+                |_| Response::Ok,
+            ),
+            Request::XGroupDestroy(key, group) => {
+                to_response(db.stream_group_destroy(&key, &group).await, |destroyed| {
+                    Response::Integer(destroyed as i64)
+                })
+            }
+            Request::XGroupSetId(key, group, id, entries_read) => to_response(
+                db.stream_group_set_id(&key, &group, id, entries_read).await,
+                |_| Response::Ok,
+            ),
+            Request::XGroupCreateConsumer(key, group, consumer) => to_response(
+                db.stream_consumer_create(&key, &group, &consumer).await,
+                |created| Response::Integer(created as i64),
+            ),
+            Request::XGroupDelConsumer(key, group, consumer) => to_response(
+                db.stream_consumer_delete(&key, &group, &consumer).await,
+                |pending| Response::Integer(pending as i64),
+            ),
+            Request::XReadGroup(group, consumer, streams, count, _, noack) => to_response(
+                db.stream_read_group(&group, &consumer, &streams, count, noack)
+                    .await,
+                |streams| {
+                    if streams.is_empty() {
+                        Response::NullArray
+                    } else {
+                        stream_group_read_response(streams)
+                    }
+                },
+            ),
+            Request::XAck(key, group, ids) => {
+                to_response(db.stream_ack(&key, &group, &ids).await, |count| {
+                    Response::Integer(count as i64)
+                })
+            }
+            Request::XPending(key, group, None) => to_response(
+                db.stream_pending_summary(&key, &group).await,
+                |(count, bounds, consumers)| {
+                    let (first, last) = match bounds {
+                        Some((first, last)) => (Some(first.into()), Some(last.into())),
+                        None => (None, None),
+                    };
+                    let consumers = consumers
+                        .into_iter()
+                        .map(|(consumer, count)| {
+                            contents(vec![
+                                consumer,
+                                RedisString::from(count.to_string().into_bytes()),
+                            ])
+                        })
+                        .collect::<Vec<_>>();
+                    Response::Array(vec![
+                        Response::Integer(count as i64),
+                        optional_content(first),
+                        optional_content(last),
+                        if consumers.is_empty() {
+                            Response::NoContent
+                        } else {
+                            Response::Array(consumers)
+                        },
+                    ])
+                },
+            ),
+            Request::XPending(key, group, Some(range)) => {
+                to_response(db.stream_pending(&key, &group, &range).await, |pending| {
+                    Response::Array(
+                        pending
+                            .into_iter()
+                            .map(|(id, consumer, idle, delivery_count)| {
+                                Response::Array(vec![
+                                    Response::Content(id.into()),
+                                    Response::Content(consumer),
+                                    Response::Integer(idle as i64),
+                                    Response::Integer(delivery_count as i64),
+                                ])
+                            })
+                            .collect(),
+                    )
+                })
+            }
+            Request::XClaim(key, group, consumer, min_idle, ids, options) => to_response(
+                db.stream_claim(&key, &group, &consumer, min_idle, &ids, options)
+                    .await,
+                |claimed| stream_claimed(claimed, options.justid),
+            ),
+            Request::XAutoClaim(key, group, consumer, min_idle, start, count, justid) => {
+                let result = db
+                    .stream_auto_claim(&key, &group, &consumer, min_idle, start, count, justid)
+                    .await;
+                to_response(result, |(cursor, claimed, deleted)| {
+                    Response::Array(vec![
+                        Response::Content(cursor.into()),
+                        stream_claimed(claimed, justid),
+                        contents(deleted.into_iter().map(RedisString::from).collect()),
+                    ])
+                })
+            }
+            Request::XInfoStream(key) => to_response(db.stream_info(&key).await, |info| {
+                let entry = |entry: Option<StreamEntry>| match entry {
+                    Some((id, fields)) => stream_entry(id, Some(fields)),
+                    None => Response::NoContent,
+                };
+                info_fields(vec![
+                    ("length", Response::Integer(info.length as i64)),
+                    ("last-generated-id", Response::Content(info.last_id.into())),
+                    (
+                        "max-deleted-entry-id",
+                        Response::Content(info.max_deleted_id.into()),
+                    ),
+                    (
+                        "entries-added",
+                        Response::Integer(info.entries_added as i64),
+                    ),
+                    (
+                        "recorded-first-entry-id",
+                        Response::Content(info.first_id.into()),
+                    ),
+                    ("groups", Response::Integer(info.groups as i64)),
+                    ("first-entry", entry(info.first_entry)),
+                    ("last-entry", entry(info.last_entry)),
+                ])
+            }),
+            Request::XInfoGroups(key) => to_response(db.stream_groups_info(&key).await, |groups| {
+                let optional_integer = |value: Option<u64>| match value {
+                    Some(value) => Response::Integer(value as i64),
+                    None => Response::NoContent,
+                };
+                Response::Array(
+                    groups
+                        .into_iter()
+                        .map(|group| {
+                            info_fields(vec![
+                                ("name", Response::Content(group.name)),
+                                ("consumers", Response::Integer(group.consumers as i64)),
+                                ("pending", Response::Integer(group.pending as i64)),
+                                ("last-delivered-id", Response::Content(group.last_id.into())),
+                                ("entries-read", optional_integer(group.entries_read)),
+                                ("lag", optional_integer(group.lag)),
+                            ])
+                        })
+                        .collect(),
+                )
+            }),
+            Request::XInfoConsumers(key, group) => {
+                to_response(db.stream_consumers_info(&key, &group).await, |consumers| {
+                    Response::Array(
+                        consumers
+                            .into_iter()
+                            .map(|consumer| {
+                                let inactive = consumer.inactive.map_or(-1, |ms| ms as i64);
+                                info_fields(vec![
+                                    ("name", Response::Content(consumer.name)),
+                                    ("pending", Response::Integer(consumer.pending as i64)),
+                                    ("idle", Response::Integer(consumer.idle as i64)),
+                                    ("inactive", Response::Integer(inactive)),
+                                ])
+                            })
+                            .collect(),
+                    )
+                })
+            }
+            Request::BLPop(..) | Request::BRPop(..) | Request::BLMove(..) => {
+                let (keys, _) = request.blocking_keys().unwrap_or_default();
+                let waiter = db.wait_keys(keys);
+                to_response(self.serve_blocked(&waiter, &request).await, |served| {
+                    served.map_or_else(|| not_served(&request), |(response, _)| response)
+                })
+            }
+            Request::Invalid(e) => e.into(),
+            Request::UnhandledCommand => {
+                Response::Error("BAD_CMD Invalid command received".to_string())
+            }
+        }
+    }
+}
+
+/// State attached to a client connection.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Index of the selected database.
+    pub db: usize,
+}
+
+/// Change made by a command with a random or floating point result.
+///
+/// It is propagated as a deterministic command so that AOF and replicas get the same result.
+enum Effect {
+    /// String set to the response, keeping its expiry.
+    StringSet(RedisString),
+    /// Hash field set to the response.
+    HashSet(RedisString, RedisString),
+    /// Set members returned in the response were removed.
+    SetRemove(RedisString),
+    /// Stream entry added with the ID returned in the response.
+    StreamAdd(
+        RedisString,
+        Option<StreamTrimOptions>,
+        Vec<(RedisString, RedisString)>,
+    ),
+    /// Pending entries returned in the response were claimed by the consumer, regardless of
+    /// their idle time.
+    StreamClaim(RedisString, RedisString, RedisString, XClaimOptions),
+    /// Same as `StreamClaim` for `XAUTOCLAIM`, whose response also lists removed entries that
+    /// were dropped from pending ones.
+    StreamAutoClaim(RedisString, RedisString, RedisString, XClaimOptions),
+}
+
+impl Effect {
+    fn of(request: &Request) -> Option<Self> {
+        match request {
+            Request::IncrByFloat(key, _) => Some(Self::StringSet(key.clone())),
+            Request::HIncrByFloat(key, field, _) => Some(Self::HashSet(key.clone(), field.clone())),
+            Request::SPop(key, _) => Some(Self::SetRemove(key.clone())),
+            Request::XAdd(key, XAddOptions { trim, .. }, _, fields) => {
+                Some(Self::StreamAdd(key.clone(), *trim, fields.clone()))
+            }
+            Request::XClaim(key, group, consumer, _, _, options) => Some(Self::StreamClaim(
+                key.clone(),
+                group.clone(),
+                consumer.clone(),
+                *options,
+            )),
+            Request::XAutoClaim(key, group, consumer, _, _, _, justid) => {
+                let options = XClaimOptions {
+                    justid: *justid,
+                    ..Default::default()
+                };
+                Some(Self::StreamAutoClaim(
+                    key.clone(),
+                    group.clone(),
+                    consumer.clone(),
+                    options,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Command applying the same change, or `None` if nothing changed.
+    fn command(self, response: &Response) -> Option<Message> {
+        match (self, response) {
+            (Self::StringSet(key), Response::Content(value)) => Some(Message::command(&[
+                b"SET",
+                key.as_slice(),
+                value.as_slice(),
+                b"KEEPTTL",
+            ])),
+            (Self::HashSet(key, field), Response::Content(value)) => Some(Message::command(&[
+                b"HSET",
+                key.as_slice(),
+                field.as_slice(),
+                value.as_slice(),
+            ])),
+            (Self::SetRemove(key), Response::Content(member)) => Some(Message::command(&[
+                b"SREM",
+                key.as_slice(),
+                member.as_slice(),
+            ])),
+            (Self::SetRemove(key), Response::Array(members)) if !members.is_empty() => {
+                let mut args = vec![b"SREM".as_slice(), key.as_slice()];
+                args.extend(members.iter().filter_map(|member| match member {
+                    Response::Content(member) => Some(member.as_slice()),
+                    _ => None,
+                }));
+                Some(Message::command(&args))
+            }
+            (Self::StreamAdd(key, trim, fields), Response::Content(id)) => {
+                let trim = trim.map(|options| {
+                    let (strategy, threshold) = match options.trim {
+                        StreamTrim::MaxLen(max_len) => (b"MAXLEN".as_slice(), max_len.to_string()),
+                        StreamTrim::MinId(min_id) => (b"MINID".as_slice(), min_id.to_string()),
+                    };
+                    (
+                        strategy,
+                        threshold,
+                        options.approximate,
+                        options.limit.to_string(),
+                    )
+                });
+                let mut args = vec![b"XADD".as_slice(), key.as_slice()];
+                if let Some((strategy, threshold, approximate, limit)) = &trim {
+                    // Approximate trimming only depends on entries, so replicas trim the same
+                    if *approximate {
+                        args.extend([*strategy, b"~", threshold.as_bytes(), b"LIMIT"]);
+                        args.push(limit.as_bytes());
+                    } else {
+                        args.extend([*strategy, threshold.as_bytes()]);
+                    }
+                }
+                args.push(id.as_slice());
+                for (field, value) in &fields {
+                    args.extend([field.as_slice(), value.as_slice()]);
+                }
+                Some(Message::command(&args))
+            }
+            (Self::StreamClaim(key, group, consumer, options), Response::Array(claimed)) => {
+                let ids: Vec<_> = claimed.iter().filter_map(response_id).collect();
+                xclaim_command(&key, &group, &consumer, &ids, options)
+            }
+            (Self::StreamAutoClaim(key, group, consumer, options), Response::Array(items)) => {
+                let [_, Response::Array(claimed), Response::Array(deleted)] = items.as_slice()
+                else {
+                    return None;
+                };
+                let ids: Vec<_> = claimed
+                    .iter()
+                    .chain(deleted)
+                    .filter_map(response_id)
+                    .collect();
+                xclaim_command(&key, &group, &consumer, &ids, options)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Build response from command result, converting errors to error responses.
+fn to_response<T>(result: Result<T, MiniRedisError>, f: impl FnOnce(T) -> Response) -> Response {
+    match result {
+        Ok(value) => f(value),
+        Err(e) => e.into(),
+    }
+}
+
+fn optional_content(value: Option<RedisString>) -> Response {
+    match value {
+        Some(value) => Response::Content(value),
+        None => Response::NoContent,
+    }
+}
+
+fn contents(values: Vec<RedisString>) -> Response {
+    Response::Array(values.into_iter().map(Response::Content).collect())
+}
+
+/// Interleave fields and their values.
+fn flatten_pairs(pairs: Vec<(RedisString, RedisString)>) -> Vec<RedisString> {
+    pairs
+        .into_iter()
+        .flat_map(|(field, value)| [field, value])
+        .collect()
+}
+
+async fn set_combine_store(
+    db: &Database,
+    operation: SetOperation,
+    destination: &RedisString,
+    keys: &[RedisString],
+) -> Response {
+    let result = db.set_combine_store(operation, destination, keys).await;
+    to_response(result, |len| Response::Integer(len as i64))
+}
+
+async fn zset_rank(
+    db: &Database,
+    key: &RedisString,
+    member: &RedisString,
+    rev: bool,
+    with_score: bool,
+) -> Response {
+    to_response(db.zset_rank(key, member, rev).await, |rank| match rank {
+        None => Response::NoContent,
+        Some((rank, score)) if with_score => Response::Array(vec![
+            Response::Integer(rank as i64),
+            Response::Content(database::format_float(score)),
+        ]),
+        Some((rank, _)) => Response::Integer(rank as i64),
+    })
+}
+
+/// Longest common subsequence, its length, or its ranges according to options.
+fn lcs_response(sequence: RedisString, matches: Vec<LcsMatch>, options: LcsOptions) -> Response {
+    let len = Response::Integer(sequence.as_slice().len() as i64);
+    if options.len {
+        return len;
+    }
+    if !options.idx {
+        return Response::Content(sequence);
+    }
+
+    let range = |(start, end): (usize, usize)| {
+        Response::Array(vec![
+            Response::Integer(start as i64),
+            Response::Integer(end as i64),
+        ])
+    };
+    let matches = matches
+        .into_iter()
+        .filter(|lcs_match| lcs_match.match_len() >= options.min_match_len)
+        .map(|lcs_match| {
+            let mut output = vec![range(lcs_match.a), range(lcs_match.b)];
+            if options.with_match_len {
+                output.push(Response::Integer(lcs_match.match_len() as i64));
+            }
+            Response::Array(output)
+        })
+        .collect();
+    Response::Array(vec![
+        Response::Content(RedisString::new(b"matches")),
+        Response::Array(matches),
+        Response::Content(RedisString::new(b"len")),
+        len,
+    ])
+}
+
+/// Next cursor of a `SCAN` command followed by visited items.
+fn scan_response(cursor: u64, items: Vec<RedisString>) -> Response {
+    Response::Array(vec![
+        Response::Content(RedisString::new(cursor.to_string().as_bytes())),
+        contents(items),
+    ])
+}
+
+/// Sorted set members, each followed by its score if requested.
+fn zset_members(members: Vec<(RedisString, f64)>, with_scores: bool) -> Response {
+    if !with_scores {
+        return contents(members.into_iter().map(|(member, _)| member).collect());
+    }
+    contents(
+        members
+            .into_iter()
+            .flat_map(|(member, score)| [member, database::format_float(score)])
+            .collect(),
+    )
+}
+
+/// Stream entries, each one being its ID followed by its fields and values.
+fn stream_entries(entries: Vec<StreamEntry>) -> Response {
+    Response::Array(
+        entries
+            .into_iter()
+            .map(|(id, fields)| stream_entry(id, Some(fields)))
+            .collect(),
+    )
+}
+
+/// Stream entry ID followed by its fields and values, or nil if it was removed.
+fn stream_entry(id: StreamId, fields: Option<Vec<(RedisString, RedisString)>>) -> Response {
+    let fields = match fields {
+        Some(fields) => contents(flatten_pairs(fields)),
+        None => Response::NoContent,
+    };
+    Response::Array(vec![Response::Content(id.into()), fields])
+}
+
+/// Claimed entries, or only their IDs.
+fn stream_claimed(claimed: Vec<StreamEntry>, justid: bool) -> Response {
+    if justid {
+        contents(claimed.into_iter().map(|(id, _)| id.into()).collect())
+    } else {
+        stream_entries(claimed)
+    }
+}
+
+/// ID of an entry in a claim response, either alone or followed by fields.
+fn response_id(item: &Response) -> Option<&[u8]> {
+    match item {
+        Response::Content(id) => Some(id.as_slice()),
+        Response::Array(entry) => match entry.first() {
+            Some(Response::Content(id)) => Some(id.as_slice()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Flat array of `XINFO` names and values.
+fn info_fields(fields: Vec<(&str, Response)>) -> Response {
+    Response::Array(
+        fields
+            .into_iter()
+            .flat_map(|(name, value)| [Response::Content(name.as_bytes().into()), value])
+            .collect(),
+    )
+}
+
+/// `XCLAIM` giving entries to a consumer whatever their idle time, or `None` without entries.
+fn xclaim_command(
+    key: &RedisString,
+    group: &RedisString,
+    consumer: &RedisString,
+    ids: &[&[u8]],
+    options: XClaimOptions,
+) -> Option<Message> {
+    if ids.is_empty() {
+        return None;
+    }
+    let mut args = vec![
+        b"XCLAIM".to_vec(),
+        key.as_slice().to_vec(),
+        group.as_slice().to_vec(),
+        consumer.as_slice().to_vec(),
+        b"0".to_vec(),
+    ];
+    args.extend(ids.iter().map(|id| id.to_vec()));
+    let numbers = [
+        (b"IDLE".as_slice(), options.idle),
+        (b"TIME", options.time),
+        (b"RETRYCOUNT", options.retry_count),
+    ];
+    for (option, value) in numbers {
+        if let Some(value) = value {
+            args.extend([option.to_vec(), value.to_string().into_bytes()]);
+        }
+    }
+    if options.force {
+        args.push(b"FORCE".to_vec());
+    }
+    if options.justid {
+        args.push(b"JUSTID".to_vec());
+    }
+    if let Some(last_id) = options.last_id {
+        args.extend([b"LASTID".to_vec(), last_id.to_string().into_bytes()]);
+    }
+    let args: Vec<_> = args.iter().map(Vec::as_slice).collect();
+    Some(Message::command(&args))
+}
+
+/// Non blocking `XREADGROUP` reading the same entries.
+fn xreadgroup_command(
+    group: &RedisString,
+    consumer: &RedisString,
+    streams: &[(RedisString, Option<StreamId>)],
+    count: Option<usize>,
+    noack: bool,
+) -> Message {
+    let mut args = vec![
+        b"XREADGROUP".to_vec(),
+        b"GROUP".to_vec(),
+        group.as_slice().to_vec(),
+        consumer.as_slice().to_vec(),
+    ];
+    if let Some(count) = count {
+        args.extend([b"COUNT".to_vec(), count.to_string().into_bytes()]);
+    }
+    if noack {
+        args.push(b"NOACK".to_vec());
+    }
+    args.push(b"STREAMS".to_vec());
+    args.extend(streams.iter().map(|(key, _)| key.as_slice().to_vec()));
+    args.extend(streams.iter().map(|(_, id)| match id {
+        Some(id) => id.to_string().into_bytes(),
+        None => b">".to_vec(),
+    }));
+    let args: Vec<_> = args.iter().map(Vec::as_slice).collect();
+    Message::command(&args)
+}
+
+fn stream_group_read_response(streams: Vec<(RedisString, Vec<GroupEntry>)>) -> Response {
+    Response::Array(
+        streams
+            .into_iter()
+            .map(|(key, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .map(|(id, fields)| stream_entry(id, fields))
+                    .collect();
+                Response::Array(vec![Response::Content(key), Response::Array(entries)])
+            })
+            .collect(),
+    )
+}
+
+/// Read entries of `XREAD`, streams read from `$` not returning any.
+async fn stream_read(
+    db: &Database,
+    streams: &[(RedisString, Option<StreamId>)],
+    count: Option<usize>,
+) -> Result<Vec<(RedisString, Vec<StreamEntry>)>, MiniRedisError> {
+    let streams: Vec<_> = streams
+        .iter()
+        .filter_map(|(key, id)| Some((key.clone(), (*id)?)))
+        .collect();
+    db.stream_read(&streams, count).await
+}
+
+fn stream_read_response(streams: Vec<(RedisString, Vec<StreamEntry>)>) -> Response {
+    Response::Array(
+        streams
+            .into_iter()
+            .map(|(key, entries)| {
+                Response::Array(vec![Response::Content(key), stream_entries(entries)])
+            })
+            .collect(),
+    )
+}
+
+/// `SET` command applying the same options, except `GET` which does not change the database.
+fn set_command(key: &RedisString, value: &RedisString, options: SetOptions) -> Message {
+    let mut args = vec![
+        b"SET".to_vec(),
+        key.as_slice().to_vec(),
+        value.as_slice().to_vec(),
+    ];
+    match options.condition {
+        Some(SetCondition::Missing) => args.push(b"NX".to_vec()),
+        Some(SetCondition::Existing) => args.push(b"XX".to_vec()),
+        None => {}
+    }
+    match options.expiry {
+        Some(SetExpiry::In(ms_delta)) => {
+            args.extend([b"PX".to_vec(), ms_delta.to_string().into_bytes()]);
+        }
+        Some(SetExpiry::At(expire_at)) => {
+            args.extend([b"PXAT".to_vec(), expire_at.to_string().into_bytes()]);
+        }
+        Some(SetExpiry::Keep) => args.push(b"KEEPTTL".to_vec()),
+        None => {}
+    }
+    let args: Vec<_> = args.iter().map(Vec::as_slice).collect();
+    Message::command(&args)
+}
+
+/// Unix time in milliseconds after a possibly negative delta from now.
+fn expire_at(ms_delta: i64) -> u64 {
+    let now = database::now_unix_millis();
+    match u64::try_from(ms_delta) {
+        Ok(ms_delta) => now.saturating_add(ms_delta),
+        Err(_) => now.saturating_sub(ms_delta.unsigned_abs()),
+    }
+}
+
+fn pexpireat_command(key: &RedisString, expire_at: u64, options: ExpireOptions) -> Message {
+    let expire_at = expire_at.to_string();
+    let mut args = vec![&b"PEXPIREAT"[..], key.as_slice(), expire_at.as_bytes()];
+    for (set, flag) in [
+        (options.nx, &b"NX"[..]),
+        (options.xx, b"XX"),
+        (options.gt, b"GT"),
+        (options.lt, b"LT"),
+    ] {
+        if set {
+            args.push(flag);
+        }
+    }
+    Message::command(&args)
+}
+
+/// Remaining time to live, or absolute expiry, of key: -2 if it does not exist, -1 if it has
+/// no expiry.
+async fn ttl_response(db: &Database, key: &RedisString, millis: bool, absolute: bool) -> Response {
+    let expire_at = match db.expire_time(key).await {
+        None => return Response::Integer(-2),
+        Some(None) => return Response::Integer(-1),
+        Some(Some(expire_at)) => expire_at,
+    };
+    let time = if absolute {
+        expire_at
+    } else {
+        expire_at.saturating_sub(database::now_unix_millis())
+    };
+    let time = if millis { time } else { (time + 500) / 1000 };
+    Response::Integer(time as i64)
+}
+
+/// Pop a single element, or an array of elements when count is given.
+async fn list_pop(
+    db: &Database,
+    key: &RedisString,
+    end: ListEnd,
+    count: Option<usize>,
+) -> Response {
+    let result = db.list_pop(key, end, count.unwrap_or(1)).await;
+    to_response(result, |elements| match (elements, count) {
+        (None, _) => Response::NoContent,
+        (Some(elements), None) => optional_content(elements.into_iter().next()),
+        (Some(elements), Some(_)) => contents(elements),
+    })
+}
+
+/// Reply of a blocking request which could not be served: nil for `BLMOVE`, which returns a
+/// single element, and a null array otherwise.
+fn not_served(request: &Request) -> Response {
+    match request {
+        Request::BLMove(..) => Response::NoContent,
+        _ => Response::NullArray,
+    }
+}
+
+fn db_index_out_of_range() -> Response {
+    MiniRedisError::DbIndexOutOfRange.into()
+}
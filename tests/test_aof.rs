@@ -0,0 +1,163 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+};
+
+use redis_starter_rust::{
+    aof::{self, Aof, AofContent, FsyncPolicy},
+    database::Database,
+    error::MiniRedisError,
+    rdb::{RdbDatabase, RedisString},
+    resp2::Message,
+};
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    env::temp_dir().join(format!("test-{name}-{}.aof", std::process::id()))
+}
+
+#[test]
+fn test_fsync_policy() {
+    assert_eq!(FsyncPolicy::parse(b"always"), Ok(FsyncPolicy::Always));
+    assert_eq!(FsyncPolicy::parse(b"EverySec"), Ok(FsyncPolicy::EverySec));
+    assert_eq!(FsyncPolicy::parse(b"no"), Ok(FsyncPolicy::No));
+    assert_eq!(
+        FsyncPolicy::parse(b"never"),
+        Err(MiniRedisError::InvalidConfig(
+            "Invalid appendfsync: never".to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_append_and_read() {
+    let path = temp_path("append");
+    let aof = Aof::new();
+
+    // Nothing is written while disabled
+    aof.append(0, &Message::command(&[b"SET", b"foo", b"bar"]))
+        .await
+        .unwrap();
+    assert!(!path.exists());
+
+    aof.open(&path).await.unwrap();
+    assert!(aof.is_enabled().await);
+    aof.append(0, &Message::command(&[b"SET", b"foo", b"bar"]))
+        .await
+        .unwrap();
+    aof.append(0, &Message::command(&[b"SET", b"foo", b"baz"]))
+        .await
+        .unwrap();
+    aof.append(1, &Message::command(&[b"SET", b"k2", b"v2"]))
+        .await
+        .unwrap();
+    aof.disable().await.unwrap();
+
+    assert_eq!(
+        aof::read_aof_file(&path).await,
+        Ok(AofContent {
+            commands: vec![
+                Message::command(&[b"SELECT", b"0"]),
+                Message::command(&[b"SET", b"foo", b"bar"]),
+                Message::command(&[b"SET", b"foo", b"baz"]),
+                Message::command(&[b"SELECT", b"1"]),
+                Message::command(&[b"SET", b"k2", b"v2"]),
+            ],
+            valid_len: 137,
+            truncated: false,
+        })
+    );
+
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_read_truncated() {
+    let path = temp_path("truncated");
+
+    fs::write(&path, b"*1\r\n$4\r\nPING\r\n*3\r\n$3\r\nSET\r\n$1\r\nc").unwrap();
+    assert_eq!(
+        aof::read_aof_file(&path).await,
+        Ok(AofContent {
+            commands: vec![Message::command(&[b"PING"])],
+            valid_len: 14,
+            truncated: true,
+        })
+    );
+
+    // Corruption is not a truncation
+    fs::write(&path, b"*1\r\n$4\r\nPING\r\n!3\r\n").unwrap();
+    assert_eq!(
+        aof::read_aof_file(&path).await,
+        Err(MiniRedisError::InvalidMessageType('!'))
+    );
+
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_write_dataset() {
+    let databases = BTreeMap::from([(
+        3,
+        RdbDatabase {
+            values: HashMap::from([(RedisString::new(b"foo"), RedisString::new(b"bar").into())]),
+            expiry: HashMap::from([(RedisString::new(b"foo"), 1703081197600)]),
+        },
+    )]);
+
+    let mut output = Vec::new();
+    aof::write_dataset(&mut output, &databases).await.unwrap();
+    assert_eq!(
+        output,
+        b"*2\r\n$6\r\nSELECT\r\n$1\r\n3\r\n\
+        *3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n\
+        *3\r\n$9\r\nPEXPIREAT\r\n$3\r\nfoo\r\n$13\r\n1703081197600\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_bgrewrite() {
+    let path = temp_path("rewrite");
+    let databases = [Database::new()];
+    let database = &databases[0];
+    let aof = Aof::new();
+
+    aof.enable(&path, &databases).await.unwrap();
+    for value in [b"1", b"2", b"3"] {
+        database.set(b"foo", value).await;
+        aof.append(0, &Message::command(&[b"SET", b"foo", value]))
+            .await
+            .unwrap();
+    }
+    assert_eq!(aof.current_size(), 110);
+    assert!(!aof.should_rewrite());
+    aof.set_auto_rewrite_min_size(50);
+    assert!(aof.should_rewrite());
+
+    // Write arriving while rewrite is running are kept
+    let handle = aof.bgrewrite(path.clone(), &databases).await.unwrap();
+    assert!(aof.bgrewrite(path.clone(), &databases).await.is_none());
+    aof.append(0, &Message::command(&[b"SET", b"k2", b"v2"]))
+        .await
+        .unwrap();
+    handle.await.unwrap().unwrap();
+
+    assert!(!aof.is_rewrite_in_progress().await);
+    assert_eq!(
+        aof::read_aof_file(&path).await.unwrap().commands,
+        vec![
+            Message::command(&[b"SELECT", b"0"]),
+            Message::command(&[b"SET", b"foo", b"3"]),
+            Message::command(&[b"SELECT", b"0"]),
+            Message::command(&[b"SET", b"k2", b"v2"]),
+        ]
+    );
+    assert_eq!(aof.current_size(), 104);
+    assert!(!aof.should_rewrite());
+
+    // New file is used after rewrite
+    aof.append(0, &Message::command(&[b"PING"])).await.unwrap();
+    assert_eq!(aof::read_aof_file(&path).await.unwrap().commands.len(), 5);
+
+    aof.disable().await.unwrap();
+    fs::remove_file(path).unwrap();
+}
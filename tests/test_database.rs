@@ -1,21 +1,36 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
-use redis_starter_rust::{database::Database, rdb::RedisString};
+use redis_starter_rust::{
+    database::{
+        glob_match, BitFieldOp, BitFieldType, BitOperation, BitOverflow, BitUnit, Database,
+        LcsMatch, LexBound, ListEnd, PendingRange, ScanOptions, Score, ScoreBound, SetOperation,
+        SortedSet, StreamId, StreamTrim, StreamTrimOptions, XAddId, XAddOptions, XClaimOptions,
+        ZAddOptions, ZRange, ZRangeBy, MAX_RANDOM_SAMPLE,
+    },
+    error::MiniRedisError,
+    rdb::RedisString,
+};
 
 #[tokio::test]
 async fn test_database_get_set() {
     let database = Database::new();
 
     // Check invalid
-    assert_eq!(database.get(b"foo").await, None);
+    assert_eq!(database.get(b"foo").await, Ok(None));
 
     // Set and get
     database.set(b"foo", b"hello").await;
-    assert_eq!(database.get(b"foo").await, Some(RedisString::new(b"hello")));
+    assert_eq!(
+        database.get(b"foo").await,
+        Ok(Some(RedisString::new(b"hello")))
+    );
 
     // Update and get
     database.set(b"foo", b"world").await;
-    assert_eq!(database.get(b"foo").await, Some(RedisString::new(b"world")));
+    assert_eq!(
+        database.get(b"foo").await,
+        Ok(Some(RedisString::new(b"world")))
+    );
 }
 
 #[tokio::test]
@@ -23,14 +38,1368 @@ async fn test_database_expire_in() {
     let database = Database::new();
 
     // Check invalid
-    assert_eq!(database.get(b"foo").await, None);
+    assert_eq!(database.get(b"foo").await, Ok(None));
 
     // Set and get
     database.set(b"foo", b"hello").await;
     database.expire_in_millis(b"foo", 100).await;
-    assert_eq!(database.get(b"foo").await, Some(RedisString::new(b"hello")));
+    assert_eq!(
+        database.get(b"foo").await,
+        Ok(Some(RedisString::new(b"hello")))
+    );
 
     // Wait and get
     tokio::time::sleep(Duration::from_millis(250)).await;
-    assert_eq!(database.get(b"foo").await, None);
+    assert_eq!(database.get(b"foo").await, Ok(None));
+}
+
+#[tokio::test]
+async fn test_database_remove_swap_clear() {
+    let database = Database::new();
+    let other = Database::new();
+
+    database.set(b"foo", b"hello").await;
+    database.expire_at_millis(b"foo", u64::MAX).await;
+    assert_eq!(
+        database.remove(b"foo").await,
+        Some((RedisString::new(b"hello").into(), Some(u64::MAX)))
+    );
+    assert_eq!(database.remove(b"foo").await, None);
+
+    // Swap content
+    database.set(b"foo", b"hello").await;
+    other.set(b"k2", b"v2").await;
+    database.swap(&other).await;
+    assert_eq!(database.keys().await, vec![RedisString::new(b"k2")]);
+    assert_eq!(other.keys().await, vec![RedisString::new(b"foo")]);
+
+    assert_eq!(other.clear().await, 1);
+    assert_eq!(other.get(b"foo").await, Ok(None));
+}
+
+#[tokio::test]
+async fn test_database_changes() {
+    let database = Database::new();
+    let key = |name: &[u8]| RedisString::new(name);
+    let element = key(b"a");
+
+    // Commands leaving values untouched are not changes
+    assert_eq!(
+        database.list_pop(&key(b"list"), ListEnd::Left, 1).await,
+        Ok(None)
+    );
+    assert_eq!(
+        database.list_remove(&key(b"list"), 0, &element).await,
+        Ok(0)
+    );
+    assert_eq!(database.set_remove(&key(b"set"), &[key(b"a")]).await, Ok(0));
+    assert_eq!(database.changes(), 0);
+
+    database
+        .list_push(&key(b"list"), ListEnd::Left, vec![element.clone()])
+        .await
+        .unwrap();
+    assert_eq!(
+        database.list_remove(&key(b"list"), 0, &key(b"b")).await,
+        Ok(0)
+    );
+    assert_eq!(database.changes(), 1);
+    assert_eq!(
+        database.list_remove(&key(b"list"), 0, &element).await,
+        Ok(1)
+    );
+    assert_eq!(database.changes(), 2);
+}
+
+#[tokio::test]
+async fn test_database_list() {
+    let database = Database::new();
+    let key = RedisString::new(b"list");
+    let strings = |values: &[&[u8]]| -> Vec<RedisString> {
+        values.iter().map(|x| RedisString::new(x)).collect()
+    };
+
+    assert_eq!(
+        database
+            .list_push(&key, ListEnd::Right, strings(&[b"b", b"c"]))
+            .await,
+        Ok(2)
+    );
+    assert_eq!(
+        database
+            .list_push(&key, ListEnd::Left, strings(&[b"a", b"z"]))
+            .await,
+        Ok(4)
+    );
+    assert_eq!(
+        database.list_range(&key, 0, -1).await,
+        Ok(strings(&[b"z", b"a", b"b", b"c"]))
+    );
+    assert_eq!(
+        database.list_range(&key, -2, 100).await,
+        Ok(strings(&[b"b", b"c"]))
+    );
+    assert_eq!(database.list_range(&key, 3, 1).await, Ok(vec![]));
+    assert_eq!(
+        database.list_index(&key, -1).await,
+        Ok(Some(RedisString::new(b"c")))
+    );
+    assert_eq!(database.list_index(&key, 4).await, Ok(None));
+
+    // Update elements
+    assert_eq!(
+        database.list_set(&key, 0, RedisString::new(b"b")).await,
+        Ok(())
+    );
+    assert_eq!(
+        database.list_set(&key, 9, RedisString::new(b"b")).await,
+        Err(MiniRedisError::IndexOutOfRange)
+    );
+    assert_eq!(
+        database
+            .list_insert(&key, false, &RedisString::new(b"a"), RedisString::new(b"b"))
+            .await,
+        Ok(5)
+    );
+    assert_eq!(
+        database
+            .list_remove(&key, -2, &RedisString::new(b"b"))
+            .await,
+        Ok(2)
+    );
+    assert_eq!(
+        database.list_range(&key, 0, -1).await,
+        Ok(strings(&[b"b", b"a", b"c"]))
+    );
+    assert_eq!(database.list_trim(&key, 1, -1).await, Ok(()));
+
+    // Move between lists
+    let other = RedisString::new(b"other");
+    assert_eq!(
+        database
+            .list_move(&key, &other, ListEnd::Right, ListEnd::Left)
+            .await,
+        Ok(Some(RedisString::new(b"c")))
+    );
+    assert_eq!(database.list_len(&other).await, Ok(1));
+
+    // Empty lists are removed
+    assert_eq!(
+        database.list_pop(&key, ListEnd::Left, 5).await,
+        Ok(Some(strings(&[b"a"])))
+    );
+    assert!(!database.exists(key.clone()).await);
+    assert_eq!(database.list_pop(&key, ListEnd::Left, 1).await, Ok(None));
+
+    // Type is checked
+    database.set(b"string", b"hello").await;
+    assert_eq!(
+        database.list_len(&RedisString::new(b"string")).await,
+        Err(MiniRedisError::WrongType)
+    );
+    assert_eq!(database.get(other).await, Err(MiniRedisError::WrongType));
+}
+
+#[tokio::test]
+async fn test_database_list_waiters() {
+    let database = Database::new();
+    let key = RedisString::new(b"list");
+
+    let first = database.wait_keys(vec![key.clone()]);
+    let second = database.wait_keys(vec![RedisString::new(b"other"), key.clone()]);
+    assert_eq!(first.pop(ListEnd::Left).await, Ok(None));
+
+    // Oldest waiter is woken and served first
+    database
+        .list_push(&key, ListEnd::Right, vec![RedisString::new(b"a")])
+        .await
+        .unwrap();
+    first.notified().await;
+    assert_eq!(second.pop(ListEnd::Left).await, Ok(None));
+    assert_eq!(
+        first.pop(ListEnd::Left).await,
+        Ok(Some((key.clone(), RedisString::new(b"a"))))
+    );
+
+    // Dropped waiter lets next one take its element
+    database
+        .list_push(&key, ListEnd::Right, vec![RedisString::new(b"b")])
+        .await
+        .unwrap();
+    drop(first);
+    second.notified().await;
+    assert_eq!(
+        second
+            .move_to(&RedisString::new(b"dst"), ListEnd::Left, ListEnd::Left)
+            .await,
+        Ok(None)
+    );
+    assert_eq!(
+        second.pop(ListEnd::Right).await,
+        Ok(Some((key, RedisString::new(b"b"))))
+    );
+}
+
+#[tokio::test]
+async fn test_database_hash() {
+    let database = Database::new();
+    let key = RedisString::new(b"hash");
+    let field = |name: &[u8]| RedisString::new(name);
+
+    assert_eq!(
+        database
+            .hash_set(
+                &key,
+                vec![(field(b"a"), field(b"1")), (field(b"b"), field(b"x"))]
+            )
+            .await,
+        Ok(2)
+    );
+    assert_eq!(
+        database.hash_set_nx(&key, field(b"a"), field(b"2")).await,
+        Ok(false)
+    );
+    assert_eq!(
+        database
+            .hash_get_many(&key, &[field(b"a"), field(b"c")])
+            .await,
+        Ok(vec![Some(field(b"1")), None])
+    );
+    assert_eq!(database.hash_strlen(&key, &field(b"b")).await, Ok(1));
+
+    // Increments
+    assert_eq!(database.hash_incr_by(&key, field(b"a"), 41).await, Ok(42));
+    assert_eq!(
+        database.hash_incr_by(&key, field(b"b"), 1).await,
+        Err(MiniRedisError::HashValueNotInteger)
+    );
+    database
+        .hash_set(&key, vec![(field(b"p"), field(b"+1"))])
+        .await
+        .unwrap();
+    assert_eq!(
+        database.hash_incr_by(&key, field(b"p"), 1).await,
+        Err(MiniRedisError::HashValueNotInteger)
+    );
+    database.hash_delete(&key, &[field(b"p")]).await.unwrap();
+    assert_eq!(
+        database.hash_incr_by_float(&key, field(b"f"), 2.5).await,
+        Ok(field(b"2.5"))
+    );
+    assert_eq!(
+        database.hash_incr_by_float(&key, field(b"f"), 0.5).await,
+        Ok(field(b"3"))
+    );
+
+    // Random fields
+    assert_eq!(
+        database.hash_random_fields(&key, 10).await.unwrap().len(),
+        3
+    );
+    assert_eq!(
+        database.hash_random_fields(&key, -10).await.unwrap().len(),
+        10
+    );
+    assert_eq!(
+        database
+            .hash_random_fields(&key, -10_000_000_000)
+            .await
+            .unwrap()
+            .len(),
+        MAX_RANDOM_SAMPLE
+    );
+
+    // Empty hashes are removed
+    assert_eq!(
+        database
+            .hash_delete(&key, &[field(b"a"), field(b"b"), field(b"f"), field(b"z")])
+            .await,
+        Ok(3)
+    );
+    assert!(!database.exists(key.clone()).await);
+    assert_eq!(database.hash_len(&key).await, Ok(0));
+}
+
+#[tokio::test]
+async fn test_database_set() {
+    let database = Database::new();
+    let strings = |values: &[&[u8]]| -> Vec<RedisString> {
+        values.iter().map(|x| RedisString::new(x)).collect()
+    };
+    let sorted = |mut values: Vec<RedisString>| {
+        values.sort();
+        values
+    };
+    let (a, b, c) = (
+        RedisString::new(b"a"),
+        RedisString::new(b"b"),
+        RedisString::new(b"c"),
+    );
+
+    assert_eq!(
+        database.set_add(&a, strings(&[b"1", b"2", b"3"])).await,
+        Ok(3)
+    );
+    assert_eq!(
+        database.set_add(&b, strings(&[b"2", b"3", b"4"])).await,
+        Ok(3)
+    );
+    assert_eq!(
+        database.set_contains(&a, &strings(&[b"1", b"4"])).await,
+        Ok(vec![true, false])
+    );
+
+    // Combinations
+    let keys = [a.clone(), b.clone()];
+    assert_eq!(
+        database
+            .set_combine(SetOperation::Intersection, &keys)
+            .await
+            .map(sorted),
+        Ok(strings(&[b"2", b"3"]))
+    );
+    assert_eq!(
+        database
+            .set_combine(SetOperation::Difference, &keys)
+            .await
+            .map(sorted),
+        Ok(strings(&[b"1"]))
+    );
+    assert_eq!(
+        database
+            .set_combine_store(SetOperation::Union, &c, &keys)
+            .await,
+        Ok(4)
+    );
+    assert_eq!(database.set_intersection_len(&keys, 1).await, Ok(1));
+
+    // Random members
+    assert_eq!(
+        database.set_random_members(&a, 5).await.map(sorted),
+        Ok(strings(&[b"1", b"2", b"3"]))
+    );
+    assert_eq!(
+        database
+            .set_random_members(&a, -10_000_000_000)
+            .await
+            .unwrap()
+            .len(),
+        MAX_RANDOM_SAMPLE
+    );
+
+    // Move and pop
+    assert_eq!(
+        database.set_move(&a, &b, RedisString::new(b"1")).await,
+        Ok(true)
+    );
+    assert_eq!(database.set_len(&b).await, Ok(4));
+    let d = RedisString::new(b"d");
+    database.set_add(&d, strings(&[b"only"])).await.unwrap();
+    database.expire_at_millis(d.clone(), u64::MAX).await;
+    assert_eq!(
+        database.set_move(&d, &d, RedisString::new(b"only")).await,
+        Ok(true)
+    );
+    assert_eq!(
+        database.set_move(&d, &d, RedisString::new(b"other")).await,
+        Ok(false)
+    );
+    assert_eq!(database.expire_time(&d).await, Some(Some(u64::MAX)));
+    assert_eq!(
+        database.set_pop(&c, 10).await.map(sorted),
+        Ok(strings(&[b"1", b"2", b"3", b"4"]))
+    );
+    assert!(!database.exists(c.clone()).await);
+
+    // Type is checked
+    database.set(b"string", b"hello").await;
+    assert_eq!(
+        database
+            .set_combine(SetOperation::Union, &[a, RedisString::new(b"string")])
+            .await,
+        Err(MiniRedisError::WrongType)
+    );
+}
+
+#[test]
+fn test_sorted_set_order() {
+    let mut zset = SortedSet::new();
+    let mut expected: Vec<(i64, RedisString)> = Vec::new();
+
+    // Pseudo random operations checked against a sorted vector
+    let mut seed = 42_u64;
+    for _ in 0..2000 {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        let member = RedisString::from_integer((seed >> 33) as i64 % 200);
+        let score = (seed >> 20) as i64 % 50;
+        expected.retain(|(_, other)| *other != member);
+        match seed % 3 {
+            0 => {
+                zset.remove(&member);
+            }
+            _ => {
+                zset.insert(member.clone(), score as f64);
+                expected.push((score, member));
+            }
+        }
+    }
+    expected.sort();
+
+    assert_eq!(zset.len(), expected.len());
+    for (rank, (_, member)) in expected.iter().enumerate() {
+        assert_eq!(zset.rank(member), Some(rank));
+    }
+    let all = ZRange {
+        by: ZRangeBy::Rank(0, -1),
+        rev: false,
+        limit: None,
+    };
+    assert_eq!(
+        zset.range(&all),
+        expected
+            .iter()
+            .map(|(score, member)| (member.clone(), *score as f64))
+            .collect::<Vec<_>>()
+    );
+
+    // Score ranges
+    let by = ZRangeBy::Score(
+        ScoreBound::Exclusive(Score(10.0)),
+        ScoreBound::Inclusive(Score(20.0)),
+    );
+    let in_range: Vec<_> = expected
+        .iter()
+        .filter(|(score, _)| *score > 10 && *score <= 20)
+        .map(|(score, member)| (member.clone(), *score as f64))
+        .collect();
+    assert_eq!(zset.count(&by), in_range.len());
+    let range = ZRange {
+        by,
+        rev: true,
+        limit: Some((2, 3)),
+    };
+    let mut reversed = in_range.clone();
+    reversed.reverse();
+    assert_eq!(zset.range(&range), reversed[2..5].to_vec());
+}
+
+#[tokio::test]
+async fn test_database_sorted_set() {
+    let database = Database::new();
+    let key = RedisString::new(b"zset");
+    let member = |name: &[u8]| RedisString::new(name);
+    let members = vec![
+        (Score(1.0), member(b"a")),
+        (Score(2.0), member(b"b")),
+        (Score(2.0), member(b"c")),
+    ];
+
+    assert_eq!(
+        database
+            .zset_add(&key, ZAddOptions::default(), false, members)
+            .await,
+        Ok(3)
+    );
+
+    // Conditional updates
+    let gt = ZAddOptions {
+        gt: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        database
+            .zset_add(
+                &key,
+                gt,
+                true,
+                vec![(Score(0.0), member(b"b")), (Score(5.0), member(b"c"))]
+            )
+            .await,
+        Ok(1)
+    );
+    assert_eq!(
+        database
+            .zset_incr(&key, gt, Score(-1.0), member(b"a"))
+            .await,
+        Ok(None)
+    );
+    assert_eq!(
+        database
+            .zset_incr(
+                &key,
+                ZAddOptions::default(),
+                Score(f64::INFINITY),
+                member(b"a")
+            )
+            .await,
+        Ok(Some(f64::INFINITY))
+    );
+    assert_eq!(
+        database
+            .zset_incr(
+                &key,
+                ZAddOptions::default(),
+                Score(f64::NEG_INFINITY),
+                member(b"a")
+            )
+            .await,
+        Err(MiniRedisError::ScoreNan)
+    );
+    assert_eq!(
+        database.zset_rank(&key, &member(b"a"), true).await,
+        Ok(Some((0, f64::INFINITY)))
+    );
+
+    // Lexicographical range among members with the same score
+    let by = ZRangeBy::Lex(LexBound::Exclusive(member(b"a")), LexBound::Max);
+    database
+        .zset_add(
+            &key,
+            ZAddOptions::default(),
+            false,
+            vec![(Score(0.0), member(b"a")), (Score(0.0), member(b"d"))],
+        )
+        .await
+        .unwrap();
+    let lex_key = RedisString::new(b"lex");
+    database
+        .zset_add(
+            &lex_key,
+            ZAddOptions::default(),
+            false,
+            vec![(Score(0.0), member(b"a")), (Score(0.0), member(b"b"))],
+        )
+        .await
+        .unwrap();
+    assert_eq!(database.zset_count(&lex_key, &by).await, Ok(1));
+
+    // Pop and store
+    assert_eq!(
+        database.zset_pop(&key, 1, true).await,
+        Ok(vec![(member(b"c"), 5.0)])
+    );
+    let range = ZRange {
+        by: ZRangeBy::Rank(0, 1),
+        rev: false,
+        limit: None,
+    };
+    let other = RedisString::new(b"other");
+    assert_eq!(database.zset_range_store(&other, &key, &range).await, Ok(2));
+    assert_eq!(
+        database.zset_range(&other, &range).await,
+        Ok(vec![(member(b"a"), 0.0), (member(b"d"), 0.0)])
+    );
+    assert_eq!(
+        database
+            .zset_remove(&other, &[member(b"a"), member(b"d")])
+            .await,
+        Ok(2)
+    );
+    assert!(!database.exists(other).await);
+}
+
+#[tokio::test]
+async fn test_database_stream() {
+    let database = Database::new();
+    let key = RedisString::new(b"stream");
+    let id = |ms, seq| StreamId { ms, seq };
+    let fields = |value: &[u8]| vec![(RedisString::new(b"field"), RedisString::new(value))];
+    let add = |xadd_id, value: &'static [u8]| {
+        database.stream_add(&key, XAddOptions::default(), xadd_id, fields(value))
+    };
+
+    // Invalid IDs do not create stream
+    assert_eq!(
+        add(XAddId::Explicit(id(0, 0)), b"a").await,
+        Err(MiniRedisError::StreamIdZero)
+    );
+    assert_eq!(database.type_name(&key).await, None);
+
+    // IDs must increase
+    assert_eq!(add(XAddId::AutoSeq(0), b"a").await, Ok(Some(id(0, 1))));
+    assert_eq!(
+        add(XAddId::Explicit(id(1, 5)), b"b").await,
+        Ok(Some(id(1, 5)))
+    );
+    assert_eq!(add(XAddId::AutoSeq(1), b"c").await, Ok(Some(id(1, 6))));
+    assert_eq!(
+        add(XAddId::Explicit(id(1, 6)), b"d").await,
+        Err(MiniRedisError::StreamIdTooSmall)
+    );
+    assert_eq!(
+        add(XAddId::AutoSeq(0), b"d").await,
+        Err(MiniRedisError::StreamIdTooSmall)
+    );
+    let auto = add(XAddId::Auto, b"d").await.unwrap().unwrap();
+    assert!(auto > id(1, 6));
+    assert_eq!(database.stream_len(&key).await, Ok(4));
+    assert_eq!(database.type_name(&key).await, Some("stream"));
+
+    // Ranges
+    let ids = |entries: Vec<(StreamId, _)>| -> Vec<StreamId> {
+        entries.into_iter().map(|(id, _)| id).collect()
+    };
+    let range = database
+        .stream_range(&key, id(1, 0), StreamId::MAX, Some(2), false)
+        .await;
+    assert_eq!(range.map(ids), Ok(vec![id(1, 5), id(1, 6)]));
+    let range = database
+        .stream_range(&key, StreamId::MIN, id(1, 5), None, true)
+        .await;
+    assert_eq!(range.map(ids), Ok(vec![id(1, 5), id(0, 1)]));
+    assert_eq!(
+        database
+            .stream_range(&key, id(1, 6), id(1, 6), None, false)
+            .await,
+        Ok(vec![(id(1, 6), fields(b"c"))])
+    );
+
+    // Reading after IDs
+    let read = database
+        .stream_read(
+            &[
+                (key.clone(), id(1, 5)),
+                (RedisString::new(b"missing"), StreamId::MIN),
+            ],
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(read.len(), 1);
+    assert_eq!(ids(read[0].1.clone()), vec![id(1, 6), auto]);
+    assert_eq!(
+        database.stream_read(&[(key.clone(), auto)], None).await,
+        Ok(vec![])
+    );
+
+    // Removing entries keeps last ID
+    assert_eq!(database.stream_delete(&key, &[auto, id(9, 9)]).await, Ok(1));
+    assert_eq!(
+        database
+            .stream_trim(&key, StreamTrim::MinId(id(1, 0)).into())
+            .await,
+        Ok(1)
+    );
+    assert_eq!(
+        database
+            .stream_trim(&key, StreamTrim::MaxLen(0).into())
+            .await,
+        Ok(2)
+    );
+    assert_eq!(database.stream_len(&key).await, Ok(0));
+    assert_eq!(database.type_name(&key).await, Some("stream"));
+    assert_eq!(database.stream_last_id(&key).await, Ok(auto));
+    assert_eq!(
+        add(XAddId::Explicit(auto), b"e").await,
+        Err(MiniRedisError::StreamIdTooSmall)
+    );
+
+    // Trimming while adding, and not creating missing streams
+    let options = XAddOptions {
+        nomkstream: false,
+        trim: Some(StreamTrim::MaxLen(1).into()),
+    };
+    let other = RedisString::new(b"other");
+    for seq in 1..=3 {
+        let added = database
+            .stream_add(&other, options, XAddId::Explicit(id(2, seq)), fields(b"x"))
+            .await;
+        assert_eq!(added, Ok(Some(id(2, seq))));
+    }
+    assert_eq!(database.stream_len(&other).await, Ok(1));
+    let options = XAddOptions {
+        nomkstream: true,
+        trim: None,
+    };
+    let missing = RedisString::new(b"missing");
+    assert_eq!(
+        database
+            .stream_add(&missing, options, XAddId::Auto, fields(b"x"))
+            .await,
+        Ok(None)
+    );
+    assert!(!database.exists(missing.clone()).await);
+
+    // Approximate trimming only removes whole nodes, up to its limit
+    let approx = RedisString::new(b"approx");
+    for seq in 1..=250 {
+        database
+            .stream_add(
+                &approx,
+                XAddOptions::default(),
+                XAddId::Explicit(id(1, seq)),
+                fields(b"x"),
+            )
+            .await
+            .unwrap();
+    }
+    let mut trim = StreamTrimOptions {
+        trim: StreamTrim::MaxLen(10),
+        approximate: true,
+        limit: 150,
+    };
+    assert_eq!(database.stream_trim(&approx, trim).await, Ok(100));
+    trim.limit = 0;
+    assert_eq!(database.stream_trim(&approx, trim).await, Ok(100));
+    assert_eq!(database.stream_len(&approx).await, Ok(50));
+
+    // Restoring metadata
+    assert_eq!(
+        database.stream_set_id(&other, id(2, 2), None, None).await,
+        Err(MiniRedisError::StreamSetIdTooSmall)
+    );
+    assert_eq!(
+        database
+            .stream_set_id(&other, id(3, 0), Some(0), None)
+            .await,
+        Err(MiniRedisError::StreamEntriesAddedTooSmall)
+    );
+    assert_eq!(
+        database
+            .stream_set_id(&other, id(3, 0), Some(5), Some(id(3, 1)))
+            .await,
+        Err(MiniRedisError::StreamMaxDeletedIdTooBig)
+    );
+    assert_eq!(
+        database.stream_set_id(&missing, id(3, 0), None, None).await,
+        Err(MiniRedisError::NoSuchKey)
+    );
+    assert_eq!(
+        database
+            .stream_set_id(&other, id(3, 0), Some(5), Some(id(2, 2)))
+            .await,
+        Ok(())
+    );
+    assert_eq!(database.stream_last_id(&other).await, Ok(id(3, 0)));
+
+    // Wrong type
+    database.set(b"string", b"value").await;
+    assert_eq!(
+        database.stream_len(&RedisString::new(b"string")).await,
+        Err(MiniRedisError::WrongType)
+    );
+}
+
+#[tokio::test]
+async fn test_database_stream_groups() {
+    let database = Database::new();
+    let key = RedisString::new(b"stream");
+    let group = RedisString::new(b"group");
+    let (alice, bob) = (RedisString::new(b"alice"), RedisString::new(b"bob"));
+    let id = |ms, seq| StreamId { ms, seq };
+    let fields = vec![(RedisString::new(b"field"), RedisString::new(b"value"))];
+
+    // Group requires stream unless created with it
+    assert_eq!(
+        database
+            .stream_group_create(&key, &group, None, false, None)
+            .await,
+        Err(MiniRedisError::StreamKeyRequired)
+    );
+    assert_eq!(
+        database
+            .stream_group_create(&key, &group, None, true, None)
+            .await,
+        Ok(())
+    );
+    assert_eq!(
+        database
+            .stream_group_create(&key, &group, None, true, None)
+            .await,
+        Err(MiniRedisError::BusyGroup)
+    );
+    for seq in 1..=3 {
+        let xadd_id = XAddId::Explicit(id(1, seq));
+        let added = database
+            .stream_add(&key, XAddOptions::default(), xadd_id, fields.clone())
+            .await;
+        assert_eq!(added, Ok(Some(id(1, seq))));
+    }
+
+    // New entries are delivered once and become pending
+    let streams = [(key.clone(), None)];
+    let read = database
+        .stream_read_group(&group, &alice, &streams, Some(2), false)
+        .await
+        .unwrap();
+    let ids: Vec<_> = read[0].1.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, vec![id(1, 1), id(1, 2)]);
+    let read = database
+        .stream_read_group(&group, &bob, &streams, None, false)
+        .await
+        .unwrap();
+    assert_eq!(read[0].1, vec![(id(1, 3), Some(fields.clone()))]);
+    assert_eq!(
+        database
+            .stream_read_group(&group, &bob, &streams, None, false)
+            .await,
+        Ok(vec![])
+    );
+
+    // History only returns entries pending for the consumer, removed ones without fields
+    assert_eq!(database.stream_delete(&key, &[id(1, 2)]).await, Ok(1));
+    let history = [(key.clone(), Some(StreamId::MIN))];
+    let read = database
+        .stream_read_group(&group, &alice, &history, None, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        read[0].1,
+        vec![(id(1, 1), Some(fields.clone())), (id(1, 2), None)]
+    );
+    let summary = database.stream_pending_summary(&key, &group).await;
+    assert_eq!(
+        summary,
+        Ok((
+            3,
+            Some((id(1, 1), id(1, 3))),
+            vec![(alice.clone(), 2), (bob.clone(), 1)]
+        ))
+    );
+
+    // Claiming moves entries and drops removed ones
+    let claimed = database
+        .stream_claim(
+            &key,
+            &group,
+            &bob,
+            0,
+            &[id(1, 1), id(1, 2)],
+            XClaimOptions::default(),
+        )
+        .await;
+    assert_eq!(claimed, Ok(vec![(id(1, 1), fields.clone())]));
+    let range = PendingRange {
+        min_idle: None,
+        start: StreamId::MIN,
+        end: StreamId::MAX,
+        count: 10,
+        consumer: Some(bob.clone()),
+    };
+    let pending = database.stream_pending(&key, &group, &range).await.unwrap();
+    let pending: Vec<_> = pending
+        .into_iter()
+        .map(|(id, consumer, _, count)| (id, consumer, count))
+        .collect();
+    assert_eq!(
+        pending,
+        vec![(id(1, 1), bob.clone(), 2), (id(1, 3), bob.clone(), 1)]
+    );
+
+    // Idle entries are claimed page by page
+    let claimed = database
+        .stream_auto_claim(&key, &group, &alice, 0, StreamId::MIN, 1, true)
+        .await;
+    assert_eq!(
+        claimed,
+        Ok((id(1, 3), vec![(id(1, 1), fields.clone())], vec![]))
+    );
+    assert_eq!(
+        database
+            .stream_ack(&key, &group, &[id(1, 1), id(1, 2)])
+            .await,
+        Ok(1)
+    );
+    assert_eq!(
+        database.stream_consumer_delete(&key, &group, &bob).await,
+        Ok(1)
+    );
+
+    let groups = database.stream_groups_info(&key).await.unwrap();
+    assert_eq!((groups[0].pending, groups[0].consumers), (0, 1));
+    assert_eq!((groups[0].entries_read, groups[0].lag), (Some(3), Some(0)));
+    assert_eq!(database.stream_group_destroy(&key, &group).await, Ok(true));
+    assert_eq!(
+        database
+            .stream_read_group(&group, &alice, &streams, None, false)
+            .await,
+        Err(MiniRedisError::NoGroup {
+            key: "stream".to_string(),
+            group: "group".to_string()
+        })
+    );
+}
+
+#[test]
+fn test_glob_match() {
+    for (pattern, string, expected) in [
+        (&b"*"[..], &b""[..], true),
+        (b"user:*", b"user:42", true),
+        (b"user:*", b"users", false),
+        (b"h?llo", b"hello", true),
+        (b"h?llo", b"hllo", false),
+        (b"h*llo", b"heeeello", true),
+        (b"*a*b", b"xaxxbab", true),
+        (b"*a*b", b"xaxxba", false),
+        (b"h[ae]llo", b"hallo", true),
+        (b"h[ae]llo", b"hillo", false),
+        (b"h[^e]llo", b"hallo", true),
+        (b"h[^e]llo", b"hello", false),
+        (b"h[a-b]llo", b"hbllo", true),
+        (b"h[b-a]llo", b"hallo", true),
+        (b"h[a-b]llo", b"hcllo", false),
+        (b"h\\*llo", b"h*llo", true),
+        (b"h\\*llo", b"hello", false),
+        (b"[\\]]", b"]", true),
+        (b"[abc", b"b", true),
+        (b"abc\\", b"abc\\", true),
+    ] {
+        assert_eq!(
+            glob_match(pattern, string),
+            expected,
+            "{} against {}",
+            String::from_utf8_lossy(pattern),
+            String::from_utf8_lossy(string)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_database_scan() {
+    let database = Database::new();
+    for i in 0..100 {
+        database.set(format!("key:{i}").as_bytes(), b"value").await;
+    }
+
+    // Keys present during the whole scan are returned despite insertions and removals
+    let options = ScanOptions {
+        count: 7,
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    let mut cursor = 0;
+    for i in 0.. {
+        let (next, keys) = database.scan(cursor, &options).await;
+        seen.extend(keys);
+        database.set(format!("new:{i}").as_bytes(), b"value").await;
+        database.remove(format!("key:{}", 99 - i).as_bytes()).await;
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+    let remaining = database.keys_matching(b"key:*").await;
+    assert!(!remaining.is_empty());
+    assert!(remaining.iter().all(|key| seen.contains(key)));
+
+    // Same for fields of a hash
+    let hash = RedisString::new(b"myhash");
+    let field = |i: usize| RedisString::new(format!("field:{i}").as_bytes());
+    let pairs = (0..100)
+        .map(|i| (field(i), RedisString::new(b"v")))
+        .collect();
+    database.hash_set(&hash, pairs).await.unwrap();
+    let mut seen = HashSet::new();
+    let mut cursor = 0;
+    for i in 0.. {
+        let (next, fields) = database.hash_scan(&hash, cursor, &options).await.unwrap();
+        seen.extend(fields.into_iter().map(|(field, _)| field));
+        database.hash_delete(&hash, &[field(99 - i)]).await.unwrap();
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+    assert!(seen.len() >= database.hash_len(&hash).await.unwrap());
+    assert!((0..50).all(|i| seen.contains(&field(i))));
+
+    // Filters
+    let key = RedisString::new(b"myset");
+    database
+        .set_add(&key, vec![RedisString::new(b"a1"), RedisString::new(b"b1")])
+        .await
+        .unwrap();
+    let options = ScanOptions {
+        pattern: Some(RedisString::new(b"my*")),
+        count: 1000,
+        type_name: Some("set".to_string()),
+    };
+    assert_eq!(database.scan(0, &options).await, (0, vec![key.clone()]));
+    let options = ScanOptions {
+        pattern: Some(RedisString::new(b"a*")),
+        ..Default::default()
+    };
+    assert_eq!(
+        database.set_scan(&key, 0, &options).await,
+        Ok((0, vec![RedisString::new(b"a1")]))
+    );
+    assert_eq!(
+        database.hash_scan(&key, 0, &ScanOptions::default()).await,
+        Err(MiniRedisError::WrongType)
+    );
+}
+
+#[tokio::test]
+async fn test_database_keys() {
+    let database = Database::new();
+    let key = |name: &[u8]| RedisString::new(name);
+
+    database.set(b"foo", b"hello").await;
+    database.set(b"bar", b"world").await;
+    database.expire_at_millis(b"foo", u64::MAX).await;
+    assert_eq!(
+        database
+            .exists_count(&[key(b"foo"), key(b"foo"), key(b"baz")])
+            .await,
+        2
+    );
+
+    // Expiry follows renamed key and replaces destination one
+    assert_eq!(
+        database.rename(&key(b"foo"), &key(b"baz"), false).await,
+        Ok(true)
+    );
+    assert_eq!(
+        database.expire_time(&key(b"baz")).await,
+        Some(Some(u64::MAX))
+    );
+    assert_eq!(
+        database.rename(&key(b"bar"), &key(b"baz"), true).await,
+        Ok(false)
+    );
+    assert_eq!(
+        database.rename(&key(b"bar"), &key(b"baz"), false).await,
+        Ok(true)
+    );
+    assert_eq!(database.expire_time(&key(b"baz")).await, Some(None));
+    assert_eq!(
+        database.rename(&key(b"foo"), &key(b"baz"), false).await,
+        Err(MiniRedisError::NoSuchKey)
+    );
+
+    // Copy keeps expiry
+    database.expire_at_millis(b"baz", u64::MAX).await;
+    let (value, expire_at) = database.get_with_expiry(&key(b"baz")).await.unwrap();
+    assert!(
+        !database
+            .restore(&key(b"baz"), value.clone(), None, false)
+            .await
+    );
+    assert!(
+        database
+            .restore(&key(b"copy"), value, expire_at, false)
+            .await
+    );
+    assert_eq!(
+        database.expire_time(&key(b"copy")).await,
+        Some(Some(u64::MAX))
+    );
+    assert_eq!(database.size().await, 2);
+
+    // Delete removes expiry as well
+    assert_eq!(database.delete(&[key(b"baz"), key(b"bar")]).await, 1);
+    assert_eq!(database.expire_time(&key(b"baz")).await, None);
+    assert!(database.random_key().await.is_some());
+
+    // Picks are bounded when most keys are expired
+    let expired = Database::new();
+    for i in 0..1000 {
+        expired.set(format!("key{i}").as_bytes(), b"value").await;
+        expired
+            .expire_at_millis(format!("key{i}").as_bytes(), 1)
+            .await;
+    }
+    assert!(expired.random_key().await.is_some());
+    assert_eq!(expired.size().await, 901);
+    assert_eq!(Database::new().random_key().await, None);
+}
+
+#[tokio::test]
+async fn test_database_strings() {
+    let database = Database::new();
+    let key = RedisString::new(b"foo");
+
+    // Binary safe append and ranges
+    assert_eq!(
+        database
+            .string_append(&key, &RedisString::new(b"a\0b"))
+            .await,
+        Ok(3)
+    );
+    assert_eq!(
+        database
+            .string_append(&key, &RedisString::new(b"\xffc"))
+            .await,
+        Ok(5)
+    );
+    assert_eq!(
+        database.string_range(&key, 1, -2).await,
+        Ok(RedisString::new(b"\0b\xff"))
+    );
+    assert_eq!(
+        database.string_range(&key, 0, -100).await,
+        Ok(RedisString::new(b"a"))
+    );
+    assert_eq!(
+        database.string_range(&key, -1, -3).await,
+        Ok(RedisString::new(b""))
+    );
+
+    // Zero padding past the end
+    assert_eq!(
+        database
+            .string_set_range(&key, 7, &RedisString::new(b"z"))
+            .await,
+        Ok(8)
+    );
+    assert_eq!(
+        database.get(b"foo").await,
+        Ok(Some(RedisString::new(b"a\0b\xffc\0\0z")))
+    );
+    let missing = RedisString::new(b"missing");
+    assert_eq!(
+        database
+            .string_set_range(&missing, 3, &RedisString::new(b""))
+            .await,
+        Ok(0)
+    );
+    assert!(!database.exists(b"missing").await);
+
+    // Longest common subsequence and its ranges from the end
+    database.set(b"key1", b"ohmytext").await;
+    database.set(b"key2", b"mynewtext").await;
+    let (sequence, matches) = database
+        .string_lcs(&RedisString::new(b"key1"), &RedisString::new(b"key2"))
+        .await
+        .unwrap();
+    assert_eq!(sequence, RedisString::new(b"mytext"));
+    assert_eq!(
+        matches,
+        vec![
+            LcsMatch {
+                a: (4, 7),
+                b: (5, 8)
+            },
+            LcsMatch {
+                a: (2, 3),
+                b: (0, 1)
+            }
+        ]
+    );
+
+    // Several keys at once
+    let pairs = vec![
+        (RedisString::new(b"key2"), RedisString::new(b"new")),
+        (RedisString::new(b"key3"), RedisString::new(b"v3")),
+    ];
+    assert!(!database.set_many(pairs.clone(), true).await);
+    assert!(database.set_many(pairs, false).await);
+    assert_eq!(
+        database
+            .get_many(&[
+                RedisString::new(b"key2"),
+                missing,
+                RedisString::new(b"key3")
+            ])
+            .await,
+        vec![
+            Some(RedisString::new(b"new")),
+            None,
+            Some(RedisString::new(b"v3"))
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_database_counters() {
+    let database = Database::new();
+    let key = RedisString::new(b"counter");
+
+    assert_eq!(database.string_incr_by(&key, 5).await, Ok(5));
+    assert_eq!(database.string_incr_by(&key, -7).await, Ok(-2));
+    database.expire_at_millis(b"counter", u64::MAX).await;
+    assert_eq!(database.string_incr_by(&key, 1).await, Ok(-1));
+    assert_eq!(database.expire_time(&key).await, Some(Some(u64::MAX)));
+    assert_eq!(
+        database.string_incr_by(&key, i64::MIN).await,
+        Err(MiniRedisError::NotInteger)
+    );
+
+    // Only canonical integers are numbers
+    for value in [
+        &b""[..],
+        b" 1",
+        b"+1",
+        b"01",
+        b"-0",
+        b"1.0",
+        b"99999999999999999999",
+    ] {
+        database.set(b"counter", value).await;
+        assert_eq!(
+            database.string_incr_by(&key, 1).await,
+            Err(MiniRedisError::NotInteger)
+        );
+    }
+
+    database.set(b"counter", b"10.5").await;
+    assert_eq!(
+        database.string_incr_by_float(&key, 0.1).await,
+        Ok(RedisString::new(b"10.6"))
+    );
+    assert_eq!(
+        database.string_incr_by_float(&key, -5.6).await,
+        Ok(RedisString::new(b"5"))
+    );
+    assert_eq!(database.string_incr_by(&key, 1).await, Ok(6));
+    database.set(b"counter", b"abc").await;
+    assert_eq!(
+        database.string_incr_by_float(&key, 1.0).await,
+        Err(MiniRedisError::NotFloat)
+    );
+}
+
+#[tokio::test]
+async fn test_database_bitmaps() {
+    let database = Database::new();
+    let key = RedisString::new(b"bits");
+
+    assert_eq!(database.bit_set(&key, 7, true).await, Ok(false));
+    assert_eq!(database.bit_set(&key, 7, true).await, Ok(true));
+    assert_eq!(database.bit_set(&key, 17, true).await, Ok(false));
+    assert_eq!(
+        database.get(b"bits").await,
+        Ok(Some(RedisString::new(b"\x01\x00\x40")))
+    );
+    assert_eq!(database.bit_get(&key, 17).await, Ok(true));
+    assert_eq!(database.bit_get(&key, 1000).await, Ok(false));
+
+    // Examples of the Redis documentation
+    database.set(b"bits", b"foobar").await;
+    assert_eq!(database.bit_count(&key, None).await, Ok(26));
+    assert_eq!(
+        database.bit_count(&key, Some((1, 1, BitUnit::Byte))).await,
+        Ok(6)
+    );
+    assert_eq!(
+        database.bit_count(&key, Some((5, 30, BitUnit::Bit))).await,
+        Ok(17)
+    );
+    assert_eq!(
+        database
+            .bit_count(&key, Some((-1, -2, BitUnit::Byte)))
+            .await,
+        Ok(0)
+    );
+
+    database.set(b"bits", b"\xff\xf0\x00").await;
+    assert_eq!(
+        database.bit_pos(&key, false, 0, None, BitUnit::Byte).await,
+        Ok(12)
+    );
+    database.set(b"bits", b"\x00\xff\xf0").await;
+    assert_eq!(
+        database.bit_pos(&key, true, 2, None, BitUnit::Byte).await,
+        Ok(16)
+    );
+    assert_eq!(
+        database
+            .bit_pos(&key, true, 7, Some(15), BitUnit::Bit)
+            .await,
+        Ok(8)
+    );
+    database.set(b"bits", b"\xff").await;
+    assert_eq!(
+        database.bit_pos(&key, false, 0, None, BitUnit::Byte).await,
+        Ok(8)
+    );
+    assert_eq!(
+        database
+            .bit_pos(&key, false, 0, Some(-1), BitUnit::Byte)
+            .await,
+        Ok(-1)
+    );
+    let missing = RedisString::new(b"missing");
+    assert_eq!(
+        database
+            .bit_pos(&missing, true, 0, None, BitUnit::Byte)
+            .await,
+        Ok(-1)
+    );
+
+    database.set(b"a", b"\x0f\xff").await;
+    database.set(b"b", b"\xf0").await;
+    let destination = RedisString::new(b"result");
+    let sources = [RedisString::new(b"a"), RedisString::new(b"b")];
+    assert_eq!(
+        database
+            .bit_op(BitOperation::Or, &destination, &sources)
+            .await,
+        Ok(2)
+    );
+    assert_eq!(
+        database.get(b"result").await,
+        Ok(Some(RedisString::new(b"\xff\xff")))
+    );
+    assert_eq!(
+        database
+            .bit_op(BitOperation::And, &destination, &sources)
+            .await,
+        Ok(2)
+    );
+    assert_eq!(
+        database.get(b"result").await,
+        Ok(Some(RedisString::new(b"\x00\x00")))
+    );
+    assert_eq!(
+        database
+            .bit_op(BitOperation::Not, &destination, &sources[1..])
+            .await,
+        Ok(1)
+    );
+    assert_eq!(
+        database.get(b"result").await,
+        Ok(Some(RedisString::new(b"\x0f")))
+    );
+    assert_eq!(
+        database
+            .bit_op(
+                BitOperation::Xor,
+                &destination,
+                std::slice::from_ref(&missing)
+            )
+            .await,
+        Ok(0)
+    );
+    assert!(!database.exists(b"result").await);
+
+    let u2 = BitFieldType {
+        signed: false,
+        bits: 2,
+    };
+    let i8 = BitFieldType {
+        signed: true,
+        bits: 8,
+    };
+    let key = RedisString::new(b"fields");
+    let ops = [
+        BitFieldOp::IncrBy(u2, 100, 1),
+        BitFieldOp::Overflow(BitOverflow::Sat),
+        BitFieldOp::IncrBy(u2, 102, 1),
+        BitFieldOp::Overflow(BitOverflow::Fail),
+        BitFieldOp::IncrBy(u2, 104, 1),
+    ];
+    for expected in [[1, 1, 1], [2, 2, 2], [3, 3, 3]] {
+        assert_eq!(
+            database.bit_field(&key, &ops).await,
+            Ok(expected.map(Some).to_vec())
+        );
+    }
+    assert_eq!(
+        database.bit_field(&key, &ops).await,
+        Ok(vec![Some(0), Some(3), None])
+    );
+    assert_eq!(
+        database
+            .bit_field(&key, &[BitFieldOp::Set(i8, 0, 200), BitFieldOp::Get(i8, 0)])
+            .await,
+        Ok(vec![Some(0), Some(-56)])
+    );
+    assert_eq!(
+        database
+            .bit_field(&missing, &[BitFieldOp::Get(i8, 0)])
+            .await,
+        Ok(vec![Some(0)])
+    );
+    assert!(!database.exists(b"missing").await);
 }
@@ -0,0 +1,89 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+};
+
+use redis_starter_rust::{
+    error::MiniRedisError,
+    persistence::{self, Persistence, SavePoint},
+    rdb::{Rdb, RdbDatabase, RedisString},
+};
+
+#[test]
+fn test_parse_save_points() {
+    assert_eq!(SavePoint::parse_list(b""), Ok(vec![]));
+    assert_eq!(
+        SavePoint::parse_list(persistence::DEFAULT_SAVE.as_bytes()).map(|points| points.len()),
+        Ok(3)
+    );
+    assert_eq!(
+        SavePoint::parse_list(b"3600 1  300 100"),
+        Ok(vec![
+            SavePoint {
+                seconds: 3600,
+                changes: 1
+            },
+            SavePoint {
+                seconds: 300,
+                changes: 100
+            },
+        ])
+    );
+    assert_eq!(
+        SavePoint::parse_list(b"3600"),
+        Err(MiniRedisError::InvalidConfig(
+            "Invalid save parameters: 3600".to_string()
+        ))
+    );
+    assert_eq!(
+        SavePoint::parse_list(b"3600 foo"),
+        Err(MiniRedisError::InvalidNumber(
+            "invalid digit found in string".to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_should_save() {
+    let persistence = Persistence::new();
+
+    // No save point
+    assert!(!persistence.should_save(10).await);
+
+    persistence
+        .set_save_points(SavePoint::parse_list(b"3600 1 0 5").unwrap())
+        .await;
+    assert!(!persistence.should_save(0).await);
+    assert!(!persistence.should_save(4).await);
+    assert!(persistence.should_save(5).await);
+
+    persistence.mark_clean(5);
+    assert_eq!(persistence.changes_since_last_save(7), 2);
+    assert!(!persistence.should_save(7).await);
+}
+
+#[tokio::test]
+async fn test_save() {
+    let path = env::temp_dir().join(format!("test-save-{}.rdb", std::process::id()));
+    let rdb = Rdb {
+        version: 11,
+        databases: BTreeMap::from([(
+            1,
+            RdbDatabase {
+                values: HashMap::from([(
+                    RedisString::new(b"foo"),
+                    RedisString::new(b"bar").into(),
+                )]),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+
+    let persistence = Persistence::new();
+    persistence.save(&rdb, &path, 3).await.unwrap();
+    assert_eq!(persistence.changes_since_last_save(3), 0);
+    assert_eq!(persistence::read_rdb_file(&path).await, Ok(rdb));
+
+    fs::remove_file(path).unwrap();
+}
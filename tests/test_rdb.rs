@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 
 use redis_starter_rust::{
+    crc64,
+    database::{Consumer, ConsumerGroup, Hash, PendingEntry, Set, Stream, StreamId, Value},
     error::MiniRedisError,
-    rdb::{self, Rdb, RedisString},
+    rdb::{self, Rdb, RdbDatabase, RedisString},
 };
 use tokio::io::BufReader;
 
@@ -13,7 +15,7 @@ async fn make_rdb(input: &[u8]) -> Result<Rdb, MiniRedisError> {
 
 #[test]
 fn test_debug() {
-    assert_eq!(format!("{:?}", rdb::Rdb::default()), "Rdb { version: 0, aux_redis_ver: None, aux_redis_bits: None, aux_ctime: None, aux_used_men: None, values: {}, expiry: {} }");
+    assert_eq!(format!("{:?}", rdb::Rdb::default()), "Rdb { version: 0, aux_redis_ver: None, aux_redis_bits: None, aux_ctime: None, aux_used_men: None, databases: {} }");
     assert_eq!(format!("{:?}", rdb::LengthEncoding::Fixed(41)), "Fixed(41)");
 }
 
@@ -54,8 +56,16 @@ async fn test_parse_single_key() {
             aux_redis_bits: Some("64".to_string()),
             aux_ctime: Some("1703066385".to_string()),
             aux_used_men: Some("915400".to_string()),
-            values: HashMap::from([(RedisString::new(b"foo"), RedisString::new(b"bar"))]),
-            ..Default::default()
+            databases: BTreeMap::from([(
+                0,
+                RdbDatabase {
+                    values: HashMap::from([(
+                        RedisString::new(b"foo"),
+                        RedisString::new(b"bar").into()
+                    )]),
+                    ..Default::default()
+                }
+            )]),
         })
     );
 }
@@ -70,11 +80,691 @@ async fn test_parse_multi_key_expire() {
             aux_redis_bits: Some("64".to_string()),
             aux_ctime: Some("1703080200".to_string()),
             aux_used_men: Some("1012368".to_string()),
-            values: HashMap::from([
-                (RedisString::new(b"foo"), RedisString::new(b"bar")),
-                (RedisString::new(b"k2"), RedisString::new(b"v2")),
+            databases: BTreeMap::from([(
+                0,
+                RdbDatabase {
+                    values: HashMap::from([
+                        (RedisString::new(b"foo"), RedisString::new(b"bar").into()),
+                        (RedisString::new(b"k2"), RedisString::new(b"v2").into()),
+                    ]),
+                    expiry: HashMap::from([(RedisString::new(b"foo"), 1703081197600)]),
+                }
+            )]),
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_length_encoding() {
+    async fn check(encoding: rdb::LengthEncoding, expected: &[u8]) {
+        let mut output = Vec::new();
+        encoding.write(&mut output).await.unwrap();
+        assert_eq!(output, expected);
+
+        let mut reader = BufReader::new(expected);
+        assert_eq!(rdb::LengthEncoding::read(&mut reader).await, Ok(encoding));
+    }
+
+    check(rdb::LengthEncoding::Fixed(10), &[0x0A]).await;
+    check(rdb::LengthEncoding::Fixed(700), &[0x42, 0xBC]).await;
+    check(rdb::LengthEncoding::Fixed(17000), &[0x80, 0, 0, 0x42, 0x68]).await;
+    check(rdb::LengthEncoding::Int8, &[0xC0]).await;
+    check(rdb::LengthEncoding::Int16, &[0xC1]).await;
+    check(rdb::LengthEncoding::Int32, &[0xC2]).await;
+}
+
+#[tokio::test]
+async fn test_write_round_trip() {
+    let long_value = RedisString::new(&[b'x'; 20_000]);
+    let rdb = rdb::Rdb {
+        version: 11,
+        aux_redis_ver: Some("7.2.0".to_string()),
+        aux_redis_bits: Some("64".to_string()),
+        aux_ctime: Some("1703080200".to_string()),
+        aux_used_men: None,
+        databases: BTreeMap::from([
+            (
+                0,
+                RdbDatabase {
+                    values: HashMap::from([
+                        (RedisString::new(b"foo"), RedisString::new(b"bar").into()),
+                        (RedisString::new(b"k2"), RedisString::new(b"").into()),
+                        (RedisString::new(b"\x00\xff"), long_value.into()),
+                    ]),
+                    expiry: HashMap::from([(RedisString::new(b"foo"), 1703081197600)]),
+                },
+            ),
+            (
+                2,
+                RdbDatabase {
+                    values: HashMap::from([(
+                        RedisString::new(b"foo"),
+                        RedisString::new(b"db2").into(),
+                    )]),
+                    ..Default::default()
+                },
+            ),
+        ]),
+    };
+
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+
+    // Empty database
+    let mut output = Vec::new();
+    rdb::Rdb::default().write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb::Rdb::default()));
+}
+
+#[tokio::test]
+async fn test_write_checksum() {
+    fn footer(input: &[u8]) -> (u64, u64) {
+        let (content, checksum) = input.split_at(input.len() - 8);
+        (
+            crc64::crc64(content),
+            u64::from_le_bytes(checksum.try_into().unwrap()),
+        )
+    }
+
+    assert_eq!(crc64::crc64(b"123456789"), 0xe9c6d914c4b8d9ca);
+
+    // Checksum written by Redis
+    for input in [
+        include_bytes!("./data/empty.rdb").as_slice(),
+        include_bytes!("./data/single-key.rdb").as_slice(),
+        include_bytes!("./data/multi-key-expire.rdb").as_slice(),
+    ] {
+        let (computed, expected) = footer(input);
+        assert_eq!(computed, expected);
+    }
+
+    // Checksum written by us
+    let rdb = make_rdb(include_bytes!("./data/multi-key-expire.rdb"))
+        .await
+        .unwrap();
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    let (computed, expected) = footer(&output);
+    assert_eq!(computed, expected);
+}
+
+#[tokio::test]
+async fn test_invalid_checksum() {
+    let mut input = include_bytes!("./data/single-key.rdb").to_vec();
+    let len = input.len();
+    let expected = u64::from_le_bytes(input[len - 8..].try_into().unwrap());
+
+    // Corrupted value
+    let value_pos = input[..len - 8].iter().rposition(|x| *x == b'r').unwrap();
+    input[value_pos] = b'R';
+    assert_eq!(
+        make_rdb(&input).await,
+        Err(MiniRedisError::InvalidRdbChecksum {
+            expected,
+            computed: crc64::crc64(&input[..len - 8]),
+        })
+    );
+
+    // Checksum disabled
+    input[len - 8..].fill(0);
+    assert!(make_rdb(&input).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_unknown_opcode() {
+    let mut input = include_bytes!("./data/empty.rdb").to_vec();
+    let eof_pos = input.len() - 9;
+    input[eof_pos] = 0xF0;
+    assert_eq!(
+        make_rdb(&input).await,
+        Err(MiniRedisError::UnknownRdbOpcode {
+            opcode: 0xF0,
+            offset: eof_pos as u64,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_check_rdb() {
+    let mut input = include_bytes!("./data/multi-key-expire.rdb").to_vec();
+    assert_eq!(
+        rdb::check_rdb(&input).await,
+        Ok(rdb::RdbSummary {
+            version: 11,
+            aux_fields: vec![
+                ("redis-ver", "7.2.3".to_string()),
+                ("redis-bits", "64".to_string()),
+                ("ctime", "1703080200".to_string()),
+                ("used-mem", "1012368".to_string()),
+            ],
+            checksum: rdb::RdbChecksum::Verified,
+            databases: vec![rdb::RdbDatabaseSummary {
+                index: 0,
+                keys: 2,
+                expires: 1,
+                already_expired: 1,
+            }],
+        })
+    );
+
+    // Checksum disabled
+    let len = input.len();
+    input[len - 8..].fill(0);
+    assert_eq!(
+        rdb::check_rdb(&input).await.map(|x| x.checksum),
+        Ok(rdb::RdbChecksum::Disabled)
+    );
+
+    // Bad checksum
+    input[len - 1] = 1;
+    assert!(matches!(
+        rdb::check_rdb(&input).await,
+        Err(MiniRedisError::InvalidRdbChecksum { .. })
+    ));
+
+    // Unknown opcode
+    let mut input = include_bytes!("./data/empty.rdb").to_vec();
+    let eof_pos = input.len() - 9;
+    input[eof_pos] = 0xF0;
+    assert_eq!(
+        rdb::check_rdb(&input).await,
+        Err(MiniRedisError::UnknownRdbOpcode {
+            opcode: 0xF0,
+            offset: eof_pos as u64,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_parse_multi_db() {
+    let mut input = b"REDIS0011".to_vec();
+    input.extend(b"\xfe\x01\xfb\x01\x00\x00\x03foo\x03bar");
+    input.extend(b"\xfe\x02\xfb\x01\x01\xfc\x00\x9c\xef\x12\x7e\x01\x00\x00\x00\x03foo\x03baz");
+    input.extend(b"\xff");
+    input.extend(crc64::crc64(&input).to_le_bytes());
+
+    assert_eq!(
+        make_rdb(&input).await,
+        Ok(rdb::Rdb {
+            version: 11,
+            databases: BTreeMap::from([
+                (
+                    1,
+                    RdbDatabase {
+                        values: HashMap::from([(
+                            RedisString::new(b"foo"),
+                            RedisString::new(b"bar").into()
+                        )]),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    2,
+                    RdbDatabase {
+                        values: HashMap::from([(
+                            RedisString::new(b"foo"),
+                            RedisString::new(b"baz").into()
+                        )]),
+                        expiry: HashMap::from([(RedisString::new(b"foo"), 1640995200000)]),
+                    }
+                ),
             ]),
-            expiry: HashMap::from([(RedisString::new(b"foo"), 1703081197600)]),
+            ..Default::default()
         })
     );
 }
+
+#[tokio::test]
+async fn test_corrupt_resize_db() {
+    fn resize_db(values_size: &[u8]) -> Vec<u8> {
+        let mut input = b"REDIS0011\xfe\x00\xfb".to_vec();
+        input.extend(values_size);
+        input.extend(b"\x00\x00\x03foo\x03bar\xff");
+        input.extend(crc64::crc64(&input).to_le_bytes());
+        input
+    }
+
+    // Huge size is only a hint
+    let rdb = make_rdb(&resize_db(b"\x81\x7f\xff\xff\xff\xff\xff\xff\xff"))
+        .await
+        .unwrap();
+    assert_eq!(rdb.databases[&0].values.len(), 1);
+
+    assert_eq!(
+        make_rdb(&resize_db(b"\x81\xff\xff\xff\xff\xff\xff\xff\xff")).await,
+        Err(MiniRedisError::InvalidRdbValue(
+            "Invalid RESIZEDB size".to_string()
+        ))
+    );
+    assert_eq!(
+        make_rdb(&resize_db(b"\xc0\xff")).await,
+        Err(MiniRedisError::InvalidRdbValue(
+            "Invalid RESIZEDB size".to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_parse_lists() {
+    let listpack = [
+        &[0x13, 0, 0, 0, 4, 0][..],
+        // "a"
+        &[0x81, b'a', 0x02],
+        // 12
+        &[0x0C, 0x01],
+        // -2
+        &[0xDF, 0xFE, 0x02],
+        // 30000
+        &[0xF1, 0x30, 0x75, 0x03],
+        &[0xFF],
+    ]
+    .concat();
+    assert_eq!(
+        rdb::read_listpack(&listpack),
+        Ok(vec![
+            RedisString::new(b"a"),
+            RedisString::new(b"12"),
+            RedisString::new(b"-2"),
+            RedisString::new(b"30000"),
+        ])
+    );
+
+    let mut input = b"REDIS0011\xfe\x00\xfb\x02\x00".to_vec();
+    // Quicklist with a packed node and a plain node
+    input.extend(b"\x12\x02ql\x02\x02");
+    input.push(listpack.len() as u8);
+    input.extend(&listpack);
+    input.extend(b"\x01\x05plain");
+    // Plain list with an LZF compressed element
+    input.extend(b"\x01\x01l\x01\xc3\x05\x15\x00a\xe0\x0b\x00");
+    input.push(0xff);
+    input.extend([0; 8]);
+
+    let rdb = make_rdb(&input).await.unwrap();
+    let values = &rdb.databases[&0].values;
+    assert_eq!(
+        values.get(&RedisString::new(b"ql")),
+        Some(&Value::List(VecDeque::from([
+            RedisString::new(b"a"),
+            RedisString::new(b"12"),
+            RedisString::new(b"-2"),
+            RedisString::new(b"30000"),
+            RedisString::new(b"plain"),
+        ])))
+    );
+    assert_eq!(
+        values.get(&RedisString::new(b"l")),
+        Some(&Value::List(VecDeque::from([RedisString::new(
+            &[b'a'; 21]
+        )])))
+    );
+
+    // Lists are written as plain lists
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+}
+
+#[tokio::test]
+async fn test_corrupt_list_length() {
+    let mut input = b"REDIS0011\xfe\x00".to_vec();
+    // Plain list claiming far more elements than stored
+    input.extend(b"\x01\x01l\x81\x7f\xff\xff\xff\xff\xff\xff\xff\x01a");
+    assert_eq!(
+        make_rdb(&input).await,
+        Err(MiniRedisError::Io("unexpected end of file".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_parse_hashes() {
+    let listpack = [
+        &[0x0F, 0, 0, 0, 4, 0][..],
+        // "f" => "v"
+        &[0x81, b'f', 0x02],
+        &[0x81, b'v', 0x02],
+        // "n" => 7
+        &[0x81, b'n', 0x02],
+        &[0x07, 0x01],
+        &[0xFF],
+    ]
+    .concat();
+
+    let mut input = b"REDIS0011\xfe\x00\xfb\x02\x00".to_vec();
+    // Listpack encoded hash
+    input.extend(b"\x10\x02lp");
+    input.push(listpack.len() as u8);
+    input.extend(&listpack);
+    // Plain hash
+    input.extend(b"\x04\x01h\x01\x03foo\x03bar");
+    input.push(0xff);
+    input.extend([0; 8]);
+
+    let rdb = make_rdb(&input).await.unwrap();
+    let values = &rdb.databases[&0].values;
+    assert_eq!(
+        values.get(&RedisString::new(b"lp")),
+        Some(&Value::Hash(Hash::from([
+            (RedisString::new(b"f"), RedisString::new(b"v")),
+            (RedisString::new(b"n"), RedisString::new(b"7")),
+        ])))
+    );
+    assert_eq!(
+        values.get(&RedisString::new(b"h")),
+        Some(&Value::Hash(Hash::from([(
+            RedisString::new(b"foo"),
+            RedisString::new(b"bar")
+        )])))
+    );
+
+    // Hashes are written as plain hashes
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+}
+
+#[tokio::test]
+async fn test_parse_sets() {
+    let mut input = b"REDIS0011\xfe\x00\xfb\x02\x00".to_vec();
+    // Intset of 16 bits integers
+    input.extend(b"\x0b\x02is\x0c\x02\x00\x00\x00\x02\x00\x00\x00\xfe\xff\x07\x00");
+    // Plain set
+    input.extend(b"\x02\x01s\x02\x01a\x01b");
+    input.push(0xff);
+    input.extend([0; 8]);
+
+    let rdb = make_rdb(&input).await.unwrap();
+    let values = &rdb.databases[&0].values;
+    assert_eq!(
+        values.get(&RedisString::new(b"is")),
+        Some(&Value::Set(Set::from([
+            RedisString::new(b"-2"),
+            RedisString::new(b"7"),
+        ])))
+    );
+    assert_eq!(
+        values.get(&RedisString::new(b"s")),
+        Some(&Value::Set(Set::from([
+            RedisString::new(b"a"),
+            RedisString::new(b"b"),
+        ])))
+    );
+
+    // Sets are written as plain sets
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+
+    // Intset length must match its content
+    let mut input = b"REDIS0011\xfe\x00\xfb\x01\x00".to_vec();
+    input.extend(b"\x0b\x02is\x0a\x02\x00\x00\x00\x02\x00\x00\x00\xfe\xff");
+    input.push(0xff);
+    input.extend([0; 8]);
+    assert_eq!(
+        make_rdb(&input).await,
+        Err(MiniRedisError::InvalidRdbValue(
+            "Invalid intset".to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_parse_sorted_sets() {
+    let listpack = [
+        &[0x0C, 0, 0, 0, 2, 0][..],
+        // "x" => 7
+        &[0x81, b'x', 0x02],
+        &[0x07, 0x01],
+        &[0xFF],
+    ]
+    .concat();
+
+    let mut input = b"REDIS0011\xfe\x00\xfb\x03\x00".to_vec();
+    // Scores stored as text, the second one being infinity
+    input.extend(b"\x03\x02z3\x02\x01a\x031.5\x01b\xfe");
+    // Binary scores
+    input.extend(b"\x05\x02z5\x01\x01c");
+    input.extend(2.5_f64.to_le_bytes());
+    // Listpack encoded
+    input.extend(b"\x11\x02lp");
+    input.push(listpack.len() as u8);
+    input.extend(&listpack);
+    input.push(0xff);
+    input.extend([0; 8]);
+
+    let rdb = make_rdb(&input).await.unwrap();
+    let values = &rdb.databases[&0].values;
+    let scores = |key: &[u8]| -> Vec<(RedisString, f64)> {
+        let zset = match values.get(&RedisString::new(key)) {
+            Some(Value::SortedSet(zset)) => zset,
+            _ => panic!("Sorted set expected"),
+        };
+        zset.iter()
+            .map(|(member, score)| (member.clone(), score))
+            .collect()
+    };
+    assert_eq!(
+        scores(b"z3"),
+        vec![
+            (RedisString::new(b"a"), 1.5),
+            (RedisString::new(b"b"), f64::INFINITY)
+        ]
+    );
+    assert_eq!(scores(b"z5"), vec![(RedisString::new(b"c"), 2.5)]);
+    assert_eq!(scores(b"lp"), vec![(RedisString::new(b"x"), 7.0)]);
+
+    // Sorted sets are written with binary scores
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+}
+
+#[test]
+fn test_listpack_round_trip() {
+    let entries: Vec<RedisString> = [
+        "0",
+        "127",
+        "128",
+        "-1",
+        "-4096",
+        "4095",
+        "-32768",
+        "70000",
+        "-8388608",
+        "2147483647",
+        "-9223372036854775808",
+        "007",
+        "+1",
+        "-0",
+        "",
+        "text",
+    ]
+    .iter()
+    .map(|entry| RedisString::new(entry.as_bytes()))
+    .chain([
+        RedisString::new(&[b'x'; 100]),
+        RedisString::new(&[b'y'; 20000]),
+    ])
+    .collect();
+
+    let listpack = rdb::write_listpack(&entries);
+    assert_eq!(&listpack[..4], (listpack.len() as u32).to_le_bytes());
+    assert_eq!(&listpack[4..6], (entries.len() as u16).to_le_bytes());
+    assert_eq!(rdb::read_listpack(&listpack), Ok(entries));
+}
+
+#[tokio::test]
+async fn test_parse_streams() {
+    // Entries 1-1 sharing master fields, 1-2 with its own fields and 2-0 deleted
+    let elements = [
+        "2", "1", "1", "a", "0", "2", "0", "0", "1", "4", "0", "0", "1", "1", "b", "x", "6", "3",
+        "1", "-1", "v", "4",
+    ]
+    .map(|element| RedisString::new(element.as_bytes()));
+    let listpack = rdb::write_listpack(&elements);
+    assert!(listpack.len() < 64);
+
+    let mut input = b"REDIS0011\xfe\x00\xfb\x01\x00\x15\x01s\x01\x10".to_vec();
+    input.extend(1_u64.to_be_bytes());
+    input.extend(1_u64.to_be_bytes());
+    input.push(listpack.len() as u8);
+    input.extend(&listpack);
+    // Length, last ID, first ID, maximum deleted ID, entries added and consumer groups
+    input.extend(b"\x02\x02\x00\x01\x01\x02\x00\x03\x00");
+    input.push(0xff);
+    input.extend([0; 8]);
+
+    let rdb = make_rdb(&input).await.unwrap();
+    let pair =
+        |field: &[u8], value: &[u8]| vec![(RedisString::new(field), RedisString::new(value))];
+    let expected = Stream {
+        entries: BTreeMap::from([
+            (StreamId { ms: 1, seq: 1 }, pair(b"a", b"1")),
+            (StreamId { ms: 1, seq: 2 }, pair(b"b", b"x")),
+        ]),
+        last_id: StreamId { ms: 2, seq: 0 },
+        max_deleted_id: StreamId { ms: 2, seq: 0 },
+        entries_added: 3,
+        ..Default::default()
+    };
+    assert_eq!(
+        rdb.databases[&0].values.get(&RedisString::new(b"s")),
+        Some(&Value::Stream(expected))
+    );
+
+    // Large streams are split in several listpacks
+    let mut stream = Stream::default();
+    for seq in 0..250 {
+        let field = if seq % 2 == 0 {
+            b"even".as_slice()
+        } else {
+            b"odd"
+        };
+        stream
+            .entries
+            .insert(StreamId { ms: 5, seq }, pair(field, &[seq as u8]));
+    }
+    stream.last_id = StreamId { ms: 6, seq: 0 };
+    stream.entries_added = 300;
+    let mut rdb = rdb;
+    rdb.databases
+        .get_mut(&0)
+        .unwrap()
+        .values
+        .insert(RedisString::new(b"large"), Value::Stream(stream));
+
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+}
+
+#[tokio::test]
+async fn test_parse_stream_groups() {
+    // Empty stream whose last entry 1-1 is pending for consumer "c" of group "g"
+    let mut input = b"REDIS0011\xfe\x00\xfb\x01\x00\x15\x01s\x00".to_vec();
+    // Length, last ID, first ID, maximum deleted ID, entries added and consumer groups
+    input.extend(b"\x00\x01\x01\x00\x00\x00\x00\x01\x01");
+    // Group name, last ID and unknown entries read
+    input.extend(b"\x01g\x01\x01\x81");
+    input.extend(u64::MAX.to_be_bytes());
+    // Group pending entry with delivery time and count
+    input.push(1);
+    input.extend(1_u64.to_be_bytes());
+    input.extend(1_u64.to_be_bytes());
+    input.extend(1000_u64.to_le_bytes());
+    input.push(2);
+    // Consumer with seen time, no active time and its pending entry
+    input.extend(b"\x01\x01c");
+    input.extend(2000_u64.to_le_bytes());
+    input.extend((-1_i64).to_le_bytes());
+    input.push(1);
+    input.extend(1_u64.to_be_bytes());
+    input.extend(1_u64.to_be_bytes());
+    input.push(0xff);
+    input.extend([0; 8]);
+
+    let rdb = make_rdb(&input).await.unwrap();
+    let id = StreamId { ms: 1, seq: 1 };
+    let consumer = RedisString::new(b"c");
+    let group = ConsumerGroup {
+        last_id: id,
+        entries_read: None,
+        pending: BTreeMap::from([(
+            id,
+            PendingEntry {
+                consumer: consumer.clone(),
+                delivery_time: 1000,
+                delivery_count: 2,
+            },
+        )]),
+        consumers: BTreeMap::from([(
+            consumer,
+            Consumer {
+                seen_time: 2000,
+                active_time: None,
+                pending: BTreeSet::from([id]),
+            },
+        )]),
+    };
+    let expected = Stream {
+        last_id: id,
+        entries_added: 1,
+        groups: BTreeMap::from([(RedisString::new(b"g"), group)]),
+        ..Default::default()
+    };
+    assert_eq!(
+        rdb.databases[&0].values.get(&RedisString::new(b"s")),
+        Some(&Value::Stream(expected))
+    );
+
+    let mut output = Vec::new();
+    rdb.write(&mut output).await.unwrap();
+    assert_eq!(make_rdb(&output).await, Ok(rdb));
+}
+
+#[tokio::test]
+async fn test_read_integer_encoded_string() {
+    for (input, expected) in [
+        (&[0xC0, 0x7F][..], &b"127"[..]),
+        (&[0xC0, 0xFF], b"-1"),
+        (&[0xC1, 0x00, 0x80], b"-32768"),
+        (&[0xC2, 0x00, 0x00, 0x00, 0x80], b"-2147483648"),
+        (&[0xC2, 0xFF, 0xFF, 0xFF, 0x7F], b"2147483647"),
+    ] {
+        let mut reader = BufReader::new(input);
+        assert_eq!(
+            RedisString::read(&mut reader).await,
+            Ok(RedisString::new(expected))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_read_corrupt_string_length() {
+    let huge = [0x81, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+    for (input, expected) in [
+        // Plain string
+        (
+            [&huge[..], b"foo"].concat(),
+            MiniRedisError::Io("early eof".to_string()),
+        ),
+        (
+            b"\x05foo".to_vec(),
+            MiniRedisError::Io("early eof".to_string()),
+        ),
+        // LZF compressed length
+        (
+            [&[0xC3][..], &huge, &[0x03, 0x02], b"foo"].concat(),
+            MiniRedisError::Io("early eof".to_string()),
+        ),
+        // LZF decompressed length
+        (
+            [&[0xC3, 0x04][..], &huge, &[0x02], b"foo"].concat(),
+            MiniRedisError::InvalidRdbValue("Invalid LZF data".to_string()),
+        ),
+    ] {
+        let mut reader = BufReader::new(input.as_slice());
+        assert_eq!(RedisString::read(&mut reader).await, Err(expected));
+    }
+}
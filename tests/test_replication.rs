@@ -0,0 +1,270 @@
+use redis_starter_rust::{
+    database::Value,
+    error::MiniRedisError,
+    rdb::RedisString,
+    replication::{self, FullResync, ReplicaSync, Replication},
+    resp2::Message,
+    response::Response,
+    ServerMode,
+};
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, BufReader};
+
+/// Run handshake against master output, returning its result, written bytes and bytes left
+/// unread.
+async fn handshake(master_output: &[u8]) -> (Result<FullResync, MiniRedisError>, Vec<u8>, Vec<u8>) {
+    let mut reader = BufReader::new(master_output);
+    let mut written = Vec::new();
+    let result = replication::handshake(&mut reader, &mut written, 6380).await;
+    let mut remaining = Vec::new();
+    reader.read_to_end(&mut remaining).await.unwrap();
+    (result, written, remaining)
+}
+
+#[tokio::test]
+async fn test_handshake() {
+    let rdb = include_bytes!("./data/single-key.rdb");
+    let mut master_output =
+        b"+PONG\r\n+OK\r\n+OK\r\n+FULLRESYNC 8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb 0\r\n"
+            .to_vec();
+    master_output.extend_from_slice(format!("${}\r\n", rdb.len()).as_bytes());
+    master_output.extend_from_slice(rdb);
+    master_output.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+
+    let (result, written, remaining) = handshake(&master_output).await;
+    let resync = result.unwrap();
+
+    assert_eq!(
+        written,
+        b"*1\r\n$4\r\nPING\r\n\
+        *3\r\n$8\r\nREPLCONF\r\n$14\r\nlistening-port\r\n$4\r\n6380\r\n\
+        *3\r\n$8\r\nREPLCONF\r\n$4\r\ncapa\r\n$6\r\npsync2\r\n\
+        *3\r\n$5\r\nPSYNC\r\n$1\r\n?\r\n$2\r\n-1\r\n"
+    );
+    assert_eq!(resync.replid, "8371b4fb1155b71f4a04d3e1bc3e18c4a990aeeb");
+    assert_eq!(resync.offset, 0);
+    assert_eq!(
+        resync.rdb.databases[&0]
+            .values
+            .get(&RedisString::new(b"foo")),
+        Some(&Value::String(RedisString::new(b"bar")))
+    );
+
+    // Propagated commands are left untouched
+    assert_eq!(remaining, b"*1\r\n$4\r\nPING\r\n");
+}
+
+#[tokio::test]
+async fn test_handshake_invalid_reply() {
+    let (result, _, _) = handshake(b"-ERR nope\r\n").await;
+    assert_eq!(
+        result,
+        Err(MiniRedisError::InvalidHandshake(
+            "Error(\"ERR nope\")".to_string()
+        ))
+    );
+
+    let (result, _, _) = handshake(b"+PONG\r\n+OK\r\n+OK\r\n+CONTINUE\r\n").await;
+    assert_eq!(
+        result,
+        Err(MiniRedisError::InvalidHandshake("CONTINUE".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_handshake_payload_without_crlf() {
+    let rdb = include_bytes!("./data/empty.rdb");
+    let mut master_output = b"+PONG\r\n+OK\r\n+OK\r\n+FULLRESYNC abc 42\r\n".to_vec();
+    master_output.extend_from_slice(format!("${}\r\n", rdb.len()).as_bytes());
+    master_output.extend_from_slice(rdb);
+
+    let (result, _, _) = handshake(&master_output).await;
+    let resync = result.unwrap();
+    assert_eq!(resync.replid, "abc");
+    assert_eq!(resync.offset, 42);
+    assert_eq!(resync.rdb.version, 11);
+}
+
+#[tokio::test]
+async fn test_propagate() {
+    let replication = Replication::new(ServerMode::Master);
+    assert_eq!(replication.replid().await.len(), 40);
+    assert_eq!(replication.offset(), 0);
+
+    let link = replication.register_replica(b"?", -1).await;
+    assert!(matches!(link.sync, ReplicaSync::Full { offset: 0, .. }));
+    let mut receiver = link.receiver;
+    assert_eq!(replication.replica_count().await, 1);
+
+    replication
+        .propagate(&Message::command(&[b"SET", b"foo", b"bar"]))
+        .await;
+    assert_eq!(
+        receiver.recv().await.unwrap().as_ref(),
+        b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n"
+    );
+    assert_eq!(replication.offset(), 31);
+
+    // Disconnected replicas are forgotten
+    drop(receiver);
+    replication.propagate(&Message::command(&[b"PING"])).await;
+    assert_eq!(replication.replica_count().await, 0);
+    assert_eq!(replication.offset(), 45);
+}
+
+#[tokio::test]
+async fn test_propagate_write() {
+    let replication = Replication::new(ServerMode::Master);
+    let mut receiver = replication.register_replica(b"?", -1).await.receiver;
+    let set = Message::command(&[b"SET", b"foo", b"bar"]);
+    let select = |index: &[u8]| {
+        let mut data = b"*2\r\n$6\r\nSELECT\r\n$1\r\n".to_vec();
+        data.extend_from_slice(index);
+        data.extend_from_slice(b"\r\n");
+        data
+    };
+    let set_bytes = b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n";
+
+    // Database is selected only when it changes
+    replication.propagate_write(0, &set).await;
+    assert_eq!(
+        receiver.recv().await.unwrap().as_ref(),
+        [select(b"0").as_slice(), set_bytes].concat()
+    );
+    replication.propagate_write(0, &set).await;
+    assert_eq!(receiver.recv().await.unwrap().as_ref(), set_bytes);
+    replication.propagate_write(2, &set).await;
+    assert_eq!(
+        receiver.recv().await.unwrap().as_ref(),
+        [select(b"2").as_slice(), set_bytes].concat()
+    );
+
+    // New replicas start from default database
+    let mut receiver = replication.register_replica(b"?", -1).await.receiver;
+    replication.propagate_write(2, &set).await;
+    assert_eq!(
+        receiver.recv().await.unwrap().as_ref(),
+        [select(b"2").as_slice(), set_bytes].concat()
+    );
+}
+
+#[tokio::test]
+async fn test_backlog() {
+    let replication = Replication::new(ServerMode::Master);
+    let replid = replication.replid().await;
+    let ping = Message::command(&[b"PING"]);
+
+    // Backlog is created when first replica connects
+    replication.propagate(&ping).await;
+    assert!(matches!(
+        replication.info().await,
+        Response::InfoReplication {
+            master_repl_offset: 14,
+            repl_backlog_active: 0,
+            ..
+        }
+    ));
+    let _link = replication.register_replica(b"?", -1).await;
+
+    replication.set_backlog_size(20).await;
+    for _ in 0..3 {
+        replication.propagate(&ping).await;
+    }
+    assert_eq!(
+        replication.info().await,
+        Response::InfoReplication {
+            role: ServerMode::Master,
+            master_replid: replid.clone(),
+            master_repl_offset: 56,
+            repl_backlog_active: 1,
+            repl_backlog_size: 20,
+            repl_backlog_first_byte_offset: 37,
+            repl_backlog_histlen: 20,
+        }
+    );
+
+    // Offset still in backlog
+    let link = replication.register_replica(replid.as_bytes(), 43).await;
+    assert_eq!(
+        link.sync,
+        ReplicaSync::Partial {
+            replid: replid.clone(),
+            missing: b"*1\r\n$4\r\nPING\r\n".to_vec(),
+        }
+    );
+    let link = replication.register_replica(replid.as_bytes(), 57).await;
+    assert_eq!(
+        link.sync,
+        ReplicaSync::Partial {
+            replid: replid.clone(),
+            missing: vec![],
+        }
+    );
+
+    // Offset out of backlog or unknown replication ID
+    for (psync_replid, psync_offset) in [
+        (replid.as_bytes(), 36),
+        (replid.as_bytes(), 58),
+        (b"foo".as_slice(), 43),
+    ] {
+        let link = replication
+            .register_replica(psync_replid, psync_offset)
+            .await;
+        assert_eq!(
+            link.sync,
+            ReplicaSync::Full {
+                replid: replid.clone(),
+                offset: 56,
+            }
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_wait() {
+    let replication = Arc::new(Replication::new(ServerMode::Master));
+
+    // Nothing to acknowledge yet
+    let mut link = replication.register_replica(b"?", -1).await;
+    assert_eq!(replication.wait(1, 100).await, 1);
+    assert_eq!(replication.wait(2, 100).await, 1);
+    while link.receiver.try_recv().is_ok() {}
+
+    // Replica must acknowledge write
+    replication
+        .propagate(&Message::command(&[b"SET", b"foo", b"bar"]))
+        .await;
+    let wait = tokio::spawn({
+        let replication = replication.clone();
+        async move { replication.wait(1, 5000).await }
+    });
+
+    assert_eq!(
+        link.receiver.recv().await.unwrap().as_ref(),
+        b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n"
+    );
+    assert_eq!(
+        link.receiver.recv().await.unwrap().as_ref(),
+        b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n"
+    );
+    link.acknowledger.ack(10);
+    tokio::task::yield_now().await;
+    assert!(!wait.is_finished());
+    link.acknowledger.ack(68);
+    assert_eq!(wait.await.unwrap(), 1);
+
+    // Timeout expires without acknowledgement
+    replication.propagate(&Message::command(&[b"PING"])).await;
+    assert_eq!(replication.wait(1, 50).await, 0);
+
+    // Disconnected replica no longer counts, even before next write
+    link.acknowledger.ack(replication.offset());
+    assert_eq!(replication.wait(1, 50).await, 1);
+    replication.unregister_replica(&link.acknowledger).await;
+    assert_eq!(replication.replica_count().await, 0);
+    assert_eq!(replication.wait(1, 50).await, 0);
+
+    // Channel is closed once pending data is received
+    while link.receiver.recv().await.is_some() {}
+}
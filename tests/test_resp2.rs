@@ -119,7 +119,7 @@ async fn test_binary() {
 #[tokio::test]
 async fn test_array() {
     // Valid empty / null
-    assert_eq!(decode(b"*-1\r\n").await, Message::Null);
+    assert_eq!(decode(b"*-1\r\n").await, Message::NullArray);
     assert_eq!(decode(b"*0\r\n").await, Message::Array(vec![]));
 
     // Valid
@@ -174,6 +174,7 @@ async fn test_write() {
 
     // Null
     check(Message::Null, "$-1\r\n").await;
+    check(Message::NullArray, "*-1\r\n").await;
 
     // Array
     check(Message::Array(vec![]), "*0\r\n").await;
@@ -0,0 +1,616 @@
+use std::{future, sync::Arc, time::Duration};
+
+use redis_starter_rust::{
+    database::{ListEnd, SetOptions, StreamId, StreamTrim, StreamTrimOptions, XAddId, XAddOptions},
+    error::MiniRedisError,
+    rdb::RedisString,
+    request::Request,
+    resp2::Message,
+    response::Response,
+    server::{Server, Session},
+    ServerMode,
+};
+use tokio::time;
+
+#[tokio::test]
+async fn test_select() {
+    let server = Server::new(ServerMode::Master, 2);
+    let mut session = Session::default();
+
+    let set = Request::Set(
+        RedisString::new(b"foo"),
+        RedisString::new(b"bar"),
+        SetOptions::default(),
+    );
+    let get = || Request::Get(RedisString::new(b"foo"));
+
+    assert_eq!(server.execute(set, &mut session).await, Response::Ok);
+    assert_eq!(
+        server.execute(Request::Select(1), &mut session).await,
+        Response::Ok
+    );
+    assert_eq!(
+        server.execute(get(), &mut session).await,
+        Response::NoContent
+    );
+    assert_eq!(
+        server.execute(Request::Select(2), &mut session).await,
+        Response::Error("ERR DB index is out of range".to_string())
+    );
+    let select = Request::parse(&Message::command(&[b"SELECT", b"-1"]));
+    assert_eq!(
+        server.execute(select, &mut session).await,
+        Response::Error("ERR DB index is out of range".to_string())
+    );
+    assert_eq!(session.db, 1);
+
+    // Other sessions are not affected
+    let mut other_session = Session::default();
+    assert_eq!(
+        server.execute(get(), &mut other_session).await,
+        Response::Content(RedisString::new(b"bar"))
+    );
+}
+
+#[tokio::test]
+async fn test_set_options() {
+    let server = Server::new(ServerMode::Master, 1);
+    let mut session = Session::default();
+    let set = |args: &[&[u8]]| {
+        let mut command = vec![b"SET".as_slice(), b"foo"];
+        command.extend(args);
+        Request::parse(&Message::command(&command))
+    };
+    let content = |value: &[u8]| Response::Content(RedisString::new(value));
+
+    // Conditions and previous value
+    assert_eq!(
+        server.execute(set(&[b"a", b"nx"]), &mut session).await,
+        Response::Ok
+    );
+    assert_eq!(
+        server.execute(set(&[b"b", b"NX"]), &mut session).await,
+        Response::NoContent
+    );
+    assert_eq!(
+        server
+            .execute(set(&[b"b", b"GET", b"XX"]), &mut session)
+            .await,
+        content(b"a")
+    );
+    assert_eq!(
+        server
+            .execute(set(&[b"c", b"NX", b"GET"]), &mut session)
+            .await,
+        content(b"b")
+    );
+
+    // Expiry is kept only when requested
+    let db = &server.databases[0];
+    let expire_at = || async { db.remove(RedisString::new(b"foo")).await.unwrap().1 };
+    server
+        .execute(set(&[b"a", b"PX", b"100000"]), &mut session)
+        .await;
+    assert!(expire_at().await.is_some());
+    server
+        .execute(set(&[b"a", b"EXAT", b"4000000000"]), &mut session)
+        .await;
+    server.execute(set(&[b"b", b"KEEPTTL"]), &mut session).await;
+    assert_eq!(expire_at().await, Some(4_000_000_000_000));
+    server
+        .execute(set(&[b"a", b"EX", b"100"]), &mut session)
+        .await;
+    server.execute(set(&[b"b"]), &mut session).await;
+    assert_eq!(expire_at().await, None);
+
+    // Syntax errors
+    let invalid = Request::Invalid;
+    assert_eq!(
+        set(&[b"a", b"NX", b"XX"]),
+        invalid(MiniRedisError::SyntaxError)
+    );
+    assert_eq!(
+        set(&[b"a", b"EX", b"1", b"PX", b"1"]),
+        invalid(MiniRedisError::SyntaxError)
+    );
+    assert_eq!(
+        set(&[b"a", b"KEEPTTL", b"EX", b"1"]),
+        invalid(MiniRedisError::SyntaxError)
+    );
+    assert_eq!(set(&[b"a", b"PX"]), invalid(MiniRedisError::SyntaxError));
+    assert_eq!(
+        set(&[b"a", b"EX", b"soon"]),
+        invalid(MiniRedisError::NotInteger)
+    );
+    assert_eq!(
+        set(&[b"a", b"PX", b"0"]),
+        invalid(MiniRedisError::InvalidExpireTime("set".to_string()))
+    );
+    assert_eq!(
+        server
+            .execute(set(&[b"a", b"EX", b"-5"]), &mut session)
+            .await,
+        Response::Error("ERR invalid expire time in 'set' command".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_expire() {
+    let server = Server::new(ServerMode::Master, 1);
+    let mut session = Session::default();
+    let run = |command: &[&[u8]]| Request::parse(&Message::command(command));
+
+    // Missing key is left untouched
+    assert_eq!(
+        server
+            .execute(run(&[b"EXPIRE", b"foo", b"100"]), &mut session)
+            .await,
+        Response::Integer(0)
+    );
+    assert_eq!(
+        server.execute(run(&[b"TTL", b"foo"]), &mut session).await,
+        Response::Integer(-2)
+    );
+    assert_eq!(server.databases[0].snapshot().await.expiry.len(), 0);
+
+    // Conditions
+    server.databases[0].set(b"foo", b"bar").await;
+    assert_eq!(
+        server.execute(run(&[b"TTL", b"foo"]), &mut session).await,
+        Response::Integer(-1)
+    );
+    for (command, expected) in [
+        (&[b"EXPIRE".as_slice(), b"foo", b"100", b"XX"][..], 0),
+        (&[b"EXPIRE", b"foo", b"100", b"GT"], 0),
+        (&[b"EXPIRE", b"foo", b"100", b"NX"], 1),
+        (&[b"EXPIRE", b"foo", b"200", b"NX"], 0),
+        (&[b"EXPIRE", b"foo", b"50", b"GT"], 0),
+        (&[b"PEXPIRE", b"foo", b"50000", b"LT"], 1),
+        (&[b"EXPIREAT", b"foo", b"4000000000", b"XX", b"GT"], 1),
+    ] {
+        assert_eq!(
+            server.execute(run(command), &mut session).await,
+            Response::Integer(expected)
+        );
+    }
+    assert_eq!(
+        server
+            .execute(run(&[b"EXPIRETIME", b"foo"]), &mut session)
+            .await,
+        Response::Integer(4_000_000_000)
+    );
+    assert_eq!(
+        server
+            .execute(run(&[b"PEXPIRETIME", b"foo"]), &mut session)
+            .await,
+        Response::Integer(4_000_000_000_000)
+    );
+
+    // Persist
+    assert_eq!(
+        server
+            .execute(run(&[b"PERSIST", b"foo"]), &mut session)
+            .await,
+        Response::Integer(1)
+    );
+    assert_eq!(
+        server
+            .execute(run(&[b"PERSIST", b"foo"]), &mut session)
+            .await,
+        Response::Integer(0)
+    );
+    assert_eq!(
+        server.execute(run(&[b"PTTL", b"foo"]), &mut session).await,
+        Response::Integer(-1)
+    );
+
+    // Past expiry removes key
+    assert_eq!(
+        server
+            .execute(run(&[b"PEXPIREAT", b"foo", b"-1"]), &mut session)
+            .await,
+        Response::Integer(1)
+    );
+    assert!(!server.databases[0].exists(b"foo").await);
+
+    // Invalid arguments
+    assert_eq!(
+        run(&[b"EXPIRE", b"foo", b"1", b"NX", b"LT"]),
+        Request::Invalid(MiniRedisError::IncompatibleOptions("NX and XX, GT or LT"))
+    );
+    assert_eq!(
+        run(&[b"EXPIRE", b"foo", b"1", b"GT", b"LT"]),
+        Request::Invalid(MiniRedisError::IncompatibleOptions("GT and LT"))
+    );
+    assert_eq!(
+        run(&[b"EXPIRE", b"foo", b"1", b"CH"]),
+        Request::Invalid(MiniRedisError::UnsupportedOption("CH".to_string()))
+    );
+    assert_eq!(
+        run(&[b"EXPIRE", b"foo", b"9223372036854775807"]),
+        Request::Invalid(MiniRedisError::InvalidExpireTime("expire".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_propagate_absolute_expiry() {
+    let server = Server::new(ServerMode::Master, 1);
+    let mut session = Session::default();
+    let mut receiver = server.replication.register_replica(b"?", -1).await.receiver;
+
+    for command in [
+        &[b"SET".as_slice(), b"foo", b"bar", b"EX", b"100"][..],
+        &[b"EXPIRE", b"foo", b"200"],
+        &[b"PEXPIRE", b"foo", b"300000"],
+        &[b"GETEX", b"foo", b"PX", b"400000"],
+    ] {
+        let msg = Message::command(command);
+        server
+            .execute_and_propagate(Request::parse(&msg), &msg, &mut session)
+            .await;
+        let expire_at = match server
+            .execute(
+                Request::parse(&Message::command(&[b"PEXPIRETIME", b"foo"])),
+                &mut session,
+            )
+            .await
+        {
+            Response::Integer(expire_at) => expire_at.to_string(),
+            response => panic!("Unexpected response {response:?}"),
+        };
+
+        // Replicas get the same absolute expiry as master
+        let expected = if command[0] == b"SET" {
+            Message::command(&[b"SET", b"foo", b"bar", b"PXAT", expire_at.as_bytes()])
+        } else {
+            Message::command(&[b"PEXPIREAT", b"foo", expire_at.as_bytes()])
+        };
+        let data = receiver.recv().await.unwrap();
+        assert!(data.ends_with(&expected.to_bytes().await));
+    }
+}
+
+#[tokio::test]
+async fn test_active_expire() {
+    let server = Server::new(ServerMode::Master, 2);
+    let mut session = Session::default();
+
+    // Short lived keys that are never read again
+    for (index, db) in server.databases.iter().enumerate() {
+        for i in 0..100 {
+            let key = format!("key:{i}");
+            db.set(key.as_bytes(), b"value").await;
+            if i % 10 > 0 || index > 0 {
+                db.expire_in_millis(key.as_bytes(), 10).await;
+            }
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    server.active_expire_cycle(Duration::from_secs(1)).await;
+    assert_eq!(server.databases[0].expire_sample(20).await, (0, 0));
+    assert_eq!(server.databases[1].expire_sample(20).await, (0, 0));
+    assert_eq!(server.databases[0].keys().await.len(), 10);
+    assert_eq!(server.expired_keys(), 190);
+    assert_eq!(
+        server
+            .execute(
+                Request::parse(&Message::command(&[b"INFO", b"stats"])),
+                &mut session
+            )
+            .await,
+        Response::InfoStats { expired_keys: 190 }
+    );
+}
+
+#[tokio::test]
+async fn test_info() {
+    let server = Server::new(ServerMode::Master, 1);
+    let mut session = Session::default();
+
+    for command in [
+        &[b"INFO".as_slice()][..],
+        &[b"INFO", b"all"],
+        &[b"INFO", b"DEFAULT"],
+        &[b"INFO", b"everything"],
+    ] {
+        let response = server
+            .execute(Request::parse(&Message::command(command)), &mut session)
+            .await;
+        assert!(matches!(
+            &response,
+            Response::InfoSections(sections) if matches!(
+                sections[..],
+                [
+                    Response::InfoReplication { .. },
+                    Response::InfoStats { expired_keys: 0 }
+                ]
+            )
+        ));
+
+        let mut output = Vec::new();
+        response.write(&mut output).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\nrole:master\n"));
+        assert!(output.ends_with("\n\nexpired_keys:0\n\r\n"));
+    }
+}
+
+#[tokio::test]
+async fn test_move_swap_flush() {
+    let server = Server::new(ServerMode::Master, 3);
+    let mut session = Session::default();
+    let key = || RedisString::new(b"foo");
+
+    server.databases[0].set(key(), b"bar").await;
+    assert_eq!(
+        server.execute(Request::Move(key(), 1), &mut session).await,
+        Response::Integer(1)
+    );
+    assert_eq!(
+        server.execute(Request::Move(key(), 1), &mut session).await,
+        Response::Integer(0)
+    );
+    assert_eq!(
+        server.execute(Request::Move(key(), 0), &mut session).await,
+        Response::Error("ERR source and destination objects are the same".to_string())
+    );
+    for command in [&[&b"MOVE"[..], b"foo", b"3"][..], &[b"SWAPDB", b"0", b"-1"]] {
+        assert_eq!(
+            server
+                .execute(Request::parse(&Message::command(command)), &mut session)
+                .await,
+            Response::Error("ERR DB index is out of range".to_string())
+        );
+    }
+    assert_eq!(
+        server.databases[1].get(key()).await,
+        Ok(Some(RedisString::new(b"bar")))
+    );
+
+    assert_eq!(
+        server.execute(Request::SwapDb(1, 2), &mut session).await,
+        Response::Ok
+    );
+    assert_eq!(server.databases[1].get(key()).await, Ok(None));
+    assert_eq!(
+        server.databases[2].get(key()).await,
+        Ok(Some(RedisString::new(b"bar")))
+    );
+
+    // Flush only selected database
+    server.databases[0].set(key(), b"bar").await;
+    assert_eq!(
+        server.execute(Request::FlushDb, &mut session).await,
+        Response::Ok
+    );
+    assert_eq!(server.databases[0].get(key()).await, Ok(None));
+    assert!(server.databases[2].exists(key()).await);
+
+    assert_eq!(
+        server.execute(Request::FlushAll, &mut session).await,
+        Response::Ok
+    );
+    assert_eq!(server.databases[2].get(key()).await, Ok(None));
+}
+
+#[tokio::test]
+async fn test_blocking_pop() {
+    let server = Arc::new(Server::new(ServerMode::Master, 1));
+    let mut session = Session::default();
+    let key = RedisString::new(b"queue");
+    let blpop = |timeout| Request::BLPop(vec![RedisString::new(b"other"), key.clone()], timeout);
+
+    // Clients are served in the order they blocked
+    let mut handles = Vec::new();
+    for _ in 0..2 {
+        let server = server.clone();
+        let request = blpop(0);
+        handles.push(tokio::spawn(async move {
+            let session = Session::default();
+            server
+                .execute_blocking(request, &session, future::pending())
+                .await
+        }));
+        time::sleep(Duration::from_millis(20)).await;
+    }
+
+    // Cancelled client does not consume elements
+    let cancelled = server.execute_blocking(blpop(0), &session, future::ready(()));
+    assert_eq!(cancelled.await, None);
+
+    let push = Request::RPush(key.clone(), vec![RedisString::new(b"1")]);
+    server.execute(push, &mut session).await;
+    let first = handles.remove(0).await.unwrap();
+    assert_eq!(
+        first,
+        Some(Response::Array(vec![
+            Response::Content(key.clone()),
+            Response::Content(RedisString::new(b"1")),
+        ]))
+    );
+    assert!(!handles[0].is_finished());
+
+    let push = Request::LPush(key.clone(), vec![RedisString::new(b"2")]);
+    server.execute(push, &mut session).await;
+    assert!(handles.remove(0).await.unwrap().is_some());
+
+    // Timeout replies a null array, but a nil element for BLMOVE
+    let response = server.execute_blocking(blpop(50), &session, future::pending());
+    let response = response.await.unwrap();
+    let mut output = Vec::new();
+    response.write(&mut output).await.unwrap();
+    assert_eq!(output, b"*-1\r\n");
+
+    let blmove = Request::BLMove(key.clone(), key.clone(), ListEnd::Left, ListEnd::Right, 50);
+    let response = server.execute_blocking(blmove, &session, future::pending());
+    let response = response.await.unwrap();
+    let mut output = Vec::new();
+    response.write(&mut output).await.unwrap();
+    assert_eq!(output, b"$-1\r\n");
+
+    // Not blocking when executed directly
+    assert_eq!(
+        server.execute(blpop(0), &mut session).await,
+        Response::NullArray
+    );
+}
+
+#[tokio::test]
+async fn test_blocking_move_swap() {
+    let server = Arc::new(Server::new(ServerMode::Master, 3));
+    let key = RedisString::new(b"queue");
+    let block_on_db1 = || {
+        let server = server.clone();
+        let request = Request::BLPop(vec![key.clone()], 1000);
+        tokio::spawn(async move {
+            let session = Session { db: 1 };
+            server
+                .execute_blocking(request, &session, future::pending())
+                .await
+        })
+    };
+    let served = |element: &[u8]| {
+        Some(Response::Array(vec![
+            Response::Content(key.clone()),
+            Response::Content(RedisString::new(element)),
+        ]))
+    };
+
+    // Moved list is served to client blocked in target database
+    let handle = block_on_db1();
+    time::sleep(Duration::from_millis(20)).await;
+    let mut session = Session::default();
+    let push = Request::RPush(key.clone(), vec![RedisString::new(b"a")]);
+    server.execute(push, &mut session).await;
+    assert_eq!(
+        server
+            .execute(Request::Move(key.clone(), 1), &mut session)
+            .await,
+        Response::Integer(1)
+    );
+    assert_eq!(handle.await.unwrap(), served(b"a"));
+
+    // As well as list swapped in
+    let handle = block_on_db1();
+    time::sleep(Duration::from_millis(20)).await;
+    let mut session = Session { db: 2 };
+    let push = Request::RPush(key.clone(), vec![RedisString::new(b"b")]);
+    server.execute(push, &mut session).await;
+    assert_eq!(
+        server.execute(Request::SwapDb(1, 2), &mut session).await,
+        Response::Ok
+    );
+    assert_eq!(handle.await.unwrap(), served(b"b"));
+}
+
+#[tokio::test]
+async fn test_blocking_stream_read() {
+    let server = Arc::new(Server::new(ServerMode::Master, 1));
+    let mut session = Session::default();
+    let key = RedisString::new(b"events");
+    let xadd = |ms| {
+        let fields = vec![(RedisString::new(b"n"), RedisString::from_integer(ms as i64))];
+        let id = XAddId::Explicit(StreamId { ms, seq: 0 });
+        Request::XAdd(key.clone(), XAddOptions::default(), id, fields)
+    };
+    let xread = |block| Request::XRead(vec![(key.clone(), None)], None, block);
+
+    server.execute(xadd(1), &mut session).await;
+
+    // Only entries added while blocked are returned
+    let handle = {
+        let server = server.clone();
+        let request = xread(Some(0));
+        tokio::spawn(async move {
+            let session = Session::default();
+            server
+                .execute_blocking(request, &session, future::pending())
+                .await
+        })
+    };
+    time::sleep(Duration::from_millis(20)).await;
+    assert!(!handle.is_finished());
+
+    server.execute(xadd(2), &mut session).await;
+    assert_eq!(
+        handle.await.unwrap(),
+        Some(Response::Array(vec![Response::Array(vec![
+            Response::Content(key.clone()),
+            Response::Array(vec![Response::Array(vec![
+                Response::Content(RedisString::new(b"2-0")),
+                Response::Array(vec![
+                    Response::Content(RedisString::new(b"n")),
+                    Response::Content(RedisString::new(b"2")),
+                ]),
+            ])]),
+        ])]))
+    );
+
+    // Timeout
+    let response = server.execute_blocking(xread(Some(50)), &session, future::pending());
+    assert_eq!(response.await, Some(Response::NullArray));
+
+    // Not blocking when executed directly
+    assert_eq!(
+        server.execute(xread(None), &mut session).await,
+        Response::NullArray
+    );
+}
+
+#[tokio::test]
+async fn test_random_count_range() {
+    let run = |command: &[&[u8]]| Request::parse(&Message::command(command));
+    let out_of_range = Request::Invalid(MiniRedisError::ValueOutOfRange);
+
+    assert_eq!(
+        run(&[b"HRANDFIELD", b"h", b"-9223372036854775808"]),
+        out_of_range
+    );
+    assert_eq!(
+        run(&[b"HRANDFIELD", b"h", b"-4611686018427387904", b"WITHVALUES"]),
+        out_of_range
+    );
+    assert_eq!(
+        run(&[b"HRANDFIELD", b"h", b"-4611686018427387903", b"WITHVALUES"]),
+        Request::HRandField(RedisString::new(b"h"), Some(-4611686018427387903), true)
+    );
+    assert_eq!(
+        run(&[b"SRANDMEMBER", b"s", b"-9223372036854775808"]),
+        out_of_range
+    );
+    assert_eq!(
+        run(&[b"SRANDMEMBER", b"s", b"-9223372036854775807"]),
+        Request::SRandMember(RedisString::new(b"s"), Some(-9223372036854775807))
+    );
+}
+
+#[test]
+fn test_stream_trim_options() {
+    let run = |command: &[&[u8]]| Request::parse(&Message::command(command));
+    let key = || RedisString::new(b"s");
+
+    assert_eq!(
+        run(&[b"XTRIM", b"s", b"MAXLEN", b"~", b"10", b"LIMIT", b"5"]),
+        Request::XTrim(
+            key(),
+            StreamTrimOptions {
+                trim: StreamTrim::MaxLen(10),
+                approximate: true,
+                limit: 5,
+            }
+        )
+    );
+    assert_eq!(
+        run(&[b"XTRIM", b"s", b"MINID", b"=", b"5-0"]),
+        Request::XTrim(key(), StreamTrim::MinId(StreamId { ms: 5, seq: 0 }).into())
+    );
+    assert_eq!(
+        run(&[b"XTRIM", b"s", b"MAXLEN", b"10", b"LIMIT", b"5"]),
+        Request::Invalid(MiniRedisError::StreamLimitWithoutApprox)
+    );
+    assert_eq!(
+        run(&[b"XADD", b"s", b"MAXLEN", b"=", b"1", b"LIMIT", b"5", b"*", b"f", b"v"]),
+        Request::Invalid(MiniRedisError::StreamLimitWithoutApprox)
+    );
+}